@@ -2,8 +2,10 @@
 #![allow(unexpected_cfgs)]
 
     use ink::prelude::string::String;
+    use ink::prelude::vec::Vec;
 
-    /// Roles posibles de usuario.
+    /// Roles posibles de usuario. Se usan para registrarse y en el evento `RolActualizado`;
+    /// lo que se guarda son los `Permisos` equivalentes.
     #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
     pub enum RolUsuario {
@@ -20,18 +22,414 @@
         Enviada,
         Recibida,
         Cancelada,
+        /// Preventa a la espera de stock; pasa a `Pendiente` cuando `reponer_stock` la completa.
+        Preventa,
+        /// El comprador objetó la entrega digital; la resuelve el administrador.
+        EnDisputa,
+    }
+
+    /// Capacidades independientes de un usuario registrado. Siempre tiene al menos una.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Permisos {
+        pub comprar: bool,
+        pub vender: bool,
+    }
+
+    impl Permisos {
+        /// `true` si no habilita ninguna acción; no se admite para usuarios registrados.
+        pub fn vacios(&self) -> bool {
+            !self.comprar && !self.vender
+        }
+
+        /// Rol equivalente, para eventos y compatibilidad. Los permisos vacíos
+        /// nunca se guardan; si llegaran acá se informarían como `Vendedor`.
+        pub fn rol(&self) -> RolUsuario {
+            match (self.comprar, self.vender) {
+                (true, true) => RolUsuario::Ambos,
+                (true, false) => RolUsuario::Comprador,
+                (false, _) => RolUsuario::Vendedor,
+            }
+        }
+    }
+
+    impl From<RolUsuario> for Permisos {
+        fn from(rol: RolUsuario) -> Self {
+            Self {
+                comprar: matches!(rol, RolUsuario::Comprador | RolUsuario::Ambos),
+                vender: matches!(rol, RolUsuario::Vendedor | RolUsuario::Ambos),
+            }
+        }
     }
 
     /// Usuario del marketplace.
     #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
     pub struct Usuario {
+        pub direccion: AccountId,
+        pub permisos: Permisos,
+        /// Suma de las calificaciones (1 a 5) recibidas como comprador.
+        pub reputacion_como_comprador: u32,
+        /// Suma de las calificaciones (1 a 5) recibidas como vendedor.
+        pub reputacion_como_vendedor: u32,
+        pub calificaciones_como_comprador: u32,
+        pub calificaciones_como_vendedor: u32,
+        pub perfil: PerfilUsuario,
+        /// Verificación emitida por un verificador; puede estar vencida.
+        pub verificacion: Option<Verificacion>,
+    }
+
+    /// Verificación de identidad o de negocio de un usuario.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Verificacion {
+        /// Nivel de verificación (1 o más); su significado lo define el verificador.
+        pub nivel: u8,
+        /// Timestamp (ms) desde el que deja de valer.
+        pub expira_en: Timestamp,
+        pub verificador: AccountId,
+    }
+
+    /// Usuario con el formato de la versión desplegada originalmente (antes de `Permisos`,
+    /// perfiles, verificación y conteo de calificaciones). Se lee al vuelo y se reescribe
+    /// con el formato actual la primera vez que el usuario se modifica.
+    ///
+    /// Esa versión no tiene `set_code_hash` y la raíz de `Marketplace` sumó campos desde
+    /// entonces, así que pasar a esta versión es un nuevo despliegue (ver README); este
+    /// formato solo se encuentra si las celdas de usuarios se trasladan tal cual.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct UsuarioLegacy {
         pub direccion: AccountId,
         pub rol: RolUsuario,
+        /// Suma de calificaciones recibidas, sin la cantidad.
         pub reputacion_como_comprador: u32,
         pub reputacion_como_vendedor: u32,
     }
 
+    impl From<UsuarioLegacy> for Usuario {
+        /// Sin la cantidad de calificaciones no se puede sacar un promedio de las sumas
+        /// guardadas, así que la reputación empieza de cero.
+        fn from(legacy: UsuarioLegacy) -> Self {
+            Self {
+                direccion: legacy.direccion,
+                permisos: Permisos::from(legacy.rol),
+                reputacion_como_comprador: 0,
+                reputacion_como_vendedor: 0,
+                calificaciones_como_comprador: 0,
+                calificaciones_como_vendedor: 0,
+                perfil: PerfilUsuario::default(),
+                verificacion: None,
+            }
+        }
+    }
+
+    /// Clave de almacenamiento de los usuarios con el formato previo a `Permisos`: la que
+    /// las versiones anteriores derivaban del campo `usuarios`.
+    pub const CLAVE_USUARIOS_LEGACY: u32 = ink::primitives::KeyComposer::from_str("Marketplace::usuarios");
+    /// Clave de almacenamiento de los usuarios con el formato actual.
+    pub const CLAVE_USUARIOS: u32 = ink::primitives::KeyComposer::from_str("Marketplace::usuarios_permisos");
+
+    impl Usuario {
+        /// `true` si tiene una verificación vigente en `ahora`.
+        pub fn verificado(&self, ahora: Timestamp) -> bool {
+            self.verificacion.as_ref().is_some_and(|v| ahora < v.expira_en)
+        }
+    }
+
+    /// Largo máximo (en bytes) del nombre visible de un usuario.
+    pub const MAX_LARGO_NOMBRE_VISIBLE: u32 = 50;
+
+    /// Datos de perfil que el usuario carga con `actualizar_perfil`.
+    #[derive(Debug, Clone, Default, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct PerfilUsuario {
+        /// Nombre a mostrar; vacío si no se cargó.
+        pub nombre_visible: String,
+        /// CID de la imagen de perfil.
+        pub avatar: Option<Cid>,
+        /// Hash de un canal de contacto (mail, usuario de mensajería); solo sirve
+        /// para que la contraparte verifique el dato que recibió por fuera.
+        pub hash_contacto: Option<[u8; 32]>,
+        /// Región de envío como código ISO 3166-1 alfa-2 en mayúsculas (p. ej. `*b"AR"`).
+        pub region_envio: Option<[u8; 2]>,
+    }
+
+    /// Campos públicos de un usuario (sin el hash de contacto).
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct PerfilPublico {
+        pub direccion: AccountId,
+        pub permisos: Permisos,
+        pub nombre_visible: String,
+        pub avatar: Option<Cid>,
+        pub region_envio: Option<[u8; 2]>,
+    }
+
+    /// Factor de punto fijo de los promedios de reputación (`450` = 4,50 estrellas).
+    pub const PRECISION_PROMEDIO: u32 = 100;
+
+    impl Usuario {
+        /// Promedio como comprador multiplicado por `PRECISION_PROMEDIO` (0 sin calificaciones).
+        pub fn promedio_como_comprador(&self) -> u32 {
+            promedio(self.reputacion_como_comprador, self.calificaciones_como_comprador)
+        }
+
+        /// Promedio como vendedor multiplicado por `PRECISION_PROMEDIO` (0 sin calificaciones).
+        pub fn promedio_como_vendedor(&self) -> u32 {
+            promedio(self.reputacion_como_vendedor, self.calificaciones_como_vendedor)
+        }
+    }
+
+    fn promedio(suma: u32, cantidad: u32) -> u32 {
+        if cantidad == 0 {
+            return 0;
+        }
+        (u64::from(suma) * u64::from(PRECISION_PROMEDIO) / u64::from(cantidad)) as u32
+    }
+
+    /// Reputación pública de un usuario, con promedios en punto fijo (ver `PRECISION_PROMEDIO`).
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct Reputacion {
+        pub promedio_como_comprador: u32,
+        pub calificaciones_como_comprador: u32,
+        pub promedio_como_vendedor: u32,
+        pub calificaciones_como_vendedor: u32,
+        /// Promedio ponderado por importe y antigüedad (ver `ReputacionPonderada`).
+        pub ponderada_como_comprador: u32,
+        pub ponderada_como_vendedor: u32,
+    }
+
+    /// Bloques tras los cuales una calificación pesa la mitad (unos 90 días de bloques de 6 s).
+    pub const VIDA_MEDIA_REPUTACION: BlockNumber = 1_296_000;
+
+    /// Peso máximo de una calificación ponderada. Las órdenes de mayor importe pesan lo
+    /// mismo, así que una sola orden grande (p. ej. consigo mismo) no fija el puntaje.
+    pub const PESO_MAXIMO_CALIFICACION: Balance = 1_000_000_000_000_000;
+
+    /// Acumulado de calificaciones ponderadas por el total de la orden, con decaimiento
+    /// exponencial según la antigüedad.
+    ///
+    /// En vez de recalcular todo, cada nueva calificación primero decae lo acumulado por
+    /// los bloques transcurridos desde `actualizado_en` y luego suma la nueva. Como el
+    /// decaimiento afecta igual a numerador y denominador, el puntaje no cambia si no
+    /// llegan calificaciones nuevas.
+    #[derive(Debug, Clone, Default, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct ReputacionPonderada {
+        /// Suma de puntaje * peso, ya decaída.
+        pub suma_ponderada: u128,
+        /// Suma de pesos, ya decaída.
+        pub peso_total: u128,
+        pub actualizado_en: BlockNumber,
+    }
+
+    impl ReputacionPonderada {
+        /// Agrega una calificación con el total de su orden como peso, entre 1 y
+        /// `PESO_MAXIMO_CALIFICACION`.
+        pub fn acumular(&mut self, puntaje: u32, total_orden: Balance, bloque: BlockNumber) {
+            let transcurrido = bloque.saturating_sub(self.actualizado_en);
+            self.suma_ponderada = decaer(self.suma_ponderada, transcurrido);
+            self.peso_total = decaer(self.peso_total, transcurrido);
+            let peso = total_orden.clamp(1, PESO_MAXIMO_CALIFICACION);
+            self.suma_ponderada = self.suma_ponderada.saturating_add(peso.saturating_mul(puntaje as u128));
+            self.peso_total = self.peso_total.saturating_add(peso);
+            self.actualizado_en = bloque;
+        }
+
+        /// Promedio ponderado multiplicado por `PRECISION_PROMEDIO` (0 sin calificaciones).
+        pub fn puntaje(&self) -> u32 {
+            if self.peso_total == 0 {
+                return 0;
+            }
+            let precision = PRECISION_PROMEDIO as u128;
+            let entero = self.suma_ponderada / self.peso_total;
+            let resto = self.suma_ponderada % self.peso_total;
+            // resto < peso_total; si resto * precision no entra en u128, peso_total >= precision.
+            let fraccion = match resto.checked_mul(precision) {
+                Some(escalado) => escalado / self.peso_total,
+                None => resto / (self.peso_total / precision),
+            };
+            // Con sumas saturadas el cociente podría pasarse de 5 estrellas.
+            entero.saturating_mul(precision).saturating_add(fraccion).min(5 * precision) as u32
+        }
+    }
+
+    /// Aplica `bloques` de decaimiento: divide por 2 en cada vida media completa e
+    /// interpola linealmente (1 - f/2) la fracción restante.
+    fn decaer(valor: u128, bloques: BlockNumber) -> u128 {
+        let vidas = bloques / VIDA_MEDIA_REPUTACION;
+        if vidas >= 128 {
+            return 0;
+        }
+        let valor = valor >> vidas;
+        let resto = (bloques % VIDA_MEDIA_REPUTACION) as u128;
+        valor - valor / (2 * VIDA_MEDIA_REPUTACION as u128) * resto
+            - valor % (2 * VIDA_MEDIA_REPUTACION as u128) * resto / (2 * VIDA_MEDIA_REPUTACION as u128)
+    }
+
+    /// Máximo de variantes por producto.
+    pub const MAX_VARIANTES: u32 = 20;
+    /// Máximo de atributos (clave, valor) por variante.
+    pub const MAX_ATRIBUTOS_VARIANTE: u32 = 8;
+    /// Largo máximo (en bytes) de cada clave o valor de atributo.
+    pub const MAX_LARGO_ATRIBUTO: u32 = 32;
+
+    /// Máximo de referencias multimedia por producto.
+    pub const MAX_MEDIOS_POR_PRODUCTO: u32 = 10;
+
+    /// CID de IPFS en formato binario (v0 o v1) que apunta a una imagen o video.
+    pub type Cid = Vec<u8>;
+
+    /// Verifica que `bytes` sea un CID binario con digest de 32 bytes.
+    ///
+    /// Acepta CIDv0 (`0x12 0x20` + sha2-256) y CIDv1 (`0x01`, códec en varint y
+    /// multihash sha2-256 `0x12` o blake3 `0x1e`).
+    pub fn cid_valido(bytes: &[u8]) -> bool {
+        const LARGO_DIGEST: u8 = 0x20;
+        let multihash = match bytes {
+            [0x12, ..] => bytes,
+            [0x01, resto @ ..] => {
+                // Códec como varint sin signo de hasta 2 bytes
+                match resto {
+                    [c, mh @ ..] if c & 0x80 == 0 => mh,
+                    [c1, c2, mh @ ..] if c1 & 0x80 != 0 && c2 & 0x80 == 0 => mh,
+                    _ => return false,
+                }
+            }
+            _ => return false,
+        };
+        match multihash {
+            [codigo, largo, digest @ ..] => {
+                (*codigo == 0x12 || (*codigo == 0x1e && bytes[0] == 0x01))
+                    && *largo == LARGO_DIGEST
+                    && digest.len() == LARGO_DIGEST as usize
+            }
+            _ => false,
+        }
+    }
+
+    /// Variante de un producto (talle, color, ...) con stock propio.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Variante {
+        pub id: u32,
+        pub atributos: Vec<(String, String)>,
+        /// Si es `None` se usa el precio del producto.
+        pub precio: Option<Balance>,
+        pub cantidad: u32,
+    }
+
+    /// Datos para crear una variante.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct NuevaVariante {
+        pub atributos: Vec<(String, String)>,
+        pub precio: Option<Balance>,
+        pub cantidad: u32,
+    }
+
+    /// Máximo de productos por llamada a `publicar_productos_lote`.
+    pub const MAX_LOTE_PRODUCTOS: u32 = 50;
+
+    /// Datos de un producto a publicar en lote.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct NuevoProducto {
+        pub nombre: String,
+        pub descripcion: String,
+        pub precio: Balance,
+        pub cantidad: u32,
+        pub categoria: String,
+    }
+
+    /// Máximo de preventas en espera por producto.
+    pub const MAX_COLA_PREVENTA: u32 = 50;
+
+    /// Configuración de preventa de un producto sin variantes.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Preventa {
+        /// Si acepta nuevas preventas; las ya encoladas se siguen cumpliendo.
+        pub activa: bool,
+        /// Fecha (timestamp en ms) estimada de disponibilidad, informativa para el comprador.
+        pub disponible_desde: Timestamp,
+        /// Tope de unidades comprometidas sin stock.
+        pub maximo_unidades: u32,
+        /// Demanda comprometida sin stock: el stock "negativo" del producto.
+        pub unidades_comprometidas: u32,
+        /// Preventas en espera, en orden de llegada: (orden_id, unidades pendientes).
+        pub cola: Vec<(u32, u32)>,
+    }
+
+    /// Máximo de productos distintos en un paquete.
+    pub const MAX_COMPONENTES_PAQUETE: u32 = 10;
+
+    /// `producto_id` de las órdenes de paquetes; nunca se asigna a un producto.
+    pub const PRODUCTO_PAQUETE: u32 = u32::MAX;
+
+    /// Paquete de productos de un mismo vendedor que se vende a un precio propio.
+    /// No se puede modificar una vez creado.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Paquete {
+        pub id: u32,
+        pub vendedor: AccountId,
+        pub nombre: String,
+        /// (producto_id, unidades por paquete)
+        pub componentes: Vec<(u32, u32)>,
+        pub precio: Balance,
+    }
+
+    /// Tipo de producto según cómo se entrega.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum TipoProducto {
+        /// Requiere envío; el vendedor marca la orden como `Enviada`.
+        Fisico,
+        /// Licencias o descargas; se entrega con `entregar_contenido_digital`.
+        Digital,
+    }
+
+    /// Largo máximo (en bytes) del contenido cifrado de una entrega digital.
+    pub const MAX_LARGO_CONTENIDO_DIGITAL: u32 = 1024;
+
+    /// Clave pública X25519 con la que los vendedores cifran las entregas digitales.
+    pub type ClavePublica = [u8; 32];
+
+    /// Contenido de una orden digital, cifrado por el vendedor para el comprador.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct EntregaDigital {
+        /// Clave del comprador usada al cifrar (la registrada al momento de la entrega).
+        pub clave_destino: ClavePublica,
+        pub contenido_cifrado: Vec<u8>,
+    }
+
+    /// Forma en que se vende un producto.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum ModoVenta {
+        /// Se compra a `precio` con `crear_orden`.
+        PrecioFijo,
+        /// Se subasta el lote completo; ver `Subasta`.
+        Subasta,
+    }
+
+    /// Subasta inglesa de un producto: gana la puja más alta al llegar a `fin`.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Subasta {
+        pub precio_reserva: Balance,
+        /// Bloque a partir del cual no se aceptan pujas y se puede finalizar.
+        pub fin: BlockNumber,
+        pub mejor_postor: Option<AccountId>,
+        /// Monto retenido en el contrato para la mejor puja.
+        pub mejor_puja: Balance,
+        pub finalizada: bool,
+    }
+
     /// Estructura de Producto.
     #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
@@ -40,18 +438,166 @@
         pub nombre: String,
         pub descripcion: String,
         pub precio: Balance,
+        /// Stock total; si hay variantes es la suma de sus stocks.
         pub cantidad: u32,
         pub categoria: String,
         pub vendedor: AccountId,
+        pub variantes: Vec<Variante>,
+        /// Suma de los puntajes de todas las reseñas del producto.
+        pub suma_puntajes_resenas: u32,
+        pub cantidad_resenas: u32,
+        pub modo_venta: ModoVenta,
+        /// `false` si el vendedor lo despublicó: no se puede comprar ni ofertar.
+        pub activo: bool,
+        /// Timestamp (ms) en que vence la publicación; se extiende con `renovar_publicacion`.
+        pub expira_en: Timestamp,
+        pub tipo: TipoProducto,
     }
 
     impl Producto {
-        /// Nuevo producto.
+        /// Nuevo producto, sin vencimiento.
         pub fn nuevo(id: u32, nombre: String, descripcion: String, precio: Balance, cantidad: u32, categoria: String, vendedor: AccountId) -> Self {
-            Self { id, nombre, descripcion, precio, cantidad, categoria, vendedor }
+            Self { id, nombre, descripcion, precio, cantidad, categoria, vendedor, variantes: Vec::new(), suma_puntajes_resenas: 0, cantidad_resenas: 0, modo_venta: ModoVenta::PrecioFijo, activo: true, expira_en: Timestamp::MAX, tipo: TipoProducto::Fisico }
+        }
+
+        /// Precio unitario y stock disponible de la variante pedida
+        /// (o del producto, si no tiene variantes).
+        pub fn precio_y_stock(&self, variante_id: Option<u32>) -> Result<(Balance, u32), SistemaError> {
+            match (self.variantes.is_empty(), variante_id) {
+                (true, None) => Ok((self.precio, self.cantidad)),
+                (true, Some(_)) => Err(SistemaError::VarianteNoExiste),
+                (false, None) => Err(SistemaError::VarianteRequerida),
+                (false, Some(id)) => {
+                    let v = self.variantes.get(id as usize).ok_or(SistemaError::VarianteNoExiste)?;
+                    Ok((v.precio.unwrap_or(self.precio), v.cantidad))
+                }
+            }
+        }
+
+        /// `true` si se cobra el precio base: sin variante o con una variante sin precio
+        /// propio. Solo entonces rigen los tramos, que se validan contra ese precio.
+        pub fn usa_precio_base(&self, variante_id: Option<u32>) -> bool {
+            variante_id
+                .and_then(|id| self.variantes.get(id as usize))
+                .is_none_or(|v| v.precio.is_none())
+        }
+
+        /// Descuenta unidades de la variante (si corresponde) y del total.
+        fn descontar_stock(&mut self, variante_id: Option<u32>, cantidad: u32) {
+            if let Some(v) = variante_id.and_then(|id| self.variantes.get_mut(id as usize)) {
+                v.cantidad = v.cantidad.saturating_sub(cantidad);
+            }
+            self.cantidad = self.cantidad.saturating_sub(cantidad);
         }
+
+        /// Devuelve unidades a la variante (si corresponde) y al total.
+        fn devolver_stock(&mut self, variante_id: Option<u32>, cantidad: u32) {
+            if let Some(v) = variante_id.and_then(|id| self.variantes.get_mut(id as usize)) {
+                v.cantidad = v.cantidad.saturating_add(cantidad);
+            }
+            self.cantidad = self.cantidad.saturating_add(cantidad);
+        }
+    }
+
+    /// Largo máximo (en bytes) del texto de una reseña.
+    pub const MAX_LARGO_RESENA: u32 = 280;
+    /// Máximo de reseñas devueltas por página.
+    pub const MAX_PAGINA_RESENAS: u32 = 50;
+
+    /// Contenido de una reseña: texto corto o hash de un documento externo.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum ContenidoResena {
+        Texto(String),
+        Hash([u8; 32]),
+    }
+
+    /// Reseña de un producto dejada por el comprador de una orden recibida.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Resena {
+        pub orden_id: u32,
+        pub autor: AccountId,
+        /// Valor entre 1 y 5.
+        pub puntaje: u8,
+        pub contenido: ContenidoResena,
+    }
+
+    /// Máximo de calificaciones devueltas por página.
+    pub const MAX_PAGINA_CALIFICACIONES: u32 = 50;
+    /// Largo máximo (en bytes) del comentario de una calificación y de su respuesta.
+    pub const MAX_LARGO_COMENTARIO_CALIFICACION: u32 = 280;
+
+    /// Lado de la orden que recibe una calificación.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum RolCalificado {
+        Comprador,
+        Vendedor,
+    }
+
+    /// Calificación emitida por una de las partes de una orden recibida.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Calificacion {
+        pub orden_id: u32,
+        pub calificador: AccountId,
+        pub calificado: AccountId,
+        pub rol: RolCalificado,
+        /// Valor entre 1 y 5.
+        pub puntaje: u8,
+        /// Bloque en que se emitió.
+        pub bloque: BlockNumber,
+        /// Comentario opcional del calificador.
+        pub comentario: Option<String>,
+        /// Única respuesta pública del calificado.
+        pub respuesta: Option<String>,
+        /// Un moderador borró el comentario; el puntaje se mantiene.
+        pub comentario_oculto: bool,
+        /// Un moderador borró la respuesta.
+        pub respuesta_oculta: bool,
+    }
+
+    /// Hash Blake2x256 con el que una parte se compromete a una calificación.
+    pub type CompromisoCalificacion = [u8; 32];
+
+    /// Bloques por defecto que tiene la otra parte para comprometer su calificación.
+    pub const BLOQUES_PLAZO_COMPROMISO_DEFECTO: BlockNumber = 14_400;
+    /// Bloques por defecto, desde la recepción, para calificar una orden (30 días de bloques de 6 s).
+    pub const BLOQUES_PLAZO_CALIFICACION_DEFECTO: BlockNumber = 432_000;
+
+    /// Compromiso que debe enviar `calificador` para luego revelar `calificacion`
+    /// y `comentario` con `revelar_calificacion` usando la misma `sal`.
+    pub fn hash_calificacion(orden_id: u32, calificador: AccountId, calificacion: u32, comentario: &Option<String>, sal: &[u8; 32]) -> CompromisoCalificacion {
+        let mut salida = [0u8; 32];
+        ink::env::hash_encoded::<ink::env::hash::Blake2x256, _>(&(orden_id, calificador, calificacion, comentario, sal), &mut salida);
+        salida
+    }
+
+    /// Compromisos ciegos de las dos partes de una orden.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct CompromisosOrden {
+        /// Compromiso del comprador sobre el vendedor.
+        pub al_vendedor: Option<CompromisoCalificacion>,
+        /// Compromiso del vendedor sobre el comprador.
+        pub al_comprador: Option<CompromisoCalificacion>,
+        /// Último bloque en que se aceptan compromisos; desde el siguiente se puede
+        /// revelar aunque la otra parte no se haya comprometido.
+        pub cierre: BlockNumber,
+    }
+
+    /// Texto de una calificación que un moderador puede ocultar.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum TextoCalificacion {
+        Comentario,
+        Respuesta,
     }
 
+    /// Clave del índice de calificaciones recibidas: (cuenta, rol, índice).
+    pub type ClaveCalificacionRecibida = (AccountId, RolCalificado, u32);
+
     /// Representa una orden.
     #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
@@ -60,47 +606,388 @@
         pub comprador: AccountId,
         pub vendedor: AccountId,
         pub producto_id: u32,
+        pub variante_id: Option<u32>,
         pub cantidad: u32,
+        /// Importe de la orden al momento de crearla.
+        pub total: Balance,
         pub estado: EstadoOrden,
         pub comprador_califico: bool,
         pub vendedor_califico: bool,
         pub comprador_solicita_cancelacion: bool,
         pub vendedor_acepta_cancelacion: bool,
+        /// Fondos pagados por adelantado y retenidos por el contrato (p. ej. puja ganadora).
+        /// Se liberan al vendedor al recibirse la orden o al comprador si se cancela.
+        pub deposito: Balance,
+        /// Si la orden es de un paquete; `producto_id` es entonces `PRODUCTO_PAQUETE`
+        /// y `cantidad` la cantidad de paquetes.
+        pub paquete_id: Option<u32>,
+        /// Bloque en que la orden pasó a `Recibida`; desde ahí corre el plazo para calificar.
+        pub recibida_en: Option<BlockNumber>,
     }
 
     impl Orden {
         /// Nueva orden (pendiente).
-        pub fn nueva(id: u32, comprador: AccountId, vendedor: AccountId, producto_id: u32, cantidad: u32) -> Self {
+        pub fn nueva(id: u32, comprador: AccountId, vendedor: AccountId, producto_id: u32, variante_id: Option<u32>, cantidad: u32, total: Balance) -> Self {
             Self {
-                id, comprador, vendedor, producto_id, cantidad,
+                id, comprador, vendedor, producto_id, variante_id, cantidad, total,
                 estado: EstadoOrden::Pendiente,
                 comprador_califico: false,
                 vendedor_califico: false,
                 comprador_solicita_cancelacion: false,
                 vendedor_acepta_cancelacion: false,
+                deposito: 0,
+                paquete_id: None,
+                recibida_en: None,
             }
         }
+
+        /// La orden todavía compromete stock o fondos (no está `Recibida` ni `Cancelada`).
+        pub fn abierta(&self) -> bool {
+            matches!(self.estado, EstadoOrden::Pendiente | EstadoOrden::Enviada | EstadoOrden::Preventa | EstadoOrden::EnDisputa)
+        }
+    }
+
+    /// Precio promocional de un producto entre dos timestamps (ms), opcionalmente
+    /// limitado a una cantidad de unidades.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Descuento {
+        pub precio: Balance,
+        pub inicio: Timestamp,
+        /// Exclusivo: a partir de `fin` rige el precio de lista.
+        pub fin: Timestamp,
+        pub unidades_maximas: Option<u32>,
+        pub unidades_vendidas: u32,
+        /// Ya se emitió `DescuentoIniciado`.
+        pub inicio_notificado: bool,
+        /// Ya se emitió `DescuentoFinalizado`.
+        pub fin_notificado: bool,
+    }
+
+    impl Descuento {
+        /// Unidades que todavía se pueden vender con descuento (`None` = sin límite).
+        pub fn unidades_restantes(&self) -> Option<u32> {
+            self.unidades_maximas.map(|max| max.saturating_sub(self.unidades_vendidas))
+        }
+
+        /// Si el descuento rige en `ahora` y le quedan unidades.
+        pub fn vigente(&self, ahora: Timestamp) -> bool {
+            self.inicio <= ahora && !self.terminado(ahora)
+        }
+
+        /// Si en `ahora` ya pasó `fin` o se agotaron las unidades.
+        pub fn terminado(&self, ahora: Timestamp) -> bool {
+            ahora >= self.fin || self.unidades_restantes() == Some(0)
+        }
+    }
+
+    /// Máximo de tramos de precio por cantidad de un producto.
+    pub const MAX_TRAMOS_PRECIO: u32 = 10;
+
+    /// Precio unitario mayorista a partir de una cantidad mínima de unidades.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct TramoPrecio {
+        pub cantidad_minima: u32,
+        pub precio: Balance,
+    }
+
+    /// Rebaja que otorga un cupón sobre el total de la orden.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum ValorCupon {
+        /// Porcentaje del total, entre 1 y 100.
+        Porcentaje(u8),
+        /// Monto fijo; el total no baja de 0.
+        Fijo(Balance),
+    }
+
+    /// Cupón de descuento de un vendedor. Se identifica por el hash del código,
+    /// así el código en claro no queda guardado en el contrato.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Cupon {
+        pub valor: ValorCupon,
+        /// Timestamp (ms) a partir del cual el cupón deja de valer.
+        pub expira_en: Timestamp,
+        pub usos_maximos: u32,
+        pub usos: u32,
+        /// Si está, el cupón solo vale para ese producto.
+        pub producto_id: Option<u32>,
+        /// Si está, el cupón solo vale para productos de esa categoría.
+        pub categoria: Option<String>,
+    }
+
+    /// Hash Blake2x256 del código de un cupón.
+    pub type HashCupon = [u8; 32];
+
+    /// Hash Blake2x256 de un código de cupón, que es la clave con la que se registra.
+    pub fn hash_codigo_cupon(codigo: &[u8]) -> HashCupon {
+        let mut salida = [0u8; 32];
+        ink::env::hash_bytes::<ink::env::hash::Blake2x256>(codigo, &mut salida);
+        salida
+    }
+
+    /// Producto junto con su precio de lista y el precio que se cobraría ahora.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct ProductoConPrecio {
+        pub producto: Producto,
+        pub precio_lista: Balance,
+        pub precio_efectivo: Balance,
+    }
+
+    /// Estados de una oferta. `Pendiente` espera respuesta del vendedor y
+    /// `Contraofertada` espera respuesta del comprador.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum EstadoOferta {
+        Pendiente,
+        Contraofertada,
+        Aceptada,
+        Rechazada,
+    }
+
+    /// Oferta de compra negociable sobre un producto de precio fijo.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Oferta {
+        pub id: u32,
+        pub producto_id: u32,
+        pub variante_id: Option<u32>,
+        pub comprador: AccountId,
+        pub vendedor: AccountId,
+        /// Último precio unitario propuesto (por el comprador o el vendedor).
+        pub precio_unitario: Balance,
+        pub cantidad: u32,
+        pub estado: EstadoOferta,
+        /// Último bloque en que se puede responder; se renueva con cada contraoferta.
+        pub expira_en: BlockNumber,
+        /// Orden generada al aceptarse.
+        pub orden_id: Option<u32>,
+    }
+
+    impl Oferta {
+        /// La oferta todavía espera respuesta de alguna de las partes.
+        pub fn abierta(&self) -> bool {
+            matches!(self.estado, EstadoOferta::Pendiente | EstadoOferta::Contraofertada)
+        }
+    }
+
+    /// Vigencia por defecto de una oferta (~1 día con bloques de 6 s).
+    pub const BLOQUES_EXPIRACION_OFERTA_DEFECTO: BlockNumber = 14_400;
+
+    /// Largos máximos (en bytes) de los campos de texto de un producto.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct LimitesTexto {
+        pub max_nombre: u32,
+        pub max_descripcion: u32,
+        pub max_categoria: u32,
+    }
+
+    /// Duraciones (en ms) de las publicaciones.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct DuracionPublicacion {
+        /// Vigencia de una publicación nueva o renovada.
+        pub vigencia: Timestamp,
+        /// Tiempo desde el vencimiento hasta que cualquiera puede eliminarla.
+        pub gracia: Timestamp,
+    }
+
+    impl Default for DuracionPublicacion {
+        fn default() -> Self {
+            const DIA: Timestamp = 24 * 60 * 60 * 1000;
+            Self { vigencia: 30 * DIA, gracia: 30 * DIA }
+        }
+    }
+
+    /// Máximo de productos por llamada a `limpiar_publicaciones_vencidas`.
+    pub const MAX_LOTE_LIMPIEZA: u32 = 50;
+
+    impl Default for LimitesTexto {
+        fn default() -> Self {
+            Self { max_nombre: 64, max_descripcion: 512, max_categoria: 32 }
+        }
     }
 
     /// Errores del sistema
     #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub enum SistemaError {
+        /// La cantidad pedida o publicada es 0.
         CantidadInsuficiente,
+        /// La cuenta no está registrada en el marketplace.
         UsuarioNoRegistrado,
+        /// El vendedor no tiene productos publicados.
         ProductosVacios,
+        /// El rol del usuario (o su lado en la orden) no permite la operación.
         NoEsRolCorrecto,
+        /// La orden no está en el estado que requiere la operación.
         EstadoInvalido,
+        /// No existe una orden con ese ID.
         OrdenNoExiste,
+        /// La cuenta ya estaba registrada.
         UsuarioExistente,
+        /// El producto no tiene unidades suficientes.
         StockInsuficiente,
+        /// La calificación no está entre 1 y 5.
         CalificacionInvalida,
+        /// Ya se calificó esta orden desde ese lado.
         YaCalificado,
+        /// No existe un producto con ese ID.
+        ProductoNoExiste,
+        /// Se agotó el rango de IDs del contador correspondiente.
+        DesbordamientoId,
+        /// Quien llama no es ni el comprador ni el vendedor de la orden.
+        NoEsParteDeLaOrden,
+        /// El producto no tiene una variante con ese ID (o no tiene variantes).
+        VarianteNoExiste,
+        /// Falta la variante: el producto tiene variantes y no se indicó cuál,
+        /// o se intentó publicar/extender un producto con variantes sin ninguna.
+        VarianteRequerida,
+        /// Los atributos de la variante están vacíos, repetidos o exceden los límites.
+        AtributosInvalidos,
+        /// El producto ya tiene `MAX_VARIANTES` variantes.
+        LimiteVariantesAlcanzado,
+        /// Quien llama no es el vendedor del producto.
+        NoEsVendedorDelProducto,
+        /// Los bytes no forman un CID de IPFS válido.
+        CidInvalido,
+        /// El producto ya tiene `MAX_MEDIOS_POR_PRODUCTO` referencias.
+        LimiteMediosAlcanzado,
+        /// El CID ya está asociado al producto.
+        MedioDuplicado,
+        /// El CID no está asociado al producto.
+        MedioNoExiste,
+        /// El nuevo orden no es una permutación de las referencias actuales.
+        ReordenamientoInvalido,
+        /// La orden ya tiene una reseña.
+        YaResenado,
+        /// El texto de la reseña está vacío, es demasiado largo o tiene caracteres no permitidos.
+        ResenaInvalida,
+        /// El nombre está vacío, supera el largo máximo o contiene caracteres de control.
+        NombreInvalido,
+        /// La descripción supera el largo máximo o contiene caracteres de control.
+        DescripcionDemasiadoLarga,
+        /// La categoría supera el largo máximo o contiene caracteres de control.
+        CategoriaDemasiadoLarga,
+        /// El precio unitario es 0.
+        PrecioInvalido,
+        /// La operación está reservada al administrador del contrato.
+        NoEsAdministrador,
+        /// Los parámetros de configuración no son válidos.
+        ConfiguracionInvalida,
+        /// El producto se vende por subasta y no admite `crear_orden`.
+        ProductoEnSubasta,
+        /// El producto no tiene una subasta asociada.
+        NoEsSubasta,
+        /// La subasta ya terminó o fue finalizada.
+        SubastaCerrada,
+        /// La subasta todavía no llegó a su bloque de fin.
+        SubastaEnCurso,
+        /// La puja no alcanza la reserva o no supera la mejor puja.
+        PujaInsuficiente,
+        /// La duración en bloques es 0.
+        DuracionInvalida,
+        /// La cuenta no tiene fondos pendientes de retiro.
+        SinFondos,
+        /// Falló la transferencia de fondos desde el contrato.
+        TransferenciaFallida,
+        /// No existe una oferta con ese ID.
+        OfertaNoExiste,
+        /// Pasó el bloque `expira_en` de la oferta.
+        OfertaExpirada,
+        /// Quien llama no es ni el comprador ni el vendedor de la oferta.
+        NoEsParteDeLaOferta,
+        /// El lote está vacío o supera `MAX_LOTE_PRODUCTOS`.
+        TamanoLoteInvalido,
+        /// El período no es válido: `inicio >= fin` o `fin` ya pasó.
+        PeriodoInvalido,
+        /// El producto no tiene un descuento programado.
+        DescuentoNoExiste,
+        /// Los tramos no son monótonos (cantidades crecientes desde 2 y precios
+        /// decrecientes por debajo del de lista) o son demasiados.
+        TramosInvalidos,
+        /// El vendedor ya tiene un cupón con ese código.
+        CuponExistente,
+        /// El vendedor del producto no tiene un cupón con ese código.
+        CuponNoExiste,
+        /// El cupón venció.
+        CuponExpirado,
+        /// El cupón ya se usó `usos_maximos` veces.
+        CuponAgotado,
+        /// El cupón está restringido a otro producto o categoría.
+        CuponNoAplica,
+        /// El valor, la vigencia o los usos del cupón no son válidos.
+        CuponInvalido,
+        /// El producto fue despublicado por su vendedor.
+        ProductoNoDisponible,
+        /// No existe un paquete con ese ID.
+        PaqueteNoExiste,
+        /// Algún componente del paquete no existe, está despublicado o no tiene stock.
+        PaqueteNoDisponible,
+        /// Componentes vacíos, repetidos, con cantidad 0, demasiados, ajenos,
+        /// con variantes o en subasta.
+        ComponentesInvalidos,
+        /// El producto no acepta preventas (no habilitadas, desactivadas o con variantes).
+        PreventaNoDisponible,
+        /// La preventa superaría el tope de unidades o de órdenes en espera.
+        LimitePreventaAlcanzado,
+        /// La publicación del producto venció; el vendedor debe renovarla.
+        PublicacionVencida,
+        /// El comprador debe registrar su clave pública para comprar productos digitales.
+        ClavePublicaRequerida,
+        /// La operación solo aplica a órdenes de productos digitales.
+        NoEsProductoDigital,
+        /// Las órdenes digitales se envían con `entregar_contenido_digital`.
+        EntregaDigitalRequerida,
+        /// El contenido cifrado está vacío o supera `MAX_LARGO_CONTENIDO_DIGITAL`.
+        ContenidoInvalido,
+        /// Comentario o respuesta de una calificación vacío, demasiado largo o con caracteres no permitidos.
+        ComentarioInvalido,
+        /// No hay calificación registrada para esa orden y lado.
+        CalificacionNoExiste,
+        /// La calificación ya tiene respuesta.
+        YaRespondido,
+        /// Quien llama no es moderador ni administrador.
+        NoEsModerador,
+        /// Quien llama no envió compromiso de calificación para esta orden.
+        CompromisoNoExiste,
+        /// La calificación, el comentario o la sal no coinciden con el compromiso.
+        CompromisoInvalido,
+        /// La otra parte aún puede comprometerse: no se puede revelar todavía.
+        RevelacionAnticipada,
+        /// Cerró el plazo para comprometer calificaciones en esta orden.
+        CompromisoCerrado,
+        /// Venció el plazo para calificar la orden desde que se recibió.
+        PlazoVencido,
+        /// Nombre visible, avatar o región del perfil no válidos.
+        PerfilInvalido,
+        /// Quien llama no es verificador ni administrador.
+        NoEsVerificador,
+        /// Nivel 0 o vencimiento que no está en el futuro.
+        VerificacionInvalida,
+        /// El total supera el límite permitido para vendedores sin verificación vigente.
+        VendedorNoVerificado,
+        /// Los permisos no habilitan ni comprar ni vender.
+        PermisosInvalidos,
+        /// La cuenta se dio de baja con `darse_de_baja`.
+        CuentaDadaDeBaja,
+        /// Hay órdenes abiertas o subastas sin finalizar en las que participa la cuenta.
+        OperacionesAbiertas,
+        /// La cuenta tiene fondos por retirar con `retirar_fondos`.
+        SaldoPendiente,
+        /// Los productos digitales no se venden en paquetes: se entregan por su propio flujo.
+        ComponenteDigital,
     }
 
     // TIPOS ALIAS NECESARIOS PORQUE ESTAMOS FUERA DEL MODULO CONTRACT
     pub type AccountId = ink::primitives::AccountId;
     pub type Balance = u128; 
+    pub type BlockNumber = u32;
+    pub type Timestamp = u64;
 
     // --- 2. EL CONTRATO PRINCIPAL ---`
 
@@ -108,36 +995,321 @@
     pub mod internal {
         use super::*;
         use ink::storage::Mapping;
+        use ink::storage::traits::ManualKey;
         use ink::prelude::string::String;
         use ink::prelude::vec::Vec;
 
         #[ink(event)]
-        pub struct RolActualizado {
+        pub struct VerificadorActualizado {
+            #[ink(topic)]
             pub cuenta: AccountId,
-            pub rol_anterior: RolUsuario,
-            pub rol_nuevo: RolUsuario,
+            pub activo: bool,
         }
 
         #[ink(event)]
-        pub struct ProductoPublicado {
-            pub vendedor: AccountId,
-            pub producto_id: u32,
+        pub struct UsuarioVerificado {
+            #[ink(topic)]
+            pub cuenta: AccountId,
+            pub nivel: u8,
+            pub expira_en: Timestamp,
+            pub verificador: AccountId,
         }
 
         #[ink(event)]
-        pub struct CompradorCalifico {
-            pub orden_id: u32,
-            pub comprador: AccountId,
-            pub vendedor: AccountId,
-            pub calificacion: u32,
+        pub struct VerificacionRevocada {
+            #[ink(topic)]
+            pub cuenta: AccountId,
+            pub verificador: AccountId,
         }
 
+        /// La cuenta cerró su participación; sus productos quedaron despublicados.
         #[ink(event)]
-        pub struct VendedorCalifico {
+        pub struct UsuarioDadoDeBaja {
+            #[ink(topic)]
+            pub cuenta: AccountId,
+            pub productos_despublicados: u32,
+        }
+
+        #[ink(event)]
+        pub struct PerfilActualizado {
+            #[ink(topic)]
+            pub cuenta: AccountId,
+            pub perfil: PerfilUsuario,
+        }
+
+        #[ink(event)]
+        pub struct RolActualizado {
+            pub cuenta: AccountId,
+            pub rol_anterior: RolUsuario,
+            pub rol_nuevo: RolUsuario,
+        }
+
+        #[ink(event)]
+        pub struct ProductoPublicado {
+            pub vendedor: AccountId,
+            pub producto_id: u32,
+        }
+
+        #[ink(event)]
+        pub struct VarianteAgregada {
+            pub producto_id: u32,
+            pub variante_id: u32,
+        }
+
+        /// Se asoció un CID a un producto; el servicio de pinning debe fijarlo.
+        #[ink(event)]
+        pub struct MedioAgregado {
+            pub producto_id: u32,
+            pub cid: Cid,
+        }
+
+        /// Se quitó un CID de un producto; puede dejar de fijarse.
+        #[ink(event)]
+        pub struct MedioEliminado {
+            pub producto_id: u32,
+            pub cid: Cid,
+        }
+
+        #[ink(event)]
+        pub struct MediosReordenados {
+            pub producto_id: u32,
+            pub cids: Vec<Cid>,
+        }
+
+        #[ink(event)]
+        pub struct ResenaPublicada {
+            #[ink(topic)]
+            pub producto_id: u32,
+            pub orden_id: u32,
+            pub autor: AccountId,
+            pub puntaje: u8,
+        }
+
+        #[ink(event)]
+        pub struct SubastaIniciada {
+            #[ink(topic)]
+            pub producto_id: u32,
+            pub precio_reserva: Balance,
+            pub fin: BlockNumber,
+        }
+
+        #[ink(event)]
+        pub struct PujaRealizada {
+            #[ink(topic)]
+            pub producto_id: u32,
+            pub postor: AccountId,
+            pub monto: Balance,
+        }
+
+        /// `ganador` y `orden_id` son `None` si la subasta cerró sin pujas.
+        #[ink(event)]
+        pub struct SubastaFinalizada {
+            #[ink(topic)]
+            pub producto_id: u32,
+            pub ganador: Option<AccountId>,
+            pub monto: Balance,
+            pub orden_id: Option<u32>,
+        }
+
+        #[ink(event)]
+        pub struct FondosRetirados {
+            pub cuenta: AccountId,
+            pub monto: Balance,
+        }
+
+        #[ink(event)]
+        pub struct OfertaRealizada {
+            #[ink(topic)]
+            pub oferta_id: u32,
+            pub producto_id: u32,
+            pub comprador: AccountId,
+            pub precio_unitario: Balance,
+            pub cantidad: u32,
+        }
+
+        #[ink(event)]
+        pub struct OfertaContraofertada {
+            #[ink(topic)]
+            pub oferta_id: u32,
+            pub por: AccountId,
+            pub precio_unitario: Balance,
+            pub expira_en: BlockNumber,
+        }
+
+        #[ink(event)]
+        pub struct OfertaAceptada {
+            #[ink(topic)]
+            pub oferta_id: u32,
+            pub orden_id: u32,
+        }
+
+        #[ink(event)]
+        pub struct OfertaRechazada {
+            #[ink(topic)]
+            pub oferta_id: u32,
+            pub por: AccountId,
+        }
+
+        /// El descuento rige entre `inicio` y `fin` (timestamps en ms).
+        #[ink(event)]
+        pub struct DescuentoProgramado {
+            #[ink(topic)]
+            pub producto_id: u32,
+            pub precio: Balance,
+            pub inicio: Timestamp,
+            pub fin: Timestamp,
+            pub unidades_maximas: Option<u32>,
+        }
+
+        /// Empezó a regir el descuento. Se emite en la primera operación sobre el producto
+        /// a partir de `inicio` (ver `sincronizar_descuento`).
+        #[ink(event)]
+        pub struct DescuentoIniciado {
+            #[ink(topic)]
+            pub producto_id: u32,
+            pub precio: Balance,
+            pub fin: Timestamp,
+        }
+
+        /// El descuento dejó de regir: llegó `fin`, se agotaron las unidades (`agotado`),
+        /// el vendedor lo canceló o lo reemplazó. Como `DescuentoIniciado`, el fin por
+        /// tiempo se emite en la primera operación posterior.
+        #[ink(event)]
+        pub struct DescuentoFinalizado {
+            #[ink(topic)]
+            pub producto_id: u32,
+            pub agotado: bool,
+        }
+
+        #[ink(event)]
+        pub struct CuponRegistrado {
+            #[ink(topic)]
+            pub vendedor: AccountId,
+            pub hash_codigo: HashCupon,
+        }
+
+        #[ink(event)]
+        pub struct CuponUsado {
+            #[ink(topic)]
+            pub vendedor: AccountId,
+            pub hash_codigo: HashCupon,
+            pub orden_id: u32,
+            /// Monto descontado del total.
+            pub descuento: Balance,
+        }
+
+        #[ink(event)]
+        pub struct PaqueteCreado {
+            #[ink(topic)]
+            pub vendedor: AccountId,
+            pub paquete_id: u32,
+        }
+
+        #[ink(event)]
+        pub struct PreventaCreada {
+            #[ink(topic)]
+            pub producto_id: u32,
+            pub orden_id: u32,
+            pub unidades_pendientes: u32,
+        }
+
+        /// La preventa recibió todo su stock y pasó a `Pendiente`.
+        #[ink(event)]
+        pub struct PreventaCumplida {
+            #[ink(topic)]
+            pub producto_id: u32,
+            pub orden_id: u32,
+        }
+
+        #[ink(event)]
+        pub struct StockRepuesto {
+            #[ink(topic)]
+            pub producto_id: u32,
+            pub unidades: u32,
+        }
+
+        #[ink(event)]
+        pub struct PublicacionRenovada {
+            #[ink(topic)]
+            pub producto_id: u32,
+            pub expira_en: Timestamp,
+        }
+
+        #[ink(event)]
+        pub struct PublicacionEliminada {
+            #[ink(topic)]
+            pub producto_id: u32,
+        }
+
+        #[ink(event)]
+        pub struct ContenidoDigitalEntregado {
+            #[ink(topic)]
+            pub orden_id: u32,
+            pub comprador: AccountId,
+        }
+
+        #[ink(event)]
+        pub struct DisputaAbierta {
+            #[ink(topic)]
+            pub orden_id: u32,
+        }
+
+        #[ink(event)]
+        pub struct DisputaResuelta {
+            #[ink(topic)]
+            pub orden_id: u32,
+            pub a_favor_del_comprador: bool,
+        }
+
+        #[ink(event)]
+        pub struct CompradorCalifico {
+            pub orden_id: u32,
+            pub comprador: AccountId,
+            pub vendedor: AccountId,
+            pub calificacion: u32,
+            pub comentario: Option<String>,
+        }
+
+        #[ink(event)]
+        pub struct VendedorCalifico {
             pub orden_id: u32,
             pub vendedor: AccountId,
             pub comprador: AccountId,
             pub calificacion: u32,
+            pub comentario: Option<String>,
+        }
+
+        #[ink(event)]
+        pub struct CalificacionComprometida {
+            #[ink(topic)]
+            pub orden_id: u32,
+            /// Lado que será calificado.
+            pub rol: RolCalificado,
+        }
+
+        #[ink(event)]
+        pub struct CalificacionRespondida {
+            #[ink(topic)]
+            pub orden_id: u32,
+            pub rol: RolCalificado,
+            pub autor: AccountId,
+            pub respuesta: String,
+        }
+
+        #[ink(event)]
+        pub struct TextoCalificacionOculto {
+            #[ink(topic)]
+            pub orden_id: u32,
+            pub rol: RolCalificado,
+            pub texto: TextoCalificacion,
+            pub moderador: AccountId,
+        }
+
+        #[ink(event)]
+        pub struct ModeradorActualizado {
+            #[ink(topic)]
+            pub cuenta: AccountId,
+            pub activo: bool,
         }
 
         /// # Contrato Marketplace
@@ -157,8 +1329,11 @@
         /// Emite eventos para acciones críticas como `ProductoPublicado`, `RolActualizado`, y calificaciones.
         #[ink(storage)]
         pub struct Marketplace {
-            /// Mapeo de usuarios registrados (por dirección).
-            usuarios: Mapping<AccountId, Usuario>,
+            /// Mapeo de usuarios registrados (por dirección). Leer con `usuario`, que
+            /// también resuelve los guardados con el formato anterior.
+            usuarios: Mapping<AccountId, Usuario, ManualKey<CLAVE_USUARIOS>>,
+            /// Usuarios todavía guardados con el formato previo a `Permisos`.
+            usuarios_legacy: Mapping<AccountId, UsuarioLegacy, ManualKey<CLAVE_USUARIOS_LEGACY>>,
             /// Mapeo de productos publicados (ID -> Producto).
             productos: Mapping<u32, Producto>,
             /// Contador para IDs de productos.
@@ -167,8 +1342,79 @@
             ordenes: Mapping<u32, Orden>,
             /// Contador para IDs de órdenes.
             next_orden_id: u32,
+            /// Órdenes de cada producto, incluidas las de paquetes que lo contienen; las
+            /// cerradas se depuran al agregar.
+            ordenes_producto: Mapping<u32, Vec<u32>>,
             /// Lista auxiliar de IDs para poder recorrer los usuarios
             lista_usuarios_ids: Vec<AccountId>,
+            /// Referencias multimedia (CIDs) de cada producto, en orden de presentación.
+            medios_producto: Mapping<u32, Vec<Cid>>,
+            /// Reseñas por (producto_id, índice); el índice va de 0 a `cantidad_resenas`.
+            resenas: Mapping<(u32, u32), Resena>,
+            /// Órdenes que ya tienen reseña.
+            ordenes_resenadas: Mapping<u32, ()>,
+            /// Subastas activas o finalizadas (producto_id -> Subasta).
+            subastas: Mapping<u32, Subasta>,
+            /// Fondos que cada cuenta puede retirar con `retirar_fondos`
+            /// (pujas superadas, depósitos liberados).
+            saldos_pendientes: Mapping<AccountId, Balance>,
+            /// Ofertas de compra (ID -> Oferta).
+            ofertas: Mapping<u32, Oferta>,
+            /// Contador para IDs de ofertas.
+            next_oferta_id: u32,
+            /// Bloques que una oferta o contraoferta queda abierta.
+            bloques_expiracion_oferta: BlockNumber,
+            /// Descuento programado de cada producto (a lo sumo uno).
+            descuentos: Mapping<u32, Descuento>,
+            /// Tramos de precio por cantidad de cada producto, ordenados por `cantidad_minima`.
+            tramos_precio: Mapping<u32, Vec<TramoPrecio>>,
+            /// Cupones por (vendedor, hash del código).
+            cupones: Mapping<(AccountId, HashCupon), Cupon>,
+            /// Paquetes de productos (ID -> Paquete).
+            paquetes: Mapping<u32, Paquete>,
+            /// Contador para IDs de paquetes.
+            next_paquete_id: u32,
+            /// Preventas habilitadas por producto.
+            preventas: Mapping<u32, Preventa>,
+            /// Claves públicas de los compradores para entregas digitales.
+            claves_publicas: Mapping<AccountId, ClavePublica>,
+            /// Entregas de las órdenes digitales (orden_id -> EntregaDigital).
+            entregas_digitales: Mapping<u32, EntregaDigital>,
+            /// Calificaciones por (orden_id, lado calificado).
+            calificaciones: Mapping<(u32, RolCalificado), Calificacion>,
+            /// Índice de calificaciones recibidas -> orden_id.
+            /// El índice va de 0 a `calificaciones_como_*` del usuario.
+            calificaciones_recibidas: Mapping<ClaveCalificacionRecibida, u32>,
+            /// Cuentas designadas por el administrador para moderar textos.
+            moderadores: Mapping<AccountId, ()>,
+            /// Compromisos de calificación pendientes de revelar (orden_id -> compromisos).
+            compromisos_calificacion: Mapping<u32, CompromisosOrden>,
+            /// Bloques que tiene la otra parte para comprometerse tras el primer compromiso.
+            bloques_plazo_compromiso: BlockNumber,
+            /// Bloques, desde la recepción, en que se aceptan compromisos de calificación.
+            bloques_plazo_calificacion: BlockNumber,
+            /// Reputación ponderada por importe y antigüedad de cada (cuenta, rol).
+            reputacion_ponderada: Mapping<(AccountId, RolCalificado), ReputacionPonderada>,
+            /// Cuentas designadas por el administrador para verificar usuarios.
+            verificadores: Mapping<AccountId, ()>,
+            /// Total máximo de una orden a un vendedor sin verificación vigente (`None`: sin límite).
+            limite_orden_no_verificado: Option<Balance>,
+            /// Cuentas dadas de baja (cuenta -> bloque de la baja). Su `Usuario` se conserva.
+            cuentas_cerradas: Mapping<AccountId, BlockNumber>,
+            /// Órdenes de cada cuenta como comprador o vendedor; las cerradas se depuran al agregar.
+            ordenes_usuario: Mapping<AccountId, Vec<u32>>,
+            /// Ofertas de cada cuenta como comprador o vendedor; las resueltas se depuran al agregar.
+            ofertas_usuario: Mapping<AccountId, Vec<u32>>,
+            /// Productos publicados por cada vendedor.
+            productos_vendedor: Mapping<AccountId, Vec<u32>>,
+            /// Cantidad de subastas sin finalizar en las que cada cuenta tiene la mejor puja.
+            subastas_liderando: Mapping<AccountId, u32>,
+            /// Cuenta que desplegó el contrato; puede ajustar la configuración.
+            administrador: AccountId,
+            /// Largos máximos aceptados para los textos de los productos.
+            limites_texto: LimitesTexto,
+            /// Vigencia y período de gracia de las publicaciones.
+            duracion_publicacion: DuracionPublicacion,
         }
 
         impl Marketplace {
@@ -177,11 +1423,46 @@
             pub fn nuevo() -> Self {
                 Self {
                     usuarios: Mapping::default(),
+                    usuarios_legacy: Mapping::default(),
                     productos: Mapping::default(),
                     next_producto_id: 0,
                     ordenes: Mapping::default(),
                     next_orden_id: 0,
+                    ordenes_producto: Mapping::default(),
                     lista_usuarios_ids: Vec::new(),
+                    medios_producto: Mapping::default(),
+                    resenas: Mapping::default(),
+                    ordenes_resenadas: Mapping::default(),
+                    subastas: Mapping::default(),
+                    saldos_pendientes: Mapping::default(),
+                    ofertas: Mapping::default(),
+                    next_oferta_id: 0,
+                    bloques_expiracion_oferta: BLOQUES_EXPIRACION_OFERTA_DEFECTO,
+                    descuentos: Mapping::default(),
+                    tramos_precio: Mapping::default(),
+                    cupones: Mapping::default(),
+                    paquetes: Mapping::default(),
+                    next_paquete_id: 0,
+                    preventas: Mapping::default(),
+                    claves_publicas: Mapping::default(),
+                    entregas_digitales: Mapping::default(),
+                    calificaciones: Mapping::default(),
+                    calificaciones_recibidas: Mapping::default(),
+                    moderadores: Mapping::default(),
+                    compromisos_calificacion: Mapping::default(),
+                    bloques_plazo_compromiso: BLOQUES_PLAZO_COMPROMISO_DEFECTO,
+                    bloques_plazo_calificacion: BLOQUES_PLAZO_CALIFICACION_DEFECTO,
+                    reputacion_ponderada: Mapping::default(),
+                    verificadores: Mapping::default(),
+                    limite_orden_no_verificado: None,
+                    cuentas_cerradas: Mapping::default(),
+                    ordenes_usuario: Mapping::default(),
+                    ofertas_usuario: Mapping::default(),
+                    productos_vendedor: Mapping::default(),
+                    subastas_liderando: Mapping::default(),
+                    administrador: Self::env().caller(),
+                    limites_texto: LimitesTexto::default(),
+                    duracion_publicacion: DuracionPublicacion::default(),
                 }
             }
             // --- Getters para testing y verificación de estado ---
@@ -197,6 +1478,119 @@
                 self.next_orden_id
             }
 
+            /// Cuenta administradora del contrato.
+            #[ink(message)]
+            pub fn obtener_administrador(&self) -> AccountId {
+                self.administrador
+            }
+
+            /// Largos máximos vigentes para los textos de los productos.
+            #[ink(message)]
+            pub fn obtener_limites_texto(&self) -> LimitesTexto {
+                self.limites_texto.clone()
+            }
+
+            /// Actualiza los largos máximos de nombre, descripción y categoría.
+            ///
+            /// # Errores
+            /// * `NoEsAdministrador`: Quien llama no es el administrador.
+            /// * `ConfiguracionInvalida`: Algún límite es 0.
+            #[ink(message)]
+            pub fn configurar_limites_texto(&mut self, limites: LimitesTexto) -> Result<(), SistemaError> {
+                self.verificar_administrador(self.env().caller())?;
+                if limites.max_nombre == 0 || limites.max_descripcion == 0 || limites.max_categoria == 0 {
+                    return Err(SistemaError::ConfiguracionInvalida);
+                }
+                self.limites_texto = limites;
+                Ok(())
+            }
+
+            /// Bloques que una oferta o contraoferta queda abierta.
+            #[ink(message)]
+            pub fn obtener_expiracion_ofertas(&self) -> BlockNumber {
+                self.bloques_expiracion_oferta
+            }
+
+            /// Cambia la vigencia (en bloques) de las ofertas nuevas y contraofertas.
+            ///
+            /// # Errores
+            /// * `NoEsAdministrador`: Quien llama no es el administrador.
+            /// * `ConfiguracionInvalida`: `bloques` es 0.
+            #[ink(message)]
+            pub fn configurar_expiracion_ofertas(&mut self, bloques: BlockNumber) -> Result<(), SistemaError> {
+                self.verificar_administrador(self.env().caller())?;
+                if bloques == 0 {
+                    return Err(SistemaError::ConfiguracionInvalida);
+                }
+                self.bloques_expiracion_oferta = bloques;
+                Ok(())
+            }
+
+            /// Bloques que tiene la otra parte para comprometer su calificación.
+            #[ink(message)]
+            pub fn obtener_plazo_compromiso(&self) -> BlockNumber {
+                self.bloques_plazo_compromiso
+            }
+
+            /// Cambia el plazo (en bloques) para comprometer calificaciones en órdenes
+            /// que aún no tienen compromisos.
+            ///
+            /// # Errores
+            /// * `NoEsAdministrador`: Quien llama no es el administrador.
+            /// * `ConfiguracionInvalida`: `bloques` es 0.
+            #[ink(message)]
+            pub fn configurar_plazo_compromiso(&mut self, bloques: BlockNumber) -> Result<(), SistemaError> {
+                self.verificar_administrador(self.env().caller())?;
+                if bloques == 0 {
+                    return Err(SistemaError::ConfiguracionInvalida);
+                }
+                self.bloques_plazo_compromiso = bloques;
+                Ok(())
+            }
+
+            /// Bloques, desde la recepción de la orden, para comprometer una calificación.
+            #[ink(message)]
+            pub fn obtener_plazo_calificacion(&self) -> BlockNumber {
+                self.bloques_plazo_calificacion
+            }
+
+            /// Cambia el plazo (en bloques) para calificar; aplica también a órdenes ya recibidas.
+            ///
+            /// # Errores
+            /// * `NoEsAdministrador`: Quien llama no es el administrador.
+            /// * `ConfiguracionInvalida`: `bloques` es 0.
+            #[ink(message)]
+            pub fn configurar_plazo_calificacion(&mut self, bloques: BlockNumber) -> Result<(), SistemaError> {
+                self.verificar_administrador(self.env().caller())?;
+                if bloques == 0 {
+                    return Err(SistemaError::ConfiguracionInvalida);
+                }
+                self.bloques_plazo_calificacion = bloques;
+                Ok(())
+            }
+
+            /// Vigencia y período de gracia vigentes de las publicaciones.
+            #[ink(message)]
+            pub fn obtener_duracion_publicacion(&self) -> DuracionPublicacion {
+                self.duracion_publicacion.clone()
+            }
+
+            /// Cambia la vigencia de las publicaciones nuevas o renovadas y el período de
+            /// gracia antes de poder eliminarlas. No modifica vencimientos ya fijados.
+            ///
+            /// # Errores
+            /// * `NoEsAdministrador`: Quien llama no es el administrador.
+            /// * `ConfiguracionInvalida`: La vigencia es 0.
+            #[ink(message)]
+            pub fn configurar_duracion_publicacion(&mut self, duracion: DuracionPublicacion) -> Result<(), SistemaError> {
+                self.verificar_administrador(self.env().caller())?;
+                if duracion.vigencia == 0 {
+                    return Err(SistemaError::ConfiguracionInvalida);
+                }
+                self.duracion_publicacion = duracion;
+                Ok(())
+            }
+
             /// Registra un nuevo usuario con el rol especificado.
             #[ink(message)]
             pub fn registrar_usuario(&mut self, rol: RolUsuario) -> Result<(), SistemaError> {
@@ -214,7 +1608,7 @@
             /// * `false` en caso contrario.
             #[ink(message)]
             pub fn esta_registrado(&self, usuario: AccountId) -> bool {
-            self.usuarios.contains(&usuario)
+            self.existe_usuario(usuario)
         }
 
         
@@ -228,103 +1622,309 @@
             /// * `None` si el usuario no está registrado.
             #[ink(message)]
             pub fn obtener_usuario(&self, usuario: AccountId) -> Option<Usuario> {
-            self.usuarios.get(&usuario)
+            self.usuario(usuario)
         }
 
+            /// Reputación de un usuario con promedios en punto fijo y cantidad de
+            /// calificaciones, junto a los puntajes ponderados por importe y antigüedad.
+            #[ink(message)]
+            pub fn obtener_reputacion(&self, usuario: AccountId) -> Option<Reputacion> {
+                let ponderada = |rol| self.reputacion_ponderada.get((usuario, rol)).unwrap_or_default().puntaje();
+                self.usuario(usuario).map(|u| Reputacion {
+                    promedio_como_comprador: u.promedio_como_comprador(),
+                    calificaciones_como_comprador: u.calificaciones_como_comprador,
+                    promedio_como_vendedor: u.promedio_como_vendedor(),
+                    calificaciones_como_vendedor: u.calificaciones_como_vendedor,
+                    ponderada_como_comprador: ponderada(RolCalificado::Comprador),
+                    ponderada_como_vendedor: ponderada(RolCalificado::Vendedor),
+                })
+            }
+
         /// Registro interno de usuario.
         fn registrar_usuario_interno(&mut self, rol: RolUsuario) -> Result<(), SistemaError> {
             let usuario_llamador = self.env().caller();
             // Verifica si el usuario es existente
-            if self.usuarios.contains(&usuario_llamador) { 
+            if self.existe_usuario(usuario_llamador) { 
                 return Err(SistemaError::UsuarioExistente);
             }
             // Si no existe, crea un nuevo usuario
             let nuevo_usuario = Usuario {
                 direccion: usuario_llamador,
-                rol,
+                permisos: Permisos::from(rol),
                 reputacion_como_comprador: 0,
                 reputacion_como_vendedor: 0,
+                calificaciones_como_comprador: 0,
+                calificaciones_como_vendedor: 0,
+                perfil: PerfilUsuario::default(),
+                verificacion: None,
             };
-            self.usuarios.insert(usuario_llamador, &nuevo_usuario);
+            self.guardar_usuario(&nuevo_usuario);
             self.lista_usuarios_ids.push(usuario_llamador);
             
             Ok(())
         }
 
 
-            /// Modifica el rol de un usuario existente.
-            ///
-            /// # Parámetros
-            /// * `nuevo_rol` - El rol al que se desea cambiar (`Comprador`, `Vendedor`, `Ambos`).
+            /// Reemplaza el perfil de quien llama.
             ///
             /// # Errores
             /// * `UsuarioNoRegistrado`: Quien llama no tiene cuenta.
-            /// * `NoEsRolCorrecto`: La transición no es válida (e.g. Vendedor -> Comprador si no es permitido directo) o es el mismo rol actual.
+            /// * `CuentaDadaDeBaja`: La cuenta está cerrada.
+            /// * `PerfilInvalido`: Nombre de más de `MAX_LARGO_NOMBRE_VISIBLE` bytes o con
+            ///   caracteres no permitidos, CID de avatar inválido o región que no son dos letras mayúsculas.
             #[ink(message)]
-            pub fn modificar_rol_usuario(&mut self,nuevo_rol: RolUsuario,) -> Result<(), SistemaError> {
-            self.modificar_rol_usuario_interno(nuevo_rol)
-        }
-
-        fn modificar_rol_usuario_interno(&mut self,nuevo_rol: RolUsuario,) -> Result<(), SistemaError> {
-            let usuario_llamador = self.env().caller();
-            // Verifica que el usuario esté registrado
-            // Obtener el usuario directamente para trabajar con su estado actual
-            let mut usuario = self.usuarios.get(&usuario_llamador)
-                .ok_or(SistemaError::UsuarioNoRegistrado)?;
+            pub fn actualizar_perfil(&mut self, perfil: PerfilUsuario) -> Result<(), SistemaError> {
+                let caller = self.env().caller();
+                let mut usuario = self.usuario(caller).ok_or(SistemaError::UsuarioNoRegistrado)?;
+                self.verificar_cuenta_abierta(caller)?;
+                let nombre_visible = String::from(perfil.nombre_visible.trim());
+                if !Self::texto_valido(&nombre_visible, MAX_LARGO_NOMBRE_VISIBLE)
+                    || perfil.avatar.as_ref().is_some_and(|cid| !cid_valido(cid))
+                    || perfil.region_envio.is_some_and(|region| !region.iter().all(u8::is_ascii_uppercase))
+                {
+                    return Err(SistemaError::PerfilInvalido);
+                }
+                usuario.perfil = PerfilUsuario { nombre_visible, ..perfil };
+                self.guardar_usuario(&usuario);
 
-            // No se permite "cambiar" al mismo rol
-            if usuario.rol == nuevo_rol {
-                return Err(SistemaError::NoEsRolCorrecto);
+                self.env().emit_event(PerfilActualizado { cuenta: caller, perfil: usuario.perfil });
+                Ok(())
             }
 
-            // Validar transiciones permitidas
-            match usuario.rol {
-                // Usuarios con rol Ambos pueden cambiar a cualquier otro rol (ya filtrado el mismo)
-                RolUsuario::Ambos => { /* permitido */ },
-                // Comprador solo puede cambiar a Vendedor
-                RolUsuario::Comprador => {
-                    if nuevo_rol != RolUsuario::Vendedor {
-                        return Err(SistemaError::NoEsRolCorrecto);
-                    }
-                }
-                // Vendedor solo puede cambiar a Comprador
-                RolUsuario::Vendedor => {
-                    if nuevo_rol != RolUsuario::Comprador {
-                        return Err(SistemaError::NoEsRolCorrecto);
-                    }
-                }
+            /// Campos públicos del perfil de un usuario.
+            /// * `None` si el usuario no está registrado.
+            #[ink(message)]
+            pub fn obtener_perfil_publico(&self, usuario: AccountId) -> Option<PerfilPublico> {
+                self.usuario(usuario).map(|u| PerfilPublico {
+                    direccion: u.direccion,
+                    permisos: u.permisos,
+                    nombre_visible: u.perfil.nombre_visible,
+                    avatar: u.perfil.avatar,
+                    region_envio: u.perfil.region_envio,
+                })
             }
 
-            // Guarda rol anterior para el evento y actualiza el mapping
-            let rol_anterior = usuario.rol.clone();
-            usuario.rol = nuevo_rol.clone();
-            self.usuarios.insert(usuario_llamador, &usuario);
-
-            // Evento
-            self.env().emit_event(RolActualizado {
-                cuenta: usuario_llamador,
-                rol_anterior,
-                rol_nuevo: nuevo_rol,
-            });
-
-            Ok(())
-        }
+            /// `true` si la cuenta es verificador designado o el administrador.
+            #[ink(message)]
+            pub fn es_verificador(&self, cuenta: AccountId) -> bool {
+                cuenta == self.administrador || self.verificadores.contains(cuenta)
+            }
 
+            /// Designa (`activo = true`) o remueve un verificador.
+            ///
+            /// # Errores
+            /// * `NoEsAdministrador`: Quien llama no es el administrador.
+            #[ink(message)]
+            pub fn configurar_verificador(&mut self, cuenta: AccountId, activo: bool) -> Result<(), SistemaError> {
+                self.verificar_administrador(self.env().caller())?;
+                if activo {
+                    self.verificadores.insert(cuenta, &());
+                } else {
+                    self.verificadores.remove(cuenta);
+                }
+                self.env().emit_event(VerificadorActualizado { cuenta, activo });
+                Ok(())
+            }
 
-            /// Publica un nuevo producto en el catálogo.
+            /// Marca a un usuario como verificado (o reemplaza su verificación).
             ///
             /// # Parámetros
-            /// * `nombre` - Nombre del producto.
-            /// * `descripcion` - Breve descripción.
-            /// * `precio` - Costo unitario.
-            /// * `cantidad` - Stock inicial disponible.
-            /// * `categoria` - Categoría para agrupación.
+            /// * `nivel` - Nivel de verificación, mayor a 0.
+            /// * `expira_en` - Timestamp (ms) de vencimiento, posterior al actual.
             ///
             /// # Errores
-            /// * `UsuarioNoRegistrado`: El caller no existe.
-            /// * `NoEsRolCorrecto`: El caller no es Vendedor o Ambos.
+            /// * `NoEsVerificador`, `UsuarioNoRegistrado`.
+            /// * `VerificacionInvalida`: Nivel 0 o vencimiento no futuro.
+            #[ink(message)]
+            pub fn verificar_usuario(&mut self, cuenta: AccountId, nivel: u8, expira_en: Timestamp) -> Result<(), SistemaError> {
+                let verificador = self.env().caller();
+                if !self.es_verificador(verificador) {
+                    return Err(SistemaError::NoEsVerificador);
+                }
+                let mut usuario = self.usuario(cuenta).ok_or(SistemaError::UsuarioNoRegistrado)?;
+                if nivel == 0 || expira_en <= self.env().block_timestamp() {
+                    return Err(SistemaError::VerificacionInvalida);
+                }
+                usuario.verificacion = Some(Verificacion { nivel, expira_en, verificador });
+                self.guardar_usuario(&usuario);
+
+                self.env().emit_event(UsuarioVerificado { cuenta, nivel, expira_en, verificador });
+                Ok(())
+            }
+
+            /// Quita la verificación de un usuario.
+            ///
+            /// # Errores
+            /// * `NoEsVerificador`, `UsuarioNoRegistrado`.
+            #[ink(message)]
+            pub fn revocar_verificacion(&mut self, cuenta: AccountId) -> Result<(), SistemaError> {
+                let verificador = self.env().caller();
+                if !self.es_verificador(verificador) {
+                    return Err(SistemaError::NoEsVerificador);
+                }
+                let mut usuario = self.usuario(cuenta).ok_or(SistemaError::UsuarioNoRegistrado)?;
+                usuario.verificacion = None;
+                self.guardar_usuario(&usuario);
+
+                self.env().emit_event(VerificacionRevocada { cuenta, verificador });
+                Ok(())
+            }
+
+            /// Total máximo de una orden a vendedores sin verificación vigente (`None`: sin límite).
+            #[ink(message)]
+            pub fn obtener_limite_orden_no_verificado(&self) -> Option<Balance> {
+                self.limite_orden_no_verificado
+            }
+
+            /// Cambia el límite que se aplica a vendedores sin verificación vigente en toda
+            /// orden nueva: compras, ofertas aceptadas, paquetes y pujas.
+            ///
+            /// # Errores
+            /// * `NoEsAdministrador`: Quien llama no es el administrador.
+            #[ink(message)]
+            pub fn configurar_limite_orden_no_verificado(&mut self, limite: Option<Balance>) -> Result<(), SistemaError> {
+                self.verificar_administrador(self.env().caller())?;
+                self.limite_orden_no_verificado = limite;
+                Ok(())
+            }
+
+            /// Modifica el rol de un usuario existente; equivale a `actualizar_permisos`
+            /// con los permisos del rol. Se puede pasar a cualquier otro rol.
+            ///
+            /// # Parámetros
+            /// * `nuevo_rol` - El rol al que se desea cambiar (`Comprador`, `Vendedor`, `Ambos`).
+            ///
+            /// # Errores
+            /// * `UsuarioNoRegistrado`: Quien llama no tiene cuenta.
+            /// * `NoEsRolCorrecto`: Es el mismo rol actual.
+            #[ink(message)]
+            pub fn modificar_rol_usuario(&mut self,nuevo_rol: RolUsuario,) -> Result<(), SistemaError> {
+            self.modificar_permisos_interno(Permisos::from(nuevo_rol))
+        }
+
+            /// Habilita o deshabilita por separado la compra y la venta para quien llama.
+            ///
+            /// # Errores
+            /// * `UsuarioNoRegistrado`: Quien llama no tiene cuenta.
+            /// * `PermisosInvalidos`: Deshabilita ambas capacidades.
+            /// * `NoEsRolCorrecto`: Son los mismos permisos actuales.
+            #[ink(message)]
+            pub fn actualizar_permisos(&mut self, permisos: Permisos) -> Result<(), SistemaError> {
+                self.modificar_permisos_interno(permisos)
+            }
+
+        fn modificar_permisos_interno(&mut self, permisos: Permisos) -> Result<(), SistemaError> {
+            let usuario_llamador = self.env().caller();
+            let mut usuario = self.usuario(usuario_llamador)
+                .ok_or(SistemaError::UsuarioNoRegistrado)?;
+            self.verificar_cuenta_abierta(usuario_llamador)?;
+            if permisos.vacios() {
+                return Err(SistemaError::PermisosInvalidos);
+            }
+            // No se permite "cambiar" a los mismos permisos
+            if usuario.permisos == permisos {
+                return Err(SistemaError::NoEsRolCorrecto);
+            }
+
+            // Guarda el rol anterior para el evento y actualiza el mapping
+            let rol_anterior = usuario.permisos.rol();
+            usuario.permisos = permisos;
+            self.guardar_usuario(&usuario);
+
+            self.env().emit_event(RolActualizado {
+                cuenta: usuario_llamador,
+                rol_anterior,
+                rol_nuevo: permisos.rol(),
+            });
+
+            Ok(())
+        }
+
+            /// Da de baja la cuenta de quien llama: despublica sus productos, rechaza sus
+            /// ofertas sin resolver (como comprador o vendedor), la quita del listado de
+            /// `obtener_todos_los_usuarios` y la marca como cerrada. Sus órdenes,
+            /// calificaciones y reputación se conservan; la cuenta no puede volver a operar.
+            ///
+            /// # Retorno
+            /// Cantidad de productos despublicados.
+            ///
+            /// # Errores
+            /// * `UsuarioNoRegistrado`: Quien llama no tiene cuenta.
+            /// * `CuentaDadaDeBaja`: Ya se dio de baja.
+            /// * `SaldoPendiente`: Tiene fondos por retirar.
+            /// * `OperacionesAbiertas`: Participa en órdenes `Pendiente`, `Enviada`, `Preventa`
+            ///   o `EnDisputa`, o en subastas sin finalizar como vendedor o mejor postor.
+            #[ink(message)]
+            pub fn darse_de_baja(&mut self) -> Result<u32, SistemaError> {
+                let cuenta = self.env().caller();
+                if !self.existe_usuario(cuenta) { return Err(SistemaError::UsuarioNoRegistrado); }
+                self.verificar_cuenta_abierta(cuenta)?;
+                if self.obtener_saldo_pendiente(cuenta) > 0 { return Err(SistemaError::SaldoPendiente); }
+
+                let productos = self.productos_vendedor.get(cuenta).unwrap_or_default();
+                let orden_abierta = self.ordenes_usuario
+                    .get(cuenta)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .any(|id| self.ordenes.get(id).is_some_and(|o| o.abierta()));
+                let subasta_abierta = self.subastas_liderando.get(cuenta).unwrap_or(0) > 0
+                    || productos.iter().any(|id| self.subastas.get(id).is_some_and(|s| !s.finalizada));
+                if orden_abierta || subasta_abierta { return Err(SistemaError::OperacionesAbiertas); }
+
+                let mut productos_despublicados = 0;
+                for id in productos {
+                    if let Some(mut prod) = self.productos.get(id) {
+                        if prod.activo {
+                            prod.activo = false;
+                            self.productos.insert(id, &prod);
+                            productos_despublicados += 1;
+                        }
+                    }
+                }
+                for oferta_id in self.ofertas_usuario.take(cuenta).unwrap_or_default() {
+                    if let Some(mut oferta) = self.ofertas.get(oferta_id).filter(Oferta::abierta) {
+                        oferta.estado = EstadoOferta::Rechazada;
+                        self.ofertas.insert(oferta_id, &oferta);
+                        self.env().emit_event(OfertaRechazada { oferta_id, por: cuenta });
+                    }
+                }
+
+                self.lista_usuarios_ids.retain(|id| *id != cuenta);
+                self.cuentas_cerradas.insert(cuenta, &self.env().block_number());
+                self.env().emit_event(UsuarioDadoDeBaja { cuenta, productos_despublicados });
+                Ok(productos_despublicados)
+            }
+
+            /// `true` si `cuenta` se dio de baja.
+            #[ink(message)]
+            pub fn cuenta_dada_de_baja(&self, cuenta: AccountId) -> bool {
+                self.cuentas_cerradas.contains(cuenta)
+            }
+
+        /// Falla si la cuenta se dio de baja.
+        fn verificar_cuenta_abierta(&self, cuenta: AccountId) -> Result<(), SistemaError> {
+            if self.cuentas_cerradas.contains(cuenta) { return Err(SistemaError::CuentaDadaDeBaja); }
+            Ok(())
+        }
+
+
+            /// Publica un nuevo producto en el catálogo.
+            ///
+            /// # Parámetros
+            /// * `nombre` - Nombre del producto.
+            /// * `descripcion` - Breve descripción.
+            /// * `precio` - Costo unitario.
+            /// * `cantidad` - Stock inicial disponible.
+            /// * `categoria` - Categoría para agrupación.
+            ///
+            /// # Errores
+            /// * `UsuarioNoRegistrado`: El caller no existe.
+            /// * `NoEsRolCorrecto`: El caller no es Vendedor o Ambos.
             /// * `CantidadInsuficiente`: El stock inicial es 0.
-            /// * `ProductosVacios`: Error interno al generar ID.
+            /// * `PrecioInvalido`: El precio es 0.
+            /// * `NombreInvalido`: El nombre está vacío o es demasiado largo.
+            /// * `DescripcionDemasiadoLarga` / `CategoriaDemasiadoLarga`: Superan el límite configurado.
+            /// * `DesbordamientoId`: No quedan IDs de producto disponibles.
             #[ink(message)]
             pub fn publicar_producto(
                 &mut self,
@@ -352,12 +1952,284 @@
             self.verificar_rol(vendedor, RolUsuario::Vendedor)?;
             // Verifica que la cantidad sea válida
             self.verificar_cantidad(cantidad)?;
+            // Normaliza y valida precio y textos
+            let (nombre, descripcion, categoria) = self.validar_datos_producto(nombre, descripcion, precio, categoria)?;
             // Agrega el producto al marketplace
-            self.agregar_producto(nombre, descripcion, precio, cantidad, categoria, vendedor)
+            self.agregar_producto(nombre, descripcion, precio, cantidad, categoria, vendedor)?;
+            Ok(())
+        }
+
+            /// Publica varios productos en una sola transacción: se publican todos o ninguno.
+            ///
+            /// Cada entrada se valida con las mismas reglas que `publicar_producto`
+            /// y se emite un `ProductoPublicado` por cada una.
+            ///
+            /// # Retorno
+            /// * `Ok(Vec<u32>)`: Los IDs asignados, en el mismo orden del lote.
+            ///
+            /// # Errores
+            /// * `TamanoLoteInvalido`: El lote está vacío o supera `MAX_LOTE_PRODUCTOS`.
+            /// * El primer error de validación encontrado (ver `publicar_producto`).
+            /// * `DesbordamientoId`: No quedan IDs para todo el lote.
+            #[ink(message)]
+            pub fn publicar_productos_lote(&mut self, productos: Vec<NuevoProducto>) -> Result<Vec<u32>, SistemaError> {
+                let vendedor = self.env().caller();
+                self.verificar_registro(vendedor)?;
+                self.verificar_rol(vendedor, RolUsuario::Vendedor)?;
+                if productos.is_empty() || productos.len() > MAX_LOTE_PRODUCTOS as usize {
+                    return Err(SistemaError::TamanoLoteInvalido);
+                }
+
+                // Validar todo el lote antes de escribir
+                let mut validados = Vec::new();
+                for p in productos {
+                    self.verificar_cantidad(p.cantidad)?;
+                    let (nombre, descripcion, categoria) = self.validar_datos_producto(p.nombre, p.descripcion, p.precio, p.categoria)?;
+                    validados.push((nombre, descripcion, p.precio, p.cantidad, categoria));
+                }
+                self.next_producto_id.checked_add(validados.len() as u32).ok_or(SistemaError::DesbordamientoId)?;
+
+                let mut ids = Vec::new();
+                for (nombre, descripcion, precio, cantidad, categoria) in validados {
+                    ids.push(self.agregar_producto(nombre, descripcion, precio, cantidad, categoria, vendedor)?);
+                }
+                Ok(ids)
+            }
+
+        /// Recorta los espacios de los textos y los valida contra `limites_texto`.
+        fn validar_datos_producto(
+            &self,
+            nombre: String,
+            descripcion: String,
+            precio: Balance,
+            categoria: String,
+        ) -> Result<(String, String, String), SistemaError> {
+            if precio == 0 {
+                return Err(SistemaError::PrecioInvalido);
+            }
+            let limites = &self.limites_texto;
+
+            let nombre = nombre.trim();
+            if nombre.is_empty() || !Self::texto_valido(nombre, limites.max_nombre) {
+                return Err(SistemaError::NombreInvalido);
+            }
+            let descripcion = descripcion.trim();
+            if !Self::texto_valido(descripcion, limites.max_descripcion) {
+                return Err(SistemaError::DescripcionDemasiadoLarga);
+            }
+            let categoria = categoria.trim();
+            if !Self::texto_valido(categoria, limites.max_categoria) {
+                return Err(SistemaError::CategoriaDemasiadoLarga);
+            }
+            Ok((String::from(nombre), String::from(descripcion), String::from(categoria)))
+        }
+
+        /// Normaliza los atributos de una variante y verifica que no repita los de otra.
+        fn validar_variante(nueva: NuevaVariante, id: u32, existentes: &[Variante]) -> Result<Variante, SistemaError> {
+            if nueva.precio == Some(0) {
+                return Err(SistemaError::PrecioInvalido);
+            }
+            if nueva.atributos.is_empty() || nueva.atributos.len() > MAX_ATRIBUTOS_VARIANTE as usize {
+                return Err(SistemaError::AtributosInvalidos);
+            }
+            let mut atributos: Vec<(String, String)> = Vec::new();
+            for (clave, valor) in nueva.atributos {
+                let (clave, valor) = (clave.trim(), valor.trim());
+                if clave.is_empty() || valor.is_empty()
+                    || !Self::texto_valido(clave, MAX_LARGO_ATRIBUTO)
+                    || !Self::texto_valido(valor, MAX_LARGO_ATRIBUTO)
+                    || atributos.iter().any(|(c, _)| c == clave)
+                {
+                    return Err(SistemaError::AtributosInvalidos);
+                }
+                atributos.push((String::from(clave), String::from(valor)));
+            }
+            atributos.sort();
+            if existentes.iter().any(|v| v.atributos == atributos) {
+                return Err(SistemaError::AtributosInvalidos);
+            }
+            Ok(Variante { id, atributos, precio: nueva.precio, cantidad: nueva.cantidad })
+        }
+
+        /// Obtiene el producto verificando que pertenezca a `vendedor`.
+        fn producto_del_vendedor(&self, vendedor: AccountId, producto_id: u32) -> Result<Producto, SistemaError> {
+            let prod = self.productos.get(producto_id).ok_or(SistemaError::ProductoNoExiste)?;
+            if prod.vendedor != vendedor {
+                return Err(SistemaError::NoEsVendedorDelProducto);
+            }
+            self.verificar_cuenta_abierta(vendedor)?;
+            Ok(prod)
+        }
+
+        /// El texto entra en `max_bytes` y no tiene caracteres de control ni de ancho cero.
+        fn texto_valido(texto: &str, max_bytes: u32) -> bool {
+            texto.len() <= max_bytes as usize
+                && !texto.chars().any(|c| c.is_control() || matches!(c, '\u{200B}'..='\u{200F}' | '\u{FEFF}'))
         }
 
         
 
+            /// Publica un producto con variantes (talle, color, ...), cada una con su stock.
+            ///
+            /// # Parámetros
+            /// * `precio` - Precio base; cada variante puede reemplazarlo.
+            /// * `variantes` - Entre 1 y `MAX_VARIANTES` variantes.
+            ///
+            /// # Retorno
+            /// * `Ok(u32)`: El ID del producto publicado.
+            ///
+            /// # Errores
+            /// * Los mismos que `publicar_producto`; `CantidadInsuficiente` si la suma de stocks es 0.
+            /// * `VarianteRequerida`: La lista de variantes está vacía.
+            /// * `AtributosInvalidos`, `LimiteVariantesAlcanzado`: Variantes mal formadas o demasiadas.
+            #[ink(message)]
+            pub fn publicar_producto_con_variantes(
+                &mut self,
+                nombre: String,
+                descripcion: String,
+                precio: Balance,
+                categoria: String,
+                variantes: Vec<NuevaVariante>,
+            ) -> Result<u32, SistemaError> {
+                let vendedor = self.env().caller();
+                self.verificar_rol(vendedor, RolUsuario::Vendedor)?;
+                if variantes.is_empty() {
+                    return Err(SistemaError::VarianteRequerida);
+                }
+                if variantes.len() > MAX_VARIANTES as usize {
+                    return Err(SistemaError::LimiteVariantesAlcanzado);
+                }
+                let mut validadas: Vec<Variante> = Vec::new();
+                let mut cantidad: u32 = 0;
+                for nueva in variantes {
+                    let variante = Self::validar_variante(nueva, validadas.len() as u32, &validadas)?;
+                    cantidad = cantidad.saturating_add(variante.cantidad);
+                    validadas.push(variante);
+                }
+                self.verificar_cantidad(cantidad)?;
+                let (nombre, descripcion, categoria) = self.validar_datos_producto(nombre, descripcion, precio, categoria)?;
+
+                let id = self.agregar_producto(nombre, descripcion, precio, cantidad, categoria, vendedor)?;
+                if let Some(mut prod) = self.productos.get(id) {
+                    prod.variantes = validadas;
+                    self.productos.insert(id, &prod);
+                }
+                Ok(id)
+            }
+
+            /// Agrega una variante a un producto que ya tiene variantes.
+            ///
+            /// # Retorno
+            /// * `Ok(u32)`: El ID de la nueva variante.
+            ///
+            /// # Errores
+            /// * `ProductoNoExiste`, `NoEsVendedorDelProducto`.
+            /// * `VarianteRequerida`: El producto se publicó sin variantes.
+            /// * `LimiteVariantesAlcanzado`, `AtributosInvalidos`, `PrecioInvalido`.
+            #[ink(message)]
+            pub fn agregar_variante(&mut self, producto_id: u32, variante: NuevaVariante) -> Result<u32, SistemaError> {
+                let mut prod = self.producto_del_vendedor(self.env().caller(), producto_id)?;
+                if prod.variantes.is_empty() {
+                    return Err(SistemaError::VarianteRequerida);
+                }
+                if prod.variantes.len() >= MAX_VARIANTES as usize {
+                    return Err(SistemaError::LimiteVariantesAlcanzado);
+                }
+                let variante_id = prod.variantes.len() as u32;
+                let variante = Self::validar_variante(variante, variante_id, &prod.variantes)?;
+                prod.cantidad = prod.cantidad.saturating_add(variante.cantidad);
+                prod.variantes.push(variante);
+                self.productos.insert(producto_id, &prod);
+
+                self.env().emit_event(VarianteAgregada { producto_id, variante_id });
+                Ok(variante_id)
+            }
+
+            /// Referencias multimedia de un producto, en orden de presentación.
+            #[ink(message)]
+            pub fn obtener_medios_producto(&self, producto_id: u32) -> Vec<Cid> {
+                self.medios_producto.get(producto_id).unwrap_or_default()
+            }
+
+            /// Asocia un CID de IPFS (imagen o video) al final de la lista del producto.
+            ///
+            /// # Errores
+            /// * `ProductoNoExiste`, `NoEsVendedorDelProducto`.
+            /// * `CidInvalido`: Los bytes no son un CID v0/v1 válido.
+            /// * `MedioDuplicado`, `LimiteMediosAlcanzado`.
+            #[ink(message)]
+            pub fn agregar_medio(&mut self, producto_id: u32, cid: Cid) -> Result<(), SistemaError> {
+                self.producto_del_vendedor(self.env().caller(), producto_id)?;
+                if !cid_valido(&cid) {
+                    return Err(SistemaError::CidInvalido);
+                }
+                let mut medios = self.obtener_medios_producto(producto_id);
+                if medios.contains(&cid) {
+                    return Err(SistemaError::MedioDuplicado);
+                }
+                if medios.len() >= MAX_MEDIOS_POR_PRODUCTO as usize {
+                    return Err(SistemaError::LimiteMediosAlcanzado);
+                }
+                medios.push(cid.clone());
+                self.medios_producto.insert(producto_id, &medios);
+
+                self.env().emit_event(MedioAgregado { producto_id, cid });
+                Ok(())
+            }
+
+            /// Quita un CID de la lista del producto.
+            ///
+            /// # Errores
+            /// * `ProductoNoExiste`, `NoEsVendedorDelProducto`.
+            /// * `MedioNoExiste`: El CID no estaba asociado.
+            #[ink(message)]
+            pub fn eliminar_medio(&mut self, producto_id: u32, cid: Cid) -> Result<(), SistemaError> {
+                self.producto_del_vendedor(self.env().caller(), producto_id)?;
+                let mut medios = self.obtener_medios_producto(producto_id);
+                let posicion = medios.iter().position(|m| *m == cid).ok_or(SistemaError::MedioNoExiste)?;
+                medios.remove(posicion);
+                if medios.is_empty() {
+                    self.medios_producto.remove(producto_id);
+                } else {
+                    self.medios_producto.insert(producto_id, &medios);
+                }
+
+                self.env().emit_event(MedioEliminado { producto_id, cid });
+                Ok(())
+            }
+
+            /// Reordena las referencias del producto.
+            ///
+            /// # Parámetros
+            /// * `nuevo_orden` - Posiciones actuales en el orden deseado (p. ej. `[2, 0, 1]`).
+            ///
+            /// # Errores
+            /// * `ProductoNoExiste`, `NoEsVendedorDelProducto`.
+            /// * `ReordenamientoInvalido`: No es una permutación de las posiciones actuales.
+            #[ink(message)]
+            pub fn reordenar_medios(&mut self, producto_id: u32, nuevo_orden: Vec<u32>) -> Result<(), SistemaError> {
+                self.producto_del_vendedor(self.env().caller(), producto_id)?;
+                let medios = self.obtener_medios_producto(producto_id);
+                if nuevo_orden.len() != medios.len() {
+                    return Err(SistemaError::ReordenamientoInvalido);
+                }
+                let mut usados = Vec::new();
+                usados.resize(medios.len(), false);
+                let mut reordenados: Vec<Cid> = Vec::new();
+                for posicion in nuevo_orden {
+                    let usado = usados.get_mut(posicion as usize).ok_or(SistemaError::ReordenamientoInvalido)?;
+                    if *usado {
+                        return Err(SistemaError::ReordenamientoInvalido);
+                    }
+                    *usado = true;
+                    reordenados.push(medios[posicion as usize].clone());
+                }
+                self.medios_producto.insert(producto_id, &reordenados);
+
+                self.env().emit_event(MediosReordenados { producto_id, cids: reordenados });
+                Ok(())
+            }
+
             /// Lista todos los productos publicados por el usuario que llama (sus propios productos).
             ///
             /// # Retorno
@@ -407,10 +2279,11 @@
         }
 
         pub fn listar_productos_por_vendedor_interno(&self, vendedor: AccountId) -> Result<Vec<Producto>, SistemaError> {
+            let ahora = self.env().block_timestamp();
             let mut productos = Vec::new();
             for id in 0..self.next_producto_id {
                 if let Some(prod) = self.productos.get(id) {
-                    if prod.vendedor == vendedor {
+                    if prod.vendedor == vendedor && prod.activo && ahora < prod.expira_en {
                         productos.push(prod);
                     }
                 }
@@ -425,915 +2298,3785 @@
             ///
             /// # Parámetros
             /// * `producto_id` - ID del producto a comprar.
+            /// * `variante_id` - Variante elegida; obligatoria si el producto tiene variantes.
             /// * `cantidad` - Unidades a adquirir.
             ///
             /// # Retorno
             /// * `Ok(u32)`: El ID de la orden generada.
             ///
             /// # Errores
-            /// * `StockInsuficiente`: El producto no tiene tantas unidades.
-            /// * `ProductosVacios`: El producto no existe.
+            /// * `StockInsuficiente`: El producto (o la variante) no tiene tantas unidades.
+            /// * `ProductoNoExiste`: El producto no existe.
+            /// * `VarianteRequerida` / `VarianteNoExiste`: Variante omitida o inválida.
             /// * `NoEsRolCorrecto`: El comprador no tiene el rol adecuado.
+            /// * `CuentaDadaDeBaja`: El comprador o el vendedor se dio de baja.
+            /// * `DesbordamientoId`: No quedan IDs de orden disponibles.
+            #[ink(message)]
+            pub fn crear_orden(&mut self, producto_id: u32, variante_id: Option<u32>, cantidad: u32) -> Result<u32, SistemaError> {
+            self.crear_orden_interna(self.env().caller(), producto_id, variante_id, cantidad, None, None)
+        }
+
+            /// Igual que `crear_orden`, pero aplica un cupón del vendedor al total.
+            /// El uso del cupón se registra junto con la orden: si algo falla no se consume.
+            ///
+            /// # Parámetros
+            /// * `codigo` - Código del cupón en claro; se busca por su hash Blake2x256.
+            ///
+            /// # Errores
+            /// * Los mismos que `crear_orden`.
+            /// * `CuponNoExiste`, `CuponExpirado`, `CuponAgotado`.
+            /// * `CuponNoAplica`: El cupón es de otro producto o categoría.
             #[ink(message)]
-            pub fn crear_orden(&mut self, producto_id: u32, cantidad: u32) -> Result<u32, SistemaError> {
-            let comprador = self.env().caller();
-            if !self.usuarios.contains(&comprador) { return Err(SistemaError::UsuarioNoRegistrado); }
-            let u = self.usuarios.get(&comprador).unwrap();
-            match u.rol {
-                RolUsuario::Comprador | RolUsuario::Ambos => {},
-                _ => return Err(SistemaError::NoEsRolCorrecto),
+            pub fn crear_orden_con_cupon(
+                &mut self,
+                producto_id: u32,
+                variante_id: Option<u32>,
+                cantidad: u32,
+                codigo: Vec<u8>,
+            ) -> Result<u32, SistemaError> {
+                self.crear_orden_interna(self.env().caller(), producto_id, variante_id, cantidad, Some(hash_codigo_cupon(&codigo)), None)
             }
 
+        /// Crea una orden de precio fijo para `comprador` con todas las validaciones de
+        /// `crear_orden`. Con `precio_acordado` (ofertas aceptadas) ese precio unitario
+        /// reemplaza al de lista, los tramos y el descuento programado.
+        fn crear_orden_interna(
+            &mut self,
+            comprador: AccountId,
+            producto_id: u32,
+            variante_id: Option<u32>,
+            cantidad: u32,
+            hash_cupon: Option<HashCupon>,
+            precio_acordado: Option<Balance>,
+        ) -> Result<u32, SistemaError> {
+            if !self.existe_usuario(comprador) { return Err(SistemaError::UsuarioNoRegistrado); }
+            let u = self.usuario(comprador).unwrap();
+            self.verificar_cuenta_abierta(comprador)?;
+            if !u.permisos.comprar { return Err(SistemaError::NoEsRolCorrecto); }
+
             if cantidad == 0 { return Err(SistemaError::CantidadInsuficiente); }
 
             // Obtener producto y validar stock
-            let mut prod = self.productos.get(producto_id).ok_or(SistemaError::ProductosVacios)?;
-            if prod.cantidad < cantidad { return Err(SistemaError::StockInsuficiente); }
-            let vendedor_addr = prod.vendedor;
-
-            // Actualizar stock
-            prod.cantidad = prod.cantidad.saturating_sub(cantidad);
-            self.productos.insert(producto_id, &prod);
+            let mut prod = self.productos.get(producto_id).ok_or(SistemaError::ProductoNoExiste)?;
+            if prod.modo_venta != ModoVenta::PrecioFijo { return Err(SistemaError::ProductoEnSubasta); }
+            self.verificar_cuenta_abierta(prod.vendedor)?;
+            self.verificar_disponible(&prod)?;
+            if prod.tipo == TipoProducto::Digital && !self.claves_publicas.contains(comprador) {
+                return Err(SistemaError::ClavePublicaRequerida);
+            }
+            let (precio_unitario, stock) = prod.precio_y_stock(variante_id)?;
+            // Lo que falte de stock se toma como preventa, si el producto la acepta. Con
+            // preventas en cola el stock les corresponde a ellas: la orden va entera a la cola.
+            let en_cola = self.preventas.get(producto_id).is_some_and(|p| !p.cola.is_empty());
+            let pendientes = if en_cola { cantidad } else { cantidad.saturating_sub(stock) };
+            if pendientes > 0 {
+                self.verificar_preventa(producto_id, pendientes)?;
+            }
 
-            // Crear orden
-            let id = self.next_orden_id;
-            // Incrementar ID de orden
-             self.next_orden_id = self.next_orden_id.checked_add(1).ok_or(SistemaError::ProductosVacios)?;
+            let (mut total, con_descuento) = match precio_acordado {
+                Some(precio) => (precio.saturating_mul(Balance::from(cantidad)), 0),
+                None => self.cotizar(producto_id, precio_unitario, cantidad, prod.usa_precio_base(variante_id)),
+            };
+            let cupon = match hash_cupon {
+                Some(hash) => {
+                    let cupon = self.cupon_aplicable(&prod, hash)?;
+                    let rebaja = match cupon.valor {
+                        ValorCupon::Porcentaje(p) => total.saturating_mul(Balance::from(p)) / 100,
+                        ValorCupon::Fijo(monto) => monto.min(total),
+                    };
+                    total = total.saturating_sub(rebaja);
+                    Some((hash, cupon, rebaja))
+                }
+                None => None,
+            };
+            let vendedor = prod.vendedor;
+            self.verificar_limite_no_verificado(vendedor, total)?;
 
-            let nueva = Orden::nueva(id, comprador, vendedor_addr, producto_id, cantidad);
-            self.ordenes.insert(id, &nueva);
+            prod.descontar_stock(variante_id, cantidad - pendientes);
+            let id = self.registrar_orden(comprador, prod, variante_id, cantidad, total, 0)?;
+            if pendientes > 0 {
+                self.encolar_preventa(producto_id, id, pendientes);
+            }
+            if con_descuento > 0 {
+                self.consumir_descuento(producto_id, con_descuento);
+            }
+            self.notificar_descuento(producto_id);
+            if let Some((hash_codigo, mut cupon, descuento)) = cupon {
+                cupon.usos = cupon.usos.saturating_add(1);
+                self.cupones.insert((vendedor, hash_codigo), &cupon);
+                self.env().emit_event(CuponUsado { vendedor, hash_codigo, orden_id: id, descuento });
+            }
             Ok(id)
         }
-        
 
-            /// Cambia el estado de una orden a `Enviada`.
-            ///
-            /// # Requisitos
-            /// * Solo el **Vendedor** de la orden puede ejecutar esto.
-            /// * La orden debe estar en estado `Pendiente`.
-            #[ink(message)]
-            pub fn marcar_orden_como_enviada(&mut self, orden_id: u32) -> Result<(), SistemaError> {
-            self.actualizar_estado_orden(orden_id, EstadoOrden::Enviada)
+        /// Verifica que el producto acepte `pendientes` unidades más en preventa.
+        fn verificar_preventa(&self, producto_id: u32, pendientes: u32) -> Result<(), SistemaError> {
+            let preventa = match self.preventas.get(producto_id) {
+                Some(p) if p.activa => p,
+                _ => return Err(SistemaError::StockInsuficiente),
+            };
+            let comprometidas = preventa.unidades_comprometidas.saturating_add(pendientes);
+            if comprometidas > preventa.maximo_unidades || preventa.cola.len() >= MAX_COLA_PREVENTA as usize {
+                return Err(SistemaError::LimitePreventaAlcanzado);
+            }
+            Ok(())
         }
 
-
-            /// Cambia el estado de una orden a `Recibida`.
-            ///
-            /// # Requisitos
-            /// * Solo el **Comprador** de la orden puede ejecutar esto.
-            /// * La orden debe estar en estado `Enviada`.
-            #[ink(message)]
-            pub fn marcar_como_recibida(&mut self, orden_id: u32) -> Result<(), SistemaError> {
-            self.actualizar_estado_orden(orden_id, EstadoOrden::Recibida)
+        /// Pasa la orden recién creada a `Preventa` y la pone al final de la cola.
+        fn encolar_preventa(&mut self, producto_id: u32, orden_id: u32, pendientes: u32) {
+            if let Some(mut orden) = self.ordenes.get(orden_id) {
+                orden.estado = EstadoOrden::Preventa;
+                self.ordenes.insert(orden_id, &orden);
+            }
+            if let Some(mut preventa) = self.preventas.get(producto_id) {
+                preventa.unidades_comprometidas = preventa.unidades_comprometidas.saturating_add(pendientes);
+                preventa.cola.push((orden_id, pendientes));
+                self.preventas.insert(producto_id, &preventa);
+            }
+            self.env().emit_event(PreventaCreada { producto_id, orden_id, unidades_pendientes: pendientes });
         }
 
-        /// Actualiza estado de la orden.
-        fn actualizar_estado_orden(&mut self, orden_id: u32, nuevo_estado: EstadoOrden) -> Result<(), SistemaError> {
-            let caller = self.env().caller();
-            self.verificar_registro(caller)?;
-            
-            let mut orden = self.ordenes.get(orden_id).ok_or(SistemaError::OrdenNoExiste)?;
-            
-            // Verificar permiso (usamos referencia a la copia)
-            self.verificar_permiso_orden(caller, &orden, &nuevo_estado)?;
-            
-            orden.estado = nuevo_estado;
-            self.ordenes.insert(orden_id, &orden);
-            Ok(())
+        /// Asigna `unidades` a las preventas en orden de llegada; lo que sobra vuelve al stock.
+        fn distribuir_stock(&mut self, producto_id: u32, mut unidades: u32) {
+            if let Some(mut preventa) = self.preventas.get(producto_id) {
+                let mut cumplidas = 0;
+                for (orden_id, pendientes) in preventa.cola.iter_mut() {
+                    if unidades == 0 { break; }
+                    let asignadas = unidades.min(*pendientes);
+                    *pendientes -= asignadas;
+                    unidades -= asignadas;
+                    preventa.unidades_comprometidas = preventa.unidades_comprometidas.saturating_sub(asignadas);
+                    if *pendientes == 0 {
+                        cumplidas += 1;
+                        if let Some(mut orden) = self.ordenes.get(*orden_id) {
+                            orden.estado = EstadoOrden::Pendiente;
+                            self.ordenes.insert(*orden_id, &orden);
+                        }
+                        self.env().emit_event(PreventaCumplida { producto_id, orden_id: *orden_id });
+                    }
+                }
+                preventa.cola.drain(..cumplidas);
+                self.preventas.insert(producto_id, &preventa);
+            }
+            if unidades > 0 {
+                if let Some(mut prod) = self.productos.get(producto_id) {
+                    prod.devolver_stock(None, unidades);
+                    self.productos.insert(producto_id, &prod);
+                }
+            }
         }
 
-            /// Permite al comprador calificar su experiencia con el vendedor.
+            /// Habilita (o actualiza) la preventa de un producto sin variantes: cuando no alcance
+            /// el stock, `crear_orden` tomará lo que haya y dejará el resto como preventa.
             ///
             /// # Parámetros
-            /// * `orden_id` - ID de la orden finalizada.
-            /// * `calificacion` - Valor entero entre 1 y 5.
+            /// * `disponible_desde` - Fecha estimada de disponibilidad (timestamp en ms).
+            /// * `maximo_unidades` - Tope de unidades comprometidas sin stock.
             ///
             /// # Errores
-            /// * `CalificacionInvalida`: Si el valor no está entre 1 y 5.
-            /// * `EstadoInvalido`: Si la orden no está `Recibida`.
-            /// * `YaCalificado`: Si ya se emitió voto para esta orden.
+            /// * `ProductoNoExiste`, `NoEsVendedorDelProducto`, `ProductoEnSubasta`.
+            /// * `PreventaNoDisponible`: El producto tiene variantes.
+            /// * `CantidadInsuficiente`: `maximo_unidades` es 0.
             #[ink(message)]
-            pub fn calificar_vendedor(&mut self, orden_id: u32, calificacion: u32) -> Result<(), SistemaError> {
-            let caller = self.env().caller();
-            
-            if !(1..=5).contains(&calificacion) { return Err(SistemaError::CalificacionInvalida); }
+            pub fn habilitar_preventa(&mut self, producto_id: u32, disponible_desde: Timestamp, maximo_unidades: u32) -> Result<(), SistemaError> {
+                let prod = self.producto_del_vendedor(self.env().caller(), producto_id)?;
+                if prod.modo_venta != ModoVenta::PrecioFijo { return Err(SistemaError::ProductoEnSubasta); }
+                if !prod.variantes.is_empty() { return Err(SistemaError::PreventaNoDisponible); }
+                self.verificar_cantidad(maximo_unidades)?;
+
+                let mut preventa = self.preventas.get(producto_id).unwrap_or(Preventa {
+                    activa: true,
+                    disponible_desde,
+                    maximo_unidades,
+                    unidades_comprometidas: 0,
+                    cola: Vec::new(),
+                });
+                preventa.activa = true;
+                preventa.disponible_desde = disponible_desde;
+                preventa.maximo_unidades = maximo_unidades;
+                self.preventas.insert(producto_id, &preventa);
+                Ok(())
+            }
 
-            let mut orden = self.ordenes.get(orden_id).ok_or(SistemaError::OrdenNoExiste)?;
-            if orden.comprador != caller { return Err(SistemaError::NoEsRolCorrecto); }
-            if orden.estado != EstadoOrden::Recibida { return Err(SistemaError::EstadoInvalido); }
-            if orden.comprador_califico { return Err(SistemaError::YaCalificado); }
+            /// Deja de aceptar preventas nuevas. Las que ya están en cola se siguen cumpliendo.
+            ///
+            /// # Errores
+            /// * `ProductoNoExiste`, `NoEsVendedorDelProducto`, `PreventaNoDisponible`.
+            #[ink(message)]
+            pub fn deshabilitar_preventa(&mut self, producto_id: u32) -> Result<(), SistemaError> {
+                self.producto_del_vendedor(self.env().caller(), producto_id)?;
+                let mut preventa = self.preventas.get(producto_id).ok_or(SistemaError::PreventaNoDisponible)?;
+                preventa.activa = false;
+                self.preventas.insert(producto_id, &preventa);
+                Ok(())
+            }
 
-            orden.comprador_califico = true;
-            self.ordenes.insert(orden_id, &orden);
-            
-            // Actualizar reputación
-            let vendedor_addr = orden.vendedor;
-            if let Some(mut v) = self.usuarios.get(&vendedor_addr) {
-                v.reputacion_como_vendedor = v.reputacion_como_vendedor.saturating_add(calificacion);
-                self.usuarios.insert(vendedor_addr, &v);
+            /// Estado de la preventa de un producto, incluida la cola de espera.
+            #[ink(message)]
+            pub fn obtener_preventa(&self, producto_id: u32) -> Option<Preventa> {
+                self.preventas.get(producto_id)
             }
-            
-            self.env().emit_event(CompradorCalifico { orden_id, comprador: caller, vendedor: vendedor_addr, calificacion });
-            Ok(())
-        }
 
+            /// Suma stock a un producto sin variantes. Primero completa las preventas en
+            /// orden de llegada y el resto queda disponible.
+            ///
+            /// # Errores
+            /// * `ProductoNoExiste`, `NoEsVendedorDelProducto`, `CantidadInsuficiente`.
+            /// * `VarianteRequerida`: El producto tiene variantes.
+            #[ink(message)]
+            pub fn reponer_stock(&mut self, producto_id: u32, unidades: u32) -> Result<(), SistemaError> {
+                let prod = self.producto_del_vendedor(self.env().caller(), producto_id)?;
+                if !prod.variantes.is_empty() { return Err(SistemaError::VarianteRequerida); }
+                self.verificar_cantidad(unidades)?;
+                self.distribuir_stock(producto_id, unidades);
+                self.env().emit_event(StockRepuesto { producto_id, unidades });
+                Ok(())
+            }
 
+            /// Permite al comprador cancelar una preventa todavía no cumplida. Las unidades
+            /// que ya tenía asignadas pasan a la siguiente preventa en cola o vuelven al stock.
+            ///
+            /// # Errores
+            /// * `OrdenNoExiste`, `NoEsParteDeLaOrden`.
+            /// * `NoEsRolCorrecto`: Quien llama es el vendedor.
+            /// * `EstadoInvalido`: La orden no está en `Preventa`.
+            #[ink(message)]
+            pub fn cancelar_preventa(&mut self, orden_id: u32) -> Result<(), SistemaError> {
+                let caller = self.env().caller();
+                let mut orden = self.ordenes.get(orden_id).ok_or(SistemaError::OrdenNoExiste)?;
+                Self::verificar_parte_de_orden(caller, &orden)?;
+                if orden.comprador != caller { return Err(SistemaError::NoEsRolCorrecto); }
+                if orden.estado != EstadoOrden::Preventa { return Err(SistemaError::EstadoInvalido); }
+
+                let mut pendientes = 0;
+                if let Some(mut preventa) = self.preventas.get(orden.producto_id) {
+                    if let Some(pos) = preventa.cola.iter().position(|(id, _)| *id == orden_id) {
+                        pendientes = preventa.cola.remove(pos).1;
+                        preventa.unidades_comprometidas = preventa.unidades_comprometidas.saturating_sub(pendientes);
+                        self.preventas.insert(orden.producto_id, &preventa);
+                    }
+                }
+                orden.estado = EstadoOrden::Cancelada;
+                self.ordenes.insert(orden_id, &orden);
 
-        
-            /// Permite al vendedor calificar al comprador.
+                let asignadas = orden.cantidad.saturating_sub(pendientes);
+                if asignadas > 0 {
+                    self.distribuir_stock(orden.producto_id, asignadas);
+                }
+                Ok(())
+            }
+
+        /// Busca el cupón del vendedor del producto y verifica que se pueda usar en él.
+        fn cupon_aplicable(&self, prod: &Producto, hash: HashCupon) -> Result<Cupon, SistemaError> {
+            let cupon = self.cupones.get((prod.vendedor, hash)).ok_or(SistemaError::CuponNoExiste)?;
+            if self.env().block_timestamp() >= cupon.expira_en { return Err(SistemaError::CuponExpirado); }
+            if cupon.usos >= cupon.usos_maximos { return Err(SistemaError::CuponAgotado); }
+            if cupon.producto_id.is_some_and(|id| id != prod.id)
+                || cupon.categoria.as_ref().is_some_and(|c| *c != prod.categoria)
+            {
+                return Err(SistemaError::CuponNoAplica);
+            }
+            Ok(cupon)
+        }
+
+            /// Registra un cupón de descuento para los productos de quien llama.
             ///
             /// # Parámetros
-            /// * `orden_id` - ID de la orden finalizada.
-            /// * `calificacion` - Valor entero entre 1 y 5.
+            /// * `hash_codigo` - `hash_codigo_cupon(codigo)`; el código no se publica on-chain.
+            /// * `valor` - Porcentaje (1 a 100) o monto fijo (mayor a 0).
+            /// * `expira_en` - Timestamp (ms) de vencimiento; debe ser futuro.
+            /// * `usos_maximos` - Cantidad de órdenes en que se puede usar (mayor a 0).
+            /// * `producto_id`, `categoria` - Restricciones opcionales.
             ///
             /// # Errores
-            /// * `CalificacionInvalida`: Si el valor no está entre 1 y 5.
-            /// * `EstadoInvalido`: Si la orden no está `Recibida`.
-            /// * `YaCalificado`: Si ya se emitió voto.
+            /// * `UsuarioNoRegistrado`, `NoEsRolCorrecto`: Debe ser vendedor.
+            /// * `CuponInvalido`: Valor, vencimiento o usos no válidos.
+            /// * `ProductoNoExiste`, `NoEsVendedorDelProducto`: Restricción a un producto ajeno.
+            /// * `CategoriaDemasiadoLarga`: La categoría no pasa la validación de productos.
+            /// * `CuponExistente`: Ya hay un cupón con ese código.
             #[ink(message)]
-            pub fn calificar_comprador(&mut self, orden_id: u32, calificacion: u32) -> Result<(), SistemaError> {
-            let caller = self.env().caller();
-            
-            // Validación de rango manual
-            if !(1..=5).contains(&calificacion) { return Err(SistemaError::CalificacionInvalida); }
+            pub fn registrar_cupon(
+                &mut self,
+                hash_codigo: HashCupon,
+                valor: ValorCupon,
+                expira_en: Timestamp,
+                usos_maximos: u32,
+                producto_id: Option<u32>,
+                categoria: Option<String>,
+            ) -> Result<(), SistemaError> {
+                let vendedor = self.env().caller();
+                self.verificar_rol(vendedor, RolUsuario::Vendedor)?;
+                let valor_valido = match valor {
+                    ValorCupon::Porcentaje(p) => (1..=100).contains(&p),
+                    ValorCupon::Fijo(monto) => monto > 0,
+                };
+                if !valor_valido || usos_maximos == 0 || expira_en <= self.env().block_timestamp() {
+                    return Err(SistemaError::CuponInvalido);
+                }
+                if let Some(id) = producto_id {
+                    self.producto_del_vendedor(vendedor, id)?;
+                }
+                let categoria = match categoria {
+                    Some(c) => {
+                        let c = c.trim();
+                        if c.is_empty() || !Self::texto_valido(c, self.limites_texto.max_categoria) {
+                            return Err(SistemaError::CategoriaDemasiadoLarga);
+                        }
+                        Some(String::from(c))
+                    }
+                    None => None,
+                };
+                if self.cupones.contains((vendedor, hash_codigo)) {
+                    return Err(SistemaError::CuponExistente);
+                }
 
-            let mut orden = self.ordenes.get(orden_id).ok_or(SistemaError::OrdenNoExiste)?;
-            if orden.vendedor != caller { return Err(SistemaError::NoEsRolCorrecto); }
-            if orden.estado != EstadoOrden::Recibida { return Err(SistemaError::EstadoInvalido); }
-            if orden.vendedor_califico { return Err(SistemaError::YaCalificado); }
+                let cupon = Cupon { valor, expira_en, usos_maximos, usos: 0, producto_id, categoria };
+                self.cupones.insert((vendedor, hash_codigo), &cupon);
+                self.env().emit_event(CuponRegistrado { vendedor, hash_codigo });
+                Ok(())
+            }
 
-            orden.vendedor_califico = true;
-            self.ordenes.insert(orden_id, &orden);
+            /// Consulta un cupón por vendedor y hash del código.
+            #[ink(message)]
+            pub fn obtener_cupon(&self, vendedor: AccountId, hash_codigo: HashCupon) -> Option<Cupon> {
+                self.cupones.get((vendedor, hash_codigo))
+            }
 
-            let comprador_addr = orden.comprador;
-            if let Some(mut c) = self.usuarios.get(&comprador_addr) {
-                c.reputacion_como_comprador = c.reputacion_como_comprador.saturating_add(calificacion);
-                self.usuarios.insert(comprador_addr, &c);
+        /// Importe de `cantidad` unidades a `precio_lista`. Si se cobra el precio base
+        /// (`precio_base`), primero se toma el tramo por cantidad que corresponda y después
+        /// el descuento vigente si es más barato: ambos se validan contra el precio base.
+        /// El precio propio de una variante se cobra tal cual. Si el descuento tiene tope
+        /// de unidades, las que excedan el tope se cobran sin descuento.
+        ///
+        /// Devuelve el total y cuántas unidades se cobraron con descuento.
+        fn cotizar(&self, producto_id: u32, precio_lista: Balance, cantidad: u32, precio_base: bool) -> (Balance, u32) {
+            if !precio_base {
+                return (precio_lista.saturating_mul(Balance::from(cantidad)), 0);
+            }
+            let precio_lista = self.precio_por_cantidad(producto_id, precio_lista, cantidad);
+            let ahora = self.env().block_timestamp();
+            match self.descuentos.get(producto_id) {
+                Some(d) if d.vigente(ahora) && d.precio < precio_lista => {
+                    let con_descuento = d.unidades_restantes().map_or(cantidad, |r| r.min(cantidad));
+                    let total = d.precio.saturating_mul(Balance::from(con_descuento))
+                        .saturating_add(precio_lista.saturating_mul(Balance::from(cantidad - con_descuento)));
+                    (total, con_descuento)
+                }
+                _ => (precio_lista.saturating_mul(Balance::from(cantidad)), 0),
             }
+        }
 
-            self.env().emit_event(VendedorCalifico { orden_id, vendedor: caller, comprador: comprador_addr, calificacion });
-            Ok(())
+        /// Menor precio unitario entre `precio_lista` y los tramos que alcanza `cantidad`.
+        fn precio_por_cantidad(&self, producto_id: u32, precio_lista: Balance, cantidad: u32) -> Balance {
+            self.tramos_precio
+                .get(producto_id)
+                .unwrap_or_default()
+                .iter()
+                .filter(|t| cantidad >= t.cantidad_minima)
+                .map(|t| t.precio)
+                .fold(precio_lista, Balance::min)
         }
 
+        /// Suma unidades vendidas al descuento.
+        fn consumir_descuento(&mut self, producto_id: u32, unidades: u32) {
+            if let Some(mut d) = self.descuentos.get(producto_id) {
+                d.unidades_vendidas = d.unidades_vendidas.saturating_add(unidades);
+                self.descuentos.insert(producto_id, &d);
+            }
+        }
 
-        /// Verifica si un usuario está registrado.
-        fn verificar_registro(&self, usuario: AccountId) -> Result<(), SistemaError> {
-            if !self.usuarios.contains(&usuario) { // Cambia contains_key por contains
-                Err(SistemaError::UsuarioNoRegistrado)
-            } else {
-                Ok(())
+        /// Emite una sola vez `DescuentoIniciado` y `DescuentoFinalizado` cuando ya
+        /// corresponden. Un contrato no puede reaccionar solo al paso del tiempo, así que
+        /// se llama desde cada operación que toca el descuento.
+        fn notificar_descuento(&mut self, producto_id: u32) {
+            let Some(mut d) = self.descuentos.get(producto_id) else { return };
+            let ahora = self.env().block_timestamp();
+            let pendientes = (d.inicio_notificado, d.fin_notificado);
+            if !d.inicio_notificado && ahora >= d.inicio {
+                d.inicio_notificado = true;
+                self.env().emit_event(DescuentoIniciado { producto_id, precio: d.precio, fin: d.fin });
+            }
+            if d.inicio_notificado && !d.fin_notificado && d.terminado(ahora) {
+                d.fin_notificado = true;
+                let agotado = d.unidades_restantes() == Some(0);
+                self.env().emit_event(DescuentoFinalizado { producto_id, agotado });
+            }
+            if pendientes != (d.inicio_notificado, d.fin_notificado) {
+                self.descuentos.insert(producto_id, &d);
             }
         }
 
-        /// Verifica si el usuario tiene el rol requerido.
-        fn verificar_rol(&self, usuario: AccountId, rol_requerido: RolUsuario) -> Result<(), SistemaError> {
-            let usuario_data = self.usuarios.get(&usuario)
-                .ok_or(SistemaError::UsuarioNoRegistrado)?;
+            /// Programa un precio promocional para un producto de precio fijo.
+            /// Reemplaza cualquier descuento anterior del producto. Rige sobre el precio
+            /// base: las variantes con precio propio se cobran sin descuento.
+            ///
+            /// # Parámetros
+            /// * `precio` - Precio unitario promocional; debe ser menor al de lista.
+            /// * `inicio`, `fin` - Timestamps (ms) de comienzo y fin (exclusivo).
+            /// * `unidades_maximas` - Tope de unidades con descuento, o `None` sin tope.
+            ///
+            /// # Errores
+            /// * `ProductoNoExiste`, `NoEsVendedorDelProducto`, `ProductoEnSubasta`.
+            /// * `PrecioInvalido`: Es 0 o no es menor al precio de lista.
+            /// * `PeriodoInvalido`: `inicio >= fin` o `fin` ya pasó.
+            /// * `CantidadInsuficiente`: `unidades_maximas` es `Some(0)`.
+            #[ink(message)]
+            pub fn programar_descuento(
+                &mut self,
+                producto_id: u32,
+                precio: Balance,
+                inicio: Timestamp,
+                fin: Timestamp,
+                unidades_maximas: Option<u32>,
+            ) -> Result<(), SistemaError> {
+                let prod = self.producto_del_vendedor(self.env().caller(), producto_id)?;
+                if prod.modo_venta != ModoVenta::PrecioFijo { return Err(SistemaError::ProductoEnSubasta); }
+                if precio == 0 || precio >= prod.precio { return Err(SistemaError::PrecioInvalido); }
+                if inicio >= fin || fin <= self.env().block_timestamp() { return Err(SistemaError::PeriodoInvalido); }
+                if unidades_maximas == Some(0) { return Err(SistemaError::CantidadInsuficiente); }
+
+                self.notificar_descuento(producto_id);
+                if self.descuentos.get(producto_id).is_some_and(|d| d.inicio_notificado && !d.fin_notificado) {
+                    self.env().emit_event(DescuentoFinalizado { producto_id, agotado: false });
+                }
+                let descuento = Descuento {
+                    precio,
+                    inicio,
+                    fin,
+                    unidades_maximas,
+                    unidades_vendidas: 0,
+                    inicio_notificado: false,
+                    fin_notificado: false,
+                };
+                self.descuentos.insert(producto_id, &descuento);
+                self.env().emit_event(DescuentoProgramado { producto_id, precio, inicio, fin, unidades_maximas });
+                self.notificar_descuento(producto_id);
+                Ok(())
+            }
 
-            match (usuario_data.rol, rol_requerido) {
-                // Solo usuarios con rol Comprador pueden crear órdenes
-                (RolUsuario::Comprador, RolUsuario::Comprador) => Ok(()),
-                // Solo usuarios con rol Vendedor pueden publicar productos
-                (RolUsuario::Vendedor, RolUsuario::Vendedor) => Ok(()),
-                // Usuarios con rol Ambos pueden hacer ambas acciones
-                (RolUsuario::Ambos, _) => Ok(()),
-                _ => Err(SistemaError::NoEsRolCorrecto),
+            /// Quita el descuento programado (vigente o futuro) de un producto.
+            ///
+            /// # Errores
+            /// * `ProductoNoExiste`, `NoEsVendedorDelProducto`, `DescuentoNoExiste`.
+            #[ink(message)]
+            pub fn cancelar_descuento(&mut self, producto_id: u32) -> Result<(), SistemaError> {
+                self.producto_del_vendedor(self.env().caller(), producto_id)?;
+                self.notificar_descuento(producto_id);
+                let descuento = self.descuentos.take(producto_id).ok_or(SistemaError::DescuentoNoExiste)?;
+                if !descuento.fin_notificado {
+                    self.env().emit_event(DescuentoFinalizado { producto_id, agotado: false });
+                }
+                Ok(())
             }
-        }
 
+            /// Emite los eventos de inicio o fin del descuento que ya correspondan y todavía
+            /// no se emitieron. Cualquiera puede llamarla; las órdenes lo hacen solas.
+            ///
+            /// # Errores
+            /// * `DescuentoNoExiste`: El producto no tiene descuento programado.
+            #[ink(message)]
+            pub fn sincronizar_descuento(&mut self, producto_id: u32) -> Result<(), SistemaError> {
+                if !self.descuentos.contains(producto_id) { return Err(SistemaError::DescuentoNoExiste); }
+                self.notificar_descuento(producto_id);
+                Ok(())
+            }
 
-        fn verificar_cantidad(&self, cantidad: u32) -> Result<(), SistemaError> {
-            if cantidad == 0 {
-                Err(SistemaError::CantidadInsuficiente)
-            } else {
+            /// `true` si el descuento del producto rige ahora, aunque todavía no se hayan
+            /// emitido sus eventos.
+            #[ink(message)]
+            pub fn descuento_vigente(&self, producto_id: u32) -> bool {
+                self.descuentos.get(producto_id).is_some_and(|d| d.vigente(self.env().block_timestamp()))
+            }
+
+            /// Define los tramos de precio por cantidad de un producto (p. ej. 10+ a 90 y
+            /// 100+ a 80). Una lista vacía los elimina.
+            ///
+            /// Los tramos se validan contra el precio base y solo se aplican a ese precio: las
+            /// variantes con precio propio no los usan.
+            ///
+            /// # Errores
+            /// * `ProductoNoExiste`, `NoEsVendedorDelProducto`, `ProductoEnSubasta`.
+            /// * `TramosInvalidos`: Más de `MAX_TRAMOS_PRECIO`, cantidades no crecientes o
+            ///   menores a 2, o precios en 0, no decrecientes o no menores al de lista.
+            #[ink(message)]
+            pub fn configurar_tramos_precio(&mut self, producto_id: u32, tramos: Vec<TramoPrecio>) -> Result<(), SistemaError> {
+                let prod = self.producto_del_vendedor(self.env().caller(), producto_id)?;
+                if prod.modo_venta != ModoVenta::PrecioFijo { return Err(SistemaError::ProductoEnSubasta); }
+                if tramos.is_empty() {
+                    self.tramos_precio.remove(producto_id);
+                    return Ok(());
+                }
+                if tramos.len() > MAX_TRAMOS_PRECIO as usize {
+                    return Err(SistemaError::TramosInvalidos);
+                }
+                let (mut cantidad_anterior, mut precio_anterior) = (1u32, prod.precio);
+                for tramo in &tramos {
+                    if tramo.cantidad_minima <= cantidad_anterior || tramo.precio == 0 || tramo.precio >= precio_anterior {
+                        return Err(SistemaError::TramosInvalidos);
+                    }
+                    cantidad_anterior = tramo.cantidad_minima;
+                    precio_anterior = tramo.precio;
+                }
+                self.tramos_precio.insert(producto_id, &tramos);
                 Ok(())
             }
-        }
 
+            /// Tramos de precio por cantidad de un producto.
+            #[ink(message)]
+            pub fn obtener_tramos_precio(&self, producto_id: u32) -> Vec<TramoPrecio> {
+                self.tramos_precio.get(producto_id).unwrap_or_default()
+            }
 
-        fn agregar_producto(
+            /// Descuento programado de un producto, aunque todavía no rija o ya haya terminado.
+            #[ink(message)]
+            pub fn obtener_descuento(&self, producto_id: u32) -> Option<Descuento> {
+                self.descuentos.get(producto_id)
+            }
+
+            /// Producto con su precio de lista y el precio unitario que se cobraría ahora.
+            /// Para productos con variantes, ambos precios corresponden al precio base.
+            #[ink(message)]
+            pub fn obtener_producto(&self, producto_id: u32) -> Option<ProductoConPrecio> {
+                let producto = self.productos.get(producto_id)?;
+                let precio_lista = producto.precio;
+                let (precio_efectivo, _) = self.cotizar(producto_id, precio_lista, 1, true);
+                Some(ProductoConPrecio { producto, precio_lista, precio_efectivo })
+            }
+
+        /// Reserva el ID de la orden, guarda el producto y la orden. Quien llama ya
+        /// validó y descontó de `prod` el stock que toma la orden.
+        fn registrar_orden(
             &mut self,
-            nombre: String,
-            descripcion: String,
-            precio: Balance,
+            comprador: AccountId,
+            prod: Producto,
+            variante_id: Option<u32>,
             cantidad: u32,
-            categoria: String,
-            vendedor: AccountId,
-        ) -> Result<(), SistemaError> {
-            let id = self.next_producto_id;
-            self.next_producto_id = self.next_producto_id.checked_add(1).ok_or(SistemaError::ProductosVacios)?;
-
-            let nuevo = Producto::nuevo(id, nombre, descripcion, precio, cantidad, categoria, vendedor);
-            self.productos.insert(id, &nuevo);
+            total: Balance,
+            deposito: Balance,
+        ) -> Result<u32, SistemaError> {
+            // Reservar ID de orden antes de tocar el stock
+            let id = self.next_orden_id;
+            self.next_orden_id = self.next_orden_id.checked_add(1).ok_or(SistemaError::DesbordamientoId)?;
 
-            // Evento de publicación
-            self.env().emit_event(ProductoPublicado { vendedor, producto_id: id });
+            self.productos.insert(prod.id, &prod);
 
-            Ok(())
+            // Crear orden
+            let mut nueva = Orden::nueva(id, comprador, prod.vendedor, prod.id, variante_id, cantidad, total);
+            nueva.deposito = deposito;
+            self.ordenes.insert(id, &nueva);
+            self.indexar_orden(&nueva, &[prod.id]);
+            Ok(id)
         }
 
-
-        fn verificar_permiso_orden(
-            &self,
-            caller: AccountId,
-            orden: &Orden,
-            nuevo_estado: &EstadoOrden
-        ) -> Result<(), SistemaError> {
-            match nuevo_estado {
-                EstadoOrden::Enviada if caller != orden.vendedor => Err(SistemaError::NoEsRolCorrecto),
-                EstadoOrden::Recibida if caller != orden.comprador => Err(SistemaError::NoEsRolCorrecto),
-                _ => self.verificar_transicion_estado(&orden.estado, nuevo_estado),
+        /// Agrega la orden a los índices de sus productos y de sus dos partes.
+        fn indexar_orden(&mut self, orden: &Orden, producto_ids: &[u32]) {
+            for producto_id in producto_ids {
+                let mut ids = self.ordenes_producto.get(producto_id).unwrap_or_default();
+                ids.retain(|id| self.ordenes.get(id).is_some_and(|o| o.abierta()));
+                ids.push(orden.id);
+                self.ordenes_producto.insert(producto_id, &ids);
+            }
+            for cuenta in [orden.comprador, orden.vendedor] {
+                let mut ids = self.ordenes_usuario.get(cuenta).unwrap_or_default();
+                ids.retain(|id| self.ordenes.get(id).is_some_and(|o| o.abierta()));
+                ids.push(orden.id);
+                self.ordenes_usuario.insert(cuenta, &ids);
             }
         }
 
-        /// Verifica que la transición de estado de la orden sea válida.
-        fn verificar_transicion_estado(
-            &self,
-            actual: &EstadoOrden,
-            nuevo: &EstadoOrden
-        ) -> Result<(), SistemaError> {
-            match (actual, nuevo) {
-                (EstadoOrden::Pendiente, EstadoOrden::Enviada) => Ok(()),
-                (EstadoOrden::Enviada, EstadoOrden::Recibida) => Ok(()),
-                _ => Err(SistemaError::EstadoInvalido),
-            }
+        fn tiene_ordenes_abiertas(&self, producto_id: u32) -> bool {
+            self.ordenes_producto
+                .get(producto_id)
+                .unwrap_or_default()
+                .into_iter()
+                .any(|id| self.ordenes.get(id).is_some_and(|o| o.abierta()))
         }
 
-            /// Inicia o completa el proceso de cancelación.
+            /// Retira un producto del catálogo: deja de poder comprarse, ofertarse o venderse
+            /// en paquetes. Las órdenes ya creadas no se ven afectadas.
             ///
-            /// Si el Comprador llama, marca su solicitud.
-            /// Si el Vendedor llama, marca su aceptación.
-            /// Cuando **ambos** han aceptado, la orden pasa a `Cancelada` y se devuelve el stock.
+            /// # Errores
+            /// * `ProductoNoExiste`, `NoEsVendedorDelProducto`.
+            /// * `ProductoEnSubasta`: Las subastas no se pueden despublicar.
+            #[ink(message)]
+            pub fn despublicar_producto(&mut self, producto_id: u32) -> Result<(), SistemaError> {
+                self.cambiar_publicacion(producto_id, false)
+            }
+
+            /// Vuelve a publicar un producto despublicado.
             ///
             /// # Errores
-            /// * `EstadoInvalido`: Solo se pueden cancelar órdenes `Pendiente`.
+            /// * `ProductoNoExiste`, `NoEsVendedorDelProducto`, `ProductoEnSubasta`.
             #[ink(message)]
-            pub fn solicitar_cancelacion_orden(&mut self, orden_id: u32) -> Result<(), SistemaError> {
-            let caller = self.env().caller();
-            if !self.usuarios.contains(&caller) { return Err(SistemaError::UsuarioNoRegistrado); }
+            pub fn republicar_producto(&mut self, producto_id: u32) -> Result<(), SistemaError> {
+                self.cambiar_publicacion(producto_id, true)
+            }
 
-            let (prod_id, cant, cancelar) = {
-                let mut orden = self.ordenes.get(orden_id).ok_or(SistemaError::OrdenNoExiste)?;
-                if orden.estado != EstadoOrden::Pendiente { return Err(SistemaError::EstadoInvalido); }
+        /// Verifica que `total` no supere el límite de órdenes a vendedores sin verificación vigente.
+        fn verificar_limite_no_verificado(&self, vendedor: AccountId, total: Balance) -> Result<(), SistemaError> {
+            if self.limite_orden_no_verificado.is_some_and(|limite| total > limite)
+                && !self.usuario(vendedor).is_some_and(|v| v.verificado(self.env().block_timestamp()))
+            {
+                return Err(SistemaError::VendedorNoVerificado);
+            }
+            Ok(())
+        }
 
-                if caller == orden.comprador { orden.comprador_solicita_cancelacion = true; }
-                else if caller == orden.vendedor { orden.vendedor_acepta_cancelacion = true; }
-                else { return Err(SistemaError::NoEsRolCorrecto); }
-                
-                self.ordenes.insert(orden_id, &orden);
+        /// Verifica que el producto esté publicado y no vencido.
+        fn verificar_disponible(&self, prod: &Producto) -> Result<(), SistemaError> {
+            if !prod.activo { return Err(SistemaError::ProductoNoDisponible); }
+            if self.env().block_timestamp() >= prod.expira_en { return Err(SistemaError::PublicacionVencida); }
+            Ok(())
+        }
 
-                (orden.producto_id, orden.cantidad, orden.comprador_solicita_cancelacion && orden.vendedor_acepta_cancelacion)
-            };
+            /// Extiende la publicación por la vigencia configurada, contada desde ahora.
+            /// Sirve también para publicaciones ya vencidas que todavía no se eliminaron.
+            ///
+            /// # Errores
+            /// * `ProductoNoExiste`, `NoEsVendedorDelProducto`.
+            #[ink(message)]
+            pub fn renovar_publicacion(&mut self, producto_id: u32) -> Result<Timestamp, SistemaError> {
+                let mut prod = self.producto_del_vendedor(self.env().caller(), producto_id)?;
+                prod.expira_en = self.env().block_timestamp().saturating_add(self.duracion_publicacion.vigencia);
+                self.productos.insert(producto_id, &prod);
 
-            if cancelar {
-                let mut orden = self.ordenes.get(orden_id).ok_or(SistemaError::OrdenNoExiste)?;
-                orden.estado = EstadoOrden::Cancelada;
-                self.ordenes.insert(orden_id, &orden);
-                
-                if let Some(mut p) = self.productos.get(prod_id) {
-                    p.cantidad = p.cantidad.saturating_add(cant);
-                    self.productos.insert(prod_id, &p);
+                self.env().emit_event(PublicacionRenovada { producto_id, expira_en: prod.expira_en });
+                Ok(prod.expira_en)
+            }
+
+            /// Elimina del almacenamiento las publicaciones vencidas hace más del período de
+            /// gracia, junto con sus medios, descuento, tramos y preventa. Cualquiera puede
+            /// llamarla; el depósito de almacenamiento liberado se le devuelve a quien firma.
+            ///
+            /// Se saltean los IDs inexistentes, los no vencidos, los que tienen preventas
+            /// en espera u órdenes abiertas (también de paquetes) y las subastas sin finalizar.
+            ///
+            /// # Retorno
+            /// * `Ok(Vec<u32>)`: Los IDs eliminados.
+            ///
+            /// # Errores
+            /// * `TamanoLoteInvalido`: Más de `MAX_LOTE_LIMPIEZA` IDs.
+            #[ink(message)]
+            pub fn limpiar_publicaciones_vencidas(&mut self, producto_ids: Vec<u32>) -> Result<Vec<u32>, SistemaError> {
+                if producto_ids.len() > MAX_LOTE_LIMPIEZA as usize {
+                    return Err(SistemaError::TamanoLoteInvalido);
+                }
+                let ahora = self.env().block_timestamp();
+                let mut eliminados = Vec::new();
+                for producto_id in producto_ids {
+                    let Some(prod) = self.productos.get(producto_id) else { continue };
+                    let limite = prod.expira_en.saturating_add(self.duracion_publicacion.gracia);
+                    let preventa_en_curso = self.preventas.get(producto_id).is_some_and(|p| !p.cola.is_empty());
+                    let subasta_en_curso = self.subastas.get(producto_id).is_some_and(|s| !s.finalizada);
+                    if ahora < limite || preventa_en_curso || subasta_en_curso || self.tiene_ordenes_abiertas(producto_id) {
+                        continue;
+                    }
+
+                    self.productos.remove(producto_id);
+                    self.medios_producto.remove(producto_id);
+                    self.descuentos.remove(producto_id);
+                    self.tramos_precio.remove(producto_id);
+                    self.preventas.remove(producto_id);
+                    self.subastas.remove(producto_id);
+                    self.ordenes_producto.remove(producto_id);
+                    self.env().emit_event(PublicacionEliminada { producto_id });
+                    eliminados.push(producto_id);
                 }
+                Ok(eliminados)
             }
+
+        fn cambiar_publicacion(&mut self, producto_id: u32, activo: bool) -> Result<(), SistemaError> {
+            let mut prod = self.producto_del_vendedor(self.env().caller(), producto_id)?;
+            if prod.modo_venta != ModoVenta::PrecioFijo { return Err(SistemaError::ProductoEnSubasta); }
+            prod.activo = activo;
+            self.productos.insert(producto_id, &prod);
             Ok(())
         }
 
-
-            /// Retorna todos los productos disponibles.
+            /// Crea un paquete con productos físicos propios, de precio fijo y sin variantes.
+            ///
+            /// # Parámetros
+            /// * `componentes` - Pares (producto_id, unidades por paquete).
+            /// * `precio` - Precio de cada paquete.
+            ///
+            /// # Retorno
+            /// * `Ok(u32)`: El ID del paquete.
+            ///
+            /// # Errores
+            /// * `UsuarioNoRegistrado`, `NoEsRolCorrecto`: Debe ser vendedor.
+            /// * `NombreInvalido`, `PrecioInvalido`.
+            /// * `ComponentesInvalidos`: Ver la documentación del error.
+            /// * `ComponenteDigital`: Algún componente es un producto digital.
+            /// * `DesbordamientoId`: No quedan IDs de paquete disponibles.
             #[ink(message)]
-            pub fn obtener_todos_los_productos(&self) -> Vec<Producto> {
-            let mut productos = Vec::new();
-            for id in 0..self.next_producto_id {
-                if let Some(prod) = self.productos.get(id) {
-                    productos.push(prod);
+            pub fn crear_paquete(&mut self, nombre: String, componentes: Vec<(u32, u32)>, precio: Balance) -> Result<u32, SistemaError> {
+                let vendedor = self.env().caller();
+                self.verificar_rol(vendedor, RolUsuario::Vendedor)?;
+                if precio == 0 { return Err(SistemaError::PrecioInvalido); }
+                let nombre = nombre.trim();
+                if nombre.is_empty() || !Self::texto_valido(nombre, self.limites_texto.max_nombre) {
+                    return Err(SistemaError::NombreInvalido);
+                }
+                if componentes.is_empty() || componentes.len() > MAX_COMPONENTES_PAQUETE as usize {
+                    return Err(SistemaError::ComponentesInvalidos);
                 }
+                for (i, (producto_id, unidades)) in componentes.iter().enumerate() {
+                    let repetido = componentes[..i].iter().any(|(otro, _)| otro == producto_id);
+                    let producto = self.productos.get(producto_id);
+                    let valido = match &producto {
+                        Some(p) => p.vendedor == vendedor && p.variantes.is_empty() && p.modo_venta == ModoVenta::PrecioFijo,
+                        None => false,
+                    };
+                    if *unidades == 0 || repetido || !valido {
+                        return Err(SistemaError::ComponentesInvalidos);
+                    }
+                    if producto.is_some_and(|p| p.tipo == TipoProducto::Digital) {
+                        return Err(SistemaError::ComponenteDigital);
+                    }
+                }
+
+                let id = self.next_paquete_id;
+                self.next_paquete_id = self.next_paquete_id.checked_add(1).ok_or(SistemaError::DesbordamientoId)?;
+                self.paquetes.insert(id, &Paquete { id, vendedor, nombre: String::from(nombre), componentes, precio });
+
+                self.env().emit_event(PaqueteCreado { vendedor, paquete_id: id });
+                Ok(id)
             }
-            productos
-        }
 
-        /// Retorna todas las órdenes.
-        #[ink(message)]
-        pub fn obtener_todas_las_ordenes(&self) -> Vec<Orden> {
-            let mut ordenes = Vec::new();
-            for id in 0..self.next_orden_id {
-                if let Some(orden) = self.ordenes.get(id) {
-                    ordenes.push(orden);
-                }
+            /// Consulta un paquete.
+            #[ink(message)]
+            pub fn obtener_paquete(&self, paquete_id: u32) -> Option<Paquete> {
+                self.paquetes.get(paquete_id)
             }
-            ordenes
-        }
 
-        /// Retorna todos los usuarios.
-        #[ink(message)]
-        pub fn obtener_todos_los_usuarios(&self) -> Vec<Usuario> {
-            let mut lista_completa: Vec<Usuario> = Vec::new();
-            
-            for id in &self.lista_usuarios_ids {
-                if let Some(usuario) = self.usuarios.get(id) {
-                    lista_completa.push(usuario);
+            /// Compra `cantidad` paquetes: descuenta el stock de todos los componentes
+            /// y crea una única orden por `precio * cantidad`.
+            ///
+            /// # Errores
+            /// * `UsuarioNoRegistrado`, `NoEsRolCorrecto`, `CantidadInsuficiente`.
+            /// * `PaqueteNoExiste`.
+            /// * `CuentaDadaDeBaja`: El vendedor del paquete se dio de baja.
+            /// * `PaqueteNoDisponible`: Algún componente no existe, está despublicado o sin stock.
+            /// * `VendedorNoVerificado`: El total supera el límite para vendedores sin verificar.
+            /// * `DesbordamientoId`: No quedan IDs de orden disponibles.
+            #[ink(message)]
+            pub fn comprar_paquete(&mut self, paquete_id: u32, cantidad: u32) -> Result<u32, SistemaError> {
+                let comprador = self.env().caller();
+                self.verificar_rol(comprador, RolUsuario::Comprador)?;
+                self.verificar_cantidad(cantidad)?;
+                let paquete = self.paquetes.get(paquete_id).ok_or(SistemaError::PaqueteNoExiste)?;
+                self.verificar_cuenta_abierta(paquete.vendedor)?;
+
+                // Validar todos los componentes antes de tocar el stock
+                let mut productos = Vec::new();
+                for (producto_id, unidades) in &paquete.componentes {
+                    let prod = self.productos.get(producto_id).ok_or(SistemaError::PaqueteNoDisponible)?;
+                    let necesarias = unidades.checked_mul(cantidad).ok_or(SistemaError::PaqueteNoDisponible)?;
+                    if self.verificar_disponible(&prod).is_err() || prod.cantidad < necesarias {
+                        return Err(SistemaError::PaqueteNoDisponible);
+                    }
+                    productos.push((prod, necesarias));
                 }
-            }
-            
-            lista_completa
-        }
-    }
 
+                let total = paquete.precio.saturating_mul(Balance::from(cantidad));
+                self.verificar_limite_no_verificado(paquete.vendedor, total)?;
 
-    #[cfg(test)]
-    mod tests {
-        use super::*;
-        use ink::env::test;
+                let id = self.next_orden_id;
+                self.next_orden_id = self.next_orden_id.checked_add(1).ok_or(SistemaError::DesbordamientoId)?;
+                for (mut prod, necesarias) in productos {
+                    prod.descontar_stock(None, necesarias);
+                    self.productos.insert(prod.id, &prod);
+                }
 
-        fn setup_contract_con_vendedor() -> Marketplace {
-            let mut contrato = Marketplace::nuevo();
-            let caller = AccountId::from([0x10; 32]);
-            test::set_caller::<ink::env::DefaultEnvironment>(caller);
-            let usuario = Usuario {
+                let mut orden = Orden::nueva(id, comprador, paquete.vendedor, PRODUCTO_PAQUETE, None, cantidad, total);
+                orden.paquete_id = Some(paquete_id);
+                self.ordenes.insert(id, &orden);
+                let componentes: Vec<u32> = paquete.componentes.iter().map(|(producto_id, _)| *producto_id).collect();
+                self.indexar_orden(&orden, &componentes);
+                Ok(id)
+            }
+
+            /// Consulta una oferta.
+            #[ink(message)]
+            pub fn obtener_oferta(&self, oferta_id: u32) -> Option<Oferta> {
+                self.ofertas.get(oferta_id)
+            }
+
+            /// Ofrece comprar un producto de precio fijo a otro precio unitario.
+            ///
+            /// # Retorno
+            /// * `Ok(u32)`: El ID de la oferta.
+            ///
+            /// # Errores
+            /// * Los mismos que `crear_orden` (rol, producto, variante, stock).
+            /// * `ProductoEnSubasta`: El producto se vende por subasta.
+            /// * `PrecioInvalido`: El precio ofrecido es 0.
+            #[ink(message)]
+            pub fn ofertar(
+                &mut self,
+                producto_id: u32,
+                variante_id: Option<u32>,
+                precio_unitario: Balance,
+                cantidad: u32,
+            ) -> Result<u32, SistemaError> {
+                let comprador = self.env().caller();
+                self.verificar_rol(comprador, RolUsuario::Comprador)?;
+                self.verificar_cantidad(cantidad)?;
+                if precio_unitario == 0 { return Err(SistemaError::PrecioInvalido); }
+
+                let prod = self.productos.get(producto_id).ok_or(SistemaError::ProductoNoExiste)?;
+                if prod.modo_venta != ModoVenta::PrecioFijo { return Err(SistemaError::ProductoEnSubasta); }
+                self.verificar_cuenta_abierta(prod.vendedor)?;
+                self.verificar_disponible(&prod)?;
+                if prod.vendedor == comprador { return Err(SistemaError::NoEsRolCorrecto); }
+                let (_, stock) = prod.precio_y_stock(variante_id)?;
+                if stock < cantidad { return Err(SistemaError::StockInsuficiente); }
+
+                let id = self.next_oferta_id;
+                self.next_oferta_id = self.next_oferta_id.checked_add(1).ok_or(SistemaError::DesbordamientoId)?;
+                let oferta = Oferta {
+                    id,
+                    producto_id,
+                    variante_id,
+                    comprador,
+                    vendedor: prod.vendedor,
+                    precio_unitario,
+                    cantidad,
+                    estado: EstadoOferta::Pendiente,
+                    expira_en: self.env().block_number().saturating_add(self.bloques_expiracion_oferta),
+                    orden_id: None,
+                };
+                self.ofertas.insert(id, &oferta);
+                for cuenta in [comprador, prod.vendedor] {
+                    let mut ids = self.ofertas_usuario.get(cuenta).unwrap_or_default();
+                    ids.retain(|id| self.ofertas.get(id).is_some_and(|o| o.abierta()));
+                    ids.push(id);
+                    self.ofertas_usuario.insert(cuenta, &ids);
+                }
+
+                self.env().emit_event(OfertaRealizada { oferta_id: id, producto_id, comprador, precio_unitario, cantidad });
+                Ok(id)
+            }
+
+            /// Responde con otro precio unitario. Le toca al vendedor si la oferta está
+            /// `Pendiente` y al comprador si está `Contraofertada`; renueva la vigencia.
+            ///
+            /// # Errores
+            /// * `OfertaNoExiste`, `NoEsParteDeLaOferta`, `OfertaExpirada`.
+            /// * `NoEsRolCorrecto`: No es el turno de quien llama.
+            /// * `EstadoInvalido`: La oferta ya fue aceptada o rechazada.
+            /// * `CuentaDadaDeBaja`: Alguna de las partes se dio de baja.
+            /// * `PrecioInvalido`: El precio es 0.
+            #[ink(message)]
+            pub fn contraofertar(&mut self, oferta_id: u32, precio_unitario: Balance) -> Result<(), SistemaError> {
+                let caller = self.env().caller();
+                let mut oferta = self.oferta_en_turno(caller, oferta_id)?;
+                if precio_unitario == 0 { return Err(SistemaError::PrecioInvalido); }
+
+                oferta.estado = if caller == oferta.vendedor { EstadoOferta::Contraofertada } else { EstadoOferta::Pendiente };
+                oferta.precio_unitario = precio_unitario;
+                oferta.expira_en = self.env().block_number().saturating_add(self.bloques_expiracion_oferta);
+                self.ofertas.insert(oferta_id, &oferta);
+
+                self.env().emit_event(OfertaContraofertada { oferta_id, por: caller, precio_unitario, expira_en: oferta.expira_en });
+                Ok(())
+            }
+
+            /// Acepta el último precio propuesto por la otra parte y crea la orden para el
+            /// comprador de la oferta por el mismo camino que `crear_orden`, con el precio acordado.
+            ///
+            /// # Retorno
+            /// * `Ok(u32)`: El ID de la orden generada.
+            ///
+            /// # Errores
+            /// * `OfertaNoExiste`, `NoEsParteDeLaOferta`, `OfertaExpirada`.
+            /// * `NoEsRolCorrecto`: No es el turno de quien llama.
+            /// * `EstadoInvalido`: La oferta ya fue aceptada o rechazada.
+            /// * `CuentaDadaDeBaja`: Alguna de las partes se dio de baja.
+            /// * Los mismos que `crear_orden`, evaluados para el comprador de la oferta.
+            #[ink(message)]
+            pub fn aceptar_oferta(&mut self, oferta_id: u32) -> Result<u32, SistemaError> {
+                let caller = self.env().caller();
+                let mut oferta = self.oferta_en_turno(caller, oferta_id)?;
+
+                let orden_id = self.crear_orden_interna(
+                    oferta.comprador,
+                    oferta.producto_id,
+                    oferta.variante_id,
+                    oferta.cantidad,
+                    None,
+                    Some(oferta.precio_unitario),
+                )?;
+
+                oferta.estado = EstadoOferta::Aceptada;
+                oferta.orden_id = Some(orden_id);
+                self.ofertas.insert(oferta_id, &oferta);
+
+                self.env().emit_event(OfertaAceptada { oferta_id, orden_id });
+                Ok(orden_id)
+            }
+
+            /// Rechaza la oferta. El vendedor puede rechazar una `Pendiente`, el comprador
+            /// una `Contraofertada`; además el comprador puede retirar la suya en cualquier momento.
+            ///
+            /// # Errores
+            /// * `OfertaNoExiste`, `NoEsParteDeLaOferta`.
+            /// * `NoEsRolCorrecto`: El vendedor intenta rechazar su propia contraoferta.
+            /// * `EstadoInvalido`: La oferta ya fue aceptada o rechazada.
+            #[ink(message)]
+            pub fn rechazar_oferta(&mut self, oferta_id: u32) -> Result<(), SistemaError> {
+                let caller = self.env().caller();
+                let mut oferta = self.ofertas.get(oferta_id).ok_or(SistemaError::OfertaNoExiste)?;
+                Self::verificar_parte_de_oferta(caller, &oferta)?;
+                match oferta.estado {
+                    EstadoOferta::Pendiente => {}
+                    EstadoOferta::Contraofertada if caller == oferta.comprador => {}
+                    EstadoOferta::Contraofertada => return Err(SistemaError::NoEsRolCorrecto),
+                    _ => return Err(SistemaError::EstadoInvalido),
+                }
+
+                oferta.estado = EstadoOferta::Rechazada;
+                self.ofertas.insert(oferta_id, &oferta);
+
+                self.env().emit_event(OfertaRechazada { oferta_id, por: caller });
+                Ok(())
+            }
+
+        /// Busca una oferta abierta y vigente en la que le toca responder a `caller`.
+        fn oferta_en_turno(&self, caller: AccountId, oferta_id: u32) -> Result<Oferta, SistemaError> {
+            let oferta = self.ofertas.get(oferta_id).ok_or(SistemaError::OfertaNoExiste)?;
+            Self::verificar_parte_de_oferta(caller, &oferta)?;
+            let en_turno = match oferta.estado {
+                EstadoOferta::Pendiente => oferta.vendedor,
+                EstadoOferta::Contraofertada => oferta.comprador,
+                _ => return Err(SistemaError::EstadoInvalido),
+            };
+            if caller != en_turno { return Err(SistemaError::NoEsRolCorrecto); }
+            if self.env().block_number() > oferta.expira_en { return Err(SistemaError::OfertaExpirada); }
+            self.verificar_cuenta_abierta(oferta.comprador)?;
+            self.verificar_cuenta_abierta(oferta.vendedor)?;
+            Ok(oferta)
+        }
+
+        /// Verifica que quien llama sea el comprador o el vendedor de la oferta.
+        fn verificar_parte_de_oferta(caller: AccountId, oferta: &Oferta) -> Result<(), SistemaError> {
+            if caller != oferta.comprador && caller != oferta.vendedor {
+                return Err(SistemaError::NoEsParteDeLaOferta);
+            }
+            Ok(())
+        }
+
+            /// Publica un producto digital (licencias, descargas). Se vende igual que uno
+            /// físico, pero en lugar de enviarse se entrega con `entregar_contenido_digital`.
+            ///
+            /// # Retorno
+            /// * `Ok(u32)`: El ID del producto.
+            ///
+            /// # Errores
+            /// * Los mismos que `publicar_producto`.
+            #[ink(message)]
+            pub fn publicar_producto_digital(
+                &mut self,
+                nombre: String,
+                descripcion: String,
+                precio: Balance,
+                cantidad: u32,
+                categoria: String,
+            ) -> Result<u32, SistemaError> {
+                let vendedor = self.env().caller();
+                self.verificar_registro(vendedor)?;
+                self.verificar_rol(vendedor, RolUsuario::Vendedor)?;
+                self.verificar_cantidad(cantidad)?;
+                let (nombre, descripcion, categoria) = self.validar_datos_producto(nombre, descripcion, precio, categoria)?;
+
+                let id = self.agregar_producto(nombre, descripcion, precio, cantidad, categoria, vendedor)?;
+                if let Some(mut prod) = self.productos.get(id) {
+                    prod.tipo = TipoProducto::Digital;
+                    self.productos.insert(id, &prod);
+                }
+                Ok(id)
+            }
+
+            /// Registra o reemplaza la clave pública X25519 de quien llama, usada por los
+            /// vendedores para cifrar entregas digitales.
+            ///
+            /// # Errores
+            /// * `UsuarioNoRegistrado`.
+            #[ink(message)]
+            pub fn registrar_clave_publica(&mut self, clave: ClavePublica) -> Result<(), SistemaError> {
+                let caller = self.env().caller();
+                self.verificar_registro(caller)?;
+                self.claves_publicas.insert(caller, &clave);
+                Ok(())
+            }
+
+            /// Clave pública registrada de una cuenta.
+            #[ink(message)]
+            pub fn obtener_clave_publica(&self, cuenta: AccountId) -> Option<ClavePublica> {
+                self.claves_publicas.get(cuenta)
+            }
+
+            /// Publica el contenido de una orden digital cifrado para la clave del comprador.
+            /// Equivale a enviar la orden: pasa de `Pendiente` a `Enviada`.
+            ///
+            /// # Errores
+            /// * `OrdenNoExiste`, `NoEsParteDeLaOrden`.
+            /// * `NoEsRolCorrecto`: Quien llama es el comprador.
+            /// * `EstadoInvalido`: La orden no está `Pendiente`.
+            /// * `NoEsProductoDigital`, `ContenidoInvalido`.
+            /// * `ClavePublicaRequerida`: El comprador no tiene clave registrada.
+            #[ink(message)]
+            pub fn entregar_contenido_digital(&mut self, orden_id: u32, contenido_cifrado: Vec<u8>) -> Result<(), SistemaError> {
+                let caller = self.env().caller();
+                let mut orden = self.ordenes.get(orden_id).ok_or(SistemaError::OrdenNoExiste)?;
+                Self::verificar_parte_de_orden(caller, &orden)?;
+                if orden.vendedor != caller { return Err(SistemaError::NoEsRolCorrecto); }
+                if orden.estado != EstadoOrden::Pendiente { return Err(SistemaError::EstadoInvalido); }
+                if !self.es_orden_digital(&orden) { return Err(SistemaError::NoEsProductoDigital); }
+                if contenido_cifrado.is_empty() || contenido_cifrado.len() > MAX_LARGO_CONTENIDO_DIGITAL as usize {
+                    return Err(SistemaError::ContenidoInvalido);
+                }
+                let clave_destino = self.claves_publicas.get(orden.comprador).ok_or(SistemaError::ClavePublicaRequerida)?;
+
+                self.entregas_digitales.insert(orden_id, &EntregaDigital { clave_destino, contenido_cifrado });
+                orden.estado = EstadoOrden::Enviada;
+                self.ordenes.insert(orden_id, &orden);
+
+                self.env().emit_event(ContenidoDigitalEntregado { orden_id, comprador: orden.comprador });
+                Ok(())
+            }
+
+            /// Entrega cifrada de una orden digital.
+            #[ink(message)]
+            pub fn obtener_entrega_digital(&self, orden_id: u32) -> Option<EntregaDigital> {
+                self.entregas_digitales.get(orden_id)
+            }
+
+            /// Permite al comprador objetar una entrega digital que no pudo descifrar o no es válida.
+            ///
+            /// # Errores
+            /// * `OrdenNoExiste`, `NoEsParteDeLaOrden`.
+            /// * `NoEsRolCorrecto`: Quien llama es el vendedor.
+            /// * `EstadoInvalido`: La orden no está `Enviada`.
+            /// * `NoEsProductoDigital`.
+            #[ink(message)]
+            pub fn abrir_disputa(&mut self, orden_id: u32) -> Result<(), SistemaError> {
+                let caller = self.env().caller();
+                let mut orden = self.ordenes.get(orden_id).ok_or(SistemaError::OrdenNoExiste)?;
+                Self::verificar_parte_de_orden(caller, &orden)?;
+                if orden.comprador != caller { return Err(SistemaError::NoEsRolCorrecto); }
+                if orden.estado != EstadoOrden::Enviada { return Err(SistemaError::EstadoInvalido); }
+                if !self.es_orden_digital(&orden) { return Err(SistemaError::NoEsProductoDigital); }
+
+                orden.estado = EstadoOrden::EnDisputa;
+                self.ordenes.insert(orden_id, &orden);
+
+                self.env().emit_event(DisputaAbierta { orden_id });
+                Ok(())
+            }
+
+            /// Resuelve una disputa. A favor del comprador la orden se cancela (y se le
+            /// libera el depósito, si lo hay); a favor del vendedor se da por `Recibida`.
+            /// El stock no se devuelve: el contenido ya fue revelado.
+            ///
+            /// # Errores
+            /// * `NoEsAdministrador`, `OrdenNoExiste`.
+            /// * `EstadoInvalido`: La orden no está `EnDisputa`.
+            #[ink(message)]
+            pub fn resolver_disputa(&mut self, orden_id: u32, a_favor_del_comprador: bool) -> Result<(), SistemaError> {
+                self.verificar_administrador(self.env().caller())?;
+                let mut orden = self.ordenes.get(orden_id).ok_or(SistemaError::OrdenNoExiste)?;
+                if orden.estado != EstadoOrden::EnDisputa { return Err(SistemaError::EstadoInvalido); }
+
+                if a_favor_del_comprador {
+                    orden.estado = EstadoOrden::Cancelada;
+                    self.acreditar_saldo(orden.comprador, orden.deposito);
+                } else {
+                    orden.estado = EstadoOrden::Recibida;
+                    orden.recibida_en = Some(self.env().block_number());
+                    self.acreditar_saldo(orden.vendedor, orden.deposito);
+                }
+                self.ordenes.insert(orden_id, &orden);
+
+                self.env().emit_event(DisputaResuelta { orden_id, a_favor_del_comprador });
+                Ok(())
+            }
+
+        /// Si la orden es de un producto digital (si el producto ya no existe, se toma como físico).
+        fn es_orden_digital(&self, orden: &Orden) -> bool {
+            orden.paquete_id.is_none()
+                && self.productos.get(orden.producto_id).is_some_and(|p| p.tipo == TipoProducto::Digital)
+        }
+
+            /// Publica un producto en subasta inglesa. Se subasta el lote completo (`cantidad` unidades).
+            ///
+            /// # Parámetros
+            /// * `precio_reserva` - Puja mínima aceptada; se guarda también como `precio` del producto.
+            /// * `duracion_bloques` - Bloques desde ahora hasta el cierre de la subasta.
+            ///
+            /// # Retorno
+            /// * `Ok(u32)`: El ID del producto.
+            ///
+            /// # Errores
+            /// * Los mismos que `publicar_producto`.
+            /// * `DuracionInvalida`: `duracion_bloques` es 0.
+            #[ink(message)]
+            pub fn publicar_subasta(
+                &mut self,
+                nombre: String,
+                descripcion: String,
+                precio_reserva: Balance,
+                cantidad: u32,
+                categoria: String,
+                duracion_bloques: BlockNumber,
+            ) -> Result<u32, SistemaError> {
+                let vendedor = self.env().caller();
+                self.verificar_registro(vendedor)?;
+                self.verificar_rol(vendedor, RolUsuario::Vendedor)?;
+                self.verificar_cantidad(cantidad)?;
+                if duracion_bloques == 0 { return Err(SistemaError::DuracionInvalida); }
+                let (nombre, descripcion, categoria) = self.validar_datos_producto(nombre, descripcion, precio_reserva, categoria)?;
+
+                let id = self.agregar_producto(nombre, descripcion, precio_reserva, cantidad, categoria, vendedor)?;
+                if let Some(mut prod) = self.productos.get(id) {
+                    prod.modo_venta = ModoVenta::Subasta;
+                    self.productos.insert(id, &prod);
+                }
+                let fin = self.env().block_number().saturating_add(duracion_bloques);
+                self.subastas.insert(id, &Subasta { precio_reserva, fin, mejor_postor: None, mejor_puja: 0, finalizada: false });
+
+                self.env().emit_event(SubastaIniciada { producto_id: id, precio_reserva, fin });
+                Ok(id)
+            }
+
+            /// Consulta la subasta de un producto.
+            #[ink(message)]
+            pub fn obtener_subasta(&self, producto_id: u32) -> Option<Subasta> {
+                self.subastas.get(producto_id)
+            }
+
+            /// Puja en una subasta con el valor transferido en la llamada.
+            ///
+            /// La puja queda retenida en el contrato. La puja superada pasa a los
+            /// fondos pendientes de su postor, que la recupera con `retirar_fondos`.
+            ///
+            /// # Errores
+            /// * `UsuarioNoRegistrado`, `NoEsRolCorrecto`: Debe ser comprador y no el vendedor.
+            /// * `NoEsSubasta`, `SubastaCerrada`.
+            /// * `CuentaDadaDeBaja`: Quien puja o el vendedor se dio de baja.
+            /// * `ProductoNoDisponible`, `PublicacionVencida`: La publicación no está activa.
+            /// * `PujaInsuficiente`: No alcanza la reserva o no supera la mejor puja.
+            /// * `VendedorNoVerificado`: La puja supera el límite para vendedores sin verificar.
+            ///   Se controla al pujar porque la puja ganadora ya está retenida cuando
+            ///   `finalizar_subasta` crea la orden.
+            #[ink(message, payable)]
+            pub fn pujar(&mut self, producto_id: u32) -> Result<(), SistemaError> {
+                let postor = self.env().caller();
+                let monto = self.env().transferred_value();
+                self.verificar_registro(postor)?;
+                self.verificar_rol(postor, RolUsuario::Comprador)?;
+
+                let mut subasta = self.subastas.get(producto_id).ok_or(SistemaError::NoEsSubasta)?;
+                let prod = self.productos.get(producto_id).ok_or(SistemaError::ProductoNoExiste)?;
+                if prod.vendedor == postor { return Err(SistemaError::NoEsRolCorrecto); }
+                self.verificar_cuenta_abierta(prod.vendedor)?;
+                if subasta.finalizada || self.env().block_number() >= subasta.fin {
+                    return Err(SistemaError::SubastaCerrada);
+                }
+                self.verificar_disponible(&prod)?;
+                if monto < subasta.precio_reserva || monto <= subasta.mejor_puja {
+                    return Err(SistemaError::PujaInsuficiente);
+                }
+                self.verificar_limite_no_verificado(prod.vendedor, monto)?;
+
+                if let Some(anterior) = subasta.mejor_postor {
+                    self.acreditar_saldo(anterior, subasta.mejor_puja);
+                    self.contar_subasta_liderando(anterior, false);
+                }
+                self.contar_subasta_liderando(postor, true);
+                subasta.mejor_postor = Some(postor);
+                subasta.mejor_puja = monto;
+                self.subastas.insert(producto_id, &subasta);
+
+                self.env().emit_event(PujaRealizada { producto_id, postor, monto });
+                Ok(())
+            }
+
+            /// Cierra una subasta vencida. Cualquiera puede llamarla.
+            ///
+            /// Si hubo pujas, crea una `Orden` para el ganador por el lote completo con la
+            /// puja como `total` y `deposito`; desde ahí sigue el flujo normal de envío,
+            /// recepción y calificación. Si el ganador o el vendedor se dieron de baja, no
+            /// se crea la orden y la puja pasa a los fondos pendientes del ganador.
+            ///
+            /// # Retorno
+            /// * `Ok(Some(orden_id))` si hubo ganador, `Ok(None)` si no hubo pujas o no se
+            ///   pudo crear la orden.
+            ///
+            /// # Errores
+            /// * `NoEsSubasta`, `SubastaCerrada` (ya finalizada), `SubastaEnCurso`.
+            #[ink(message)]
+            pub fn finalizar_subasta(&mut self, producto_id: u32) -> Result<Option<u32>, SistemaError> {
+                let mut subasta = self.subastas.get(producto_id).ok_or(SistemaError::NoEsSubasta)?;
+                if subasta.finalizada { return Err(SistemaError::SubastaCerrada); }
+                if self.env().block_number() < subasta.fin { return Err(SistemaError::SubastaEnCurso); }
+
+                let orden_id = match subasta.mejor_postor {
+                    Some(ganador) => {
+                        let mut prod = self.productos.get(producto_id).ok_or(SistemaError::ProductoNoExiste)?;
+                        let monto = subasta.mejor_puja;
+                        self.contar_subasta_liderando(ganador, false);
+                        if self.cuenta_dada_de_baja(ganador) || self.cuenta_dada_de_baja(prod.vendedor) {
+                            // Sin orden posible: la puja vuelve al ganador
+                            self.acreditar_saldo(ganador, monto);
+                            None
+                        } else {
+                            let cantidad = prod.cantidad;
+                            prod.descontar_stock(None, cantidad);
+                            Some(self.registrar_orden(ganador, prod, None, cantidad, monto, monto)?)
+                        }
+                    }
+                    None => None,
+                };
+                subasta.finalizada = true;
+                self.subastas.insert(producto_id, &subasta);
+
+                self.env().emit_event(SubastaFinalizada {
+                    producto_id,
+                    ganador: subasta.mejor_postor,
+                    monto: subasta.mejor_puja,
+                    orden_id,
+                });
+                Ok(orden_id)
+            }
+
+            /// Fondos que `cuenta` puede retirar.
+            #[ink(message)]
+            pub fn obtener_saldo_pendiente(&self, cuenta: AccountId) -> Balance {
+                self.saldos_pendientes.get(cuenta).unwrap_or(0)
+            }
+
+            /// Transfiere a quien llama todos sus fondos pendientes.
+            ///
+            /// # Errores
+            /// * `SinFondos`: No hay nada para retirar.
+            /// * `TransferenciaFallida`: El contrato no pudo transferir.
+            #[ink(message)]
+            pub fn retirar_fondos(&mut self) -> Result<Balance, SistemaError> {
+                let cuenta = self.env().caller();
+                let monto = self.obtener_saldo_pendiente(cuenta);
+                if monto == 0 { return Err(SistemaError::SinFondos); }
+
+                // Se descuenta antes de transferir
+                self.saldos_pendientes.remove(cuenta);
+                if self.env().transfer(cuenta, monto).is_err() {
+                    self.saldos_pendientes.insert(cuenta, &monto);
+                    return Err(SistemaError::TransferenciaFallida);
+                }
+
+                self.env().emit_event(FondosRetirados { cuenta, monto });
+                Ok(monto)
+            }
+
+        /// Suma `monto` a los fondos pendientes de `cuenta`.
+        /// Suma o resta una subasta abierta en la que `cuenta` tiene la mejor puja.
+        fn contar_subasta_liderando(&mut self, cuenta: AccountId, suma: bool) {
+            let actual = self.subastas_liderando.get(cuenta).unwrap_or(0);
+            let nuevo = if suma { actual.saturating_add(1) } else { actual.saturating_sub(1) };
+            self.subastas_liderando.insert(cuenta, &nuevo);
+        }
+
+        fn acreditar_saldo(&mut self, cuenta: AccountId, monto: Balance) {
+            if monto == 0 { return; }
+            let saldo = self.obtener_saldo_pendiente(cuenta).saturating_add(monto);
+            self.saldos_pendientes.insert(cuenta, &saldo);
+        }
+        
+
+            /// Cambia el estado de una orden a `Enviada`.
+            ///
+            /// # Requisitos
+            /// * Solo el **Vendedor** de la orden puede ejecutar esto.
+            /// * La orden debe estar en estado `Pendiente`.
+            ///
+            /// # Errores
+            /// * `NoEsParteDeLaOrden`: Quien llama no participa de la orden.
+            /// * `NoEsRolCorrecto`: Quien llama es el comprador.
+            /// * `EntregaDigitalRequerida`: La orden es de un producto digital.
+            #[ink(message)]
+            pub fn marcar_orden_como_enviada(&mut self, orden_id: u32) -> Result<(), SistemaError> {
+            self.actualizar_estado_orden(orden_id, EstadoOrden::Enviada)
+        }
+
+
+            /// Cambia el estado de una orden a `Recibida`.
+            ///
+            /// # Requisitos
+            /// * Solo el **Comprador** de la orden puede ejecutar esto.
+            /// * La orden debe estar en estado `Enviada`.
+            ///
+            /// # Errores
+            /// * `NoEsParteDeLaOrden`: Quien llama no participa de la orden.
+            /// * `NoEsRolCorrecto`: Quien llama es el vendedor.
+            #[ink(message)]
+            pub fn marcar_como_recibida(&mut self, orden_id: u32) -> Result<(), SistemaError> {
+            self.actualizar_estado_orden(orden_id, EstadoOrden::Recibida)
+        }
+
+        /// Actualiza estado de la orden.
+        fn actualizar_estado_orden(&mut self, orden_id: u32, nuevo_estado: EstadoOrden) -> Result<(), SistemaError> {
+            let caller = self.env().caller();
+            self.verificar_registro(caller)?;
+            
+            let mut orden = self.ordenes.get(orden_id).ok_or(SistemaError::OrdenNoExiste)?;
+            
+            // Verificar permiso (usamos referencia a la copia)
+            self.verificar_permiso_orden(caller, &orden, &nuevo_estado)?;
+            if nuevo_estado == EstadoOrden::Enviada && self.es_orden_digital(&orden) {
+                return Err(SistemaError::EntregaDigitalRequerida);
+            }
+            
+            if nuevo_estado == EstadoOrden::Recibida {
+                self.acreditar_saldo(orden.vendedor, orden.deposito);
+                orden.recibida_en = Some(self.env().block_number());
+            }
+            orden.estado = nuevo_estado;
+            self.ordenes.insert(orden_id, &orden);
+            Ok(())
+        }
+
+            /// Compromete la calificación de quien llama sobre la otra parte de la orden
+            /// (el comprador califica al vendedor y viceversa) sin revelarla.
+            ///
+            /// El primer compromiso abre un plazo de `obtener_plazo_compromiso` bloques
+            /// para que la otra parte envíe el suyo. Nadie puede revelar hasta que ambos
+            /// se comprometan o cierre ese plazo, así que quien califica segundo no ve la
+            /// calificación del primero. Quien se compromete segundo solo depende de ese
+            /// cierre, aunque ya haya vencido el plazo desde la recepción.
+            ///
+            /// # Parámetros
+            /// * `orden_id` - ID de la orden recibida.
+            /// * `compromiso` - `hash_calificacion(orden_id, caller, calificacion, comentario, sal)`.
+            ///
+            /// # Errores
+            /// * `OrdenNoExiste`, `NoEsParteDeLaOrden`.
+            /// * `EstadoInvalido`: Si la orden no está `Recibida`.
+            /// * `PlazoVencido`: Si nadie se comprometió y pasaron más de `obtener_plazo_calificacion`
+            ///   bloques desde la recepción. En órdenes recibidas sin bloque de recepción registrado,
+            ///   el plazo empieza al intentar el primer compromiso.
+            /// * `YaCalificado`: Si quien llama ya se comprometió en esta orden.
+            /// * `CompromisoCerrado`: Si venció el plazo abierto por la otra parte.
+            #[ink(message)]
+            pub fn comprometer_calificacion(&mut self, orden_id: u32, compromiso: CompromisoCalificacion) -> Result<(), SistemaError> {
+                let caller = self.env().caller();
+                let mut orden = self.ordenes.get(orden_id).ok_or(SistemaError::OrdenNoExiste)?;
+                Self::verificar_parte_de_orden(caller, &orden)?;
+                if orden.estado != EstadoOrden::Recibida { return Err(SistemaError::EstadoInvalido); }
+
+                let ahora = self.env().block_number();
+                // Con el plazo de compromiso abierto por la otra parte rige solo su `cierre`
+                let mut compromisos = match self.compromisos_calificacion.get(orden_id) {
+                    Some(compromisos) => compromisos,
+                    None => {
+                        let recibida_en = match orden.recibida_en {
+                            Some(bloque) => bloque,
+                            // Recibida antes de registrarse el bloque de recepción: el plazo corre desde ahora
+                            None => {
+                                orden.recibida_en = Some(ahora);
+                                self.ordenes.insert(orden_id, &orden);
+                                ahora
+                            }
+                        };
+                        if ahora > recibida_en.saturating_add(self.bloques_plazo_calificacion) {
+                            return Err(SistemaError::PlazoVencido);
+                        }
+                        CompromisosOrden {
+                            al_vendedor: None,
+                            al_comprador: None,
+                            cierre: ahora.saturating_add(self.bloques_plazo_compromiso),
+                        }
+                    }
+                };
+                let rol = Self::rol_calificado_por(caller, &orden);
+                let propio = match rol {
+                    RolCalificado::Vendedor => &mut compromisos.al_vendedor,
+                    RolCalificado::Comprador => &mut compromisos.al_comprador,
+                };
+                if propio.is_some() { return Err(SistemaError::YaCalificado); }
+                if ahora > compromisos.cierre { return Err(SistemaError::CompromisoCerrado); }
+                *propio = Some(compromiso);
+                self.compromisos_calificacion.insert(orden_id, &compromisos);
+
+                self.env().emit_event(CalificacionComprometida { orden_id, rol });
+                Ok(())
+            }
+
+            /// Revela la calificación comprometida por quien llama y la aplica a la
+            /// reputación de la otra parte.
+            ///
+            /// # Parámetros
+            /// * `calificacion` - Valor entero entre 1 y 5.
+            /// * `comentario` - Texto opcional de hasta `MAX_LARGO_COMENTARIO_CALIFICACION` bytes.
+            /// * `sal` - La misma usada para calcular el compromiso.
+            ///
+            /// # Errores
+            /// * `OrdenNoExiste`, `NoEsParteDeLaOrden`.
+            /// * `CompromisoNoExiste`: Quien llama no se comprometió.
+            /// * `YaCalificado`: Ya la reveló.
+            /// * `RevelacionAnticipada`: La otra parte no se comprometió y el plazo sigue abierto.
+            /// * `CompromisoInvalido`: Los datos no coinciden con el compromiso.
+            /// * `CalificacionInvalida`: El valor no está entre 1 y 5.
+            /// * `ComentarioInvalido`: El comentario está vacío o no es válido.
+            #[ink(message)]
+            pub fn revelar_calificacion(&mut self, orden_id: u32, calificacion: u32, comentario: Option<String>, sal: [u8; 32]) -> Result<(), SistemaError> {
+                let caller = self.env().caller();
+                let mut orden = self.ordenes.get(orden_id).ok_or(SistemaError::OrdenNoExiste)?;
+                Self::verificar_parte_de_orden(caller, &orden)?;
+                let compromisos = self.compromisos_calificacion.get(orden_id).ok_or(SistemaError::CompromisoNoExiste)?;
+                let rol = Self::rol_calificado_por(caller, &orden);
+                let (propio, ajeno, revelada) = match rol {
+                    RolCalificado::Vendedor => (compromisos.al_vendedor, compromisos.al_comprador, &mut orden.comprador_califico),
+                    RolCalificado::Comprador => (compromisos.al_comprador, compromisos.al_vendedor, &mut orden.vendedor_califico),
+                };
+                let propio = propio.ok_or(SistemaError::CompromisoNoExiste)?;
+                if *revelada { return Err(SistemaError::YaCalificado); }
+                if ajeno.is_none() && self.env().block_number() <= compromisos.cierre {
+                    return Err(SistemaError::RevelacionAnticipada);
+                }
+                if hash_calificacion(orden_id, caller, calificacion, &comentario, &sal) != propio {
+                    return Err(SistemaError::CompromisoInvalido);
+                }
+                if !(1..=5).contains(&calificacion) { return Err(SistemaError::CalificacionInvalida); }
+                let comentario = comentario.map(|c| Self::normalizar_comentario(&c)).transpose()?;
+
+                *revelada = true;
+                self.ordenes.insert(orden_id, &orden);
+
+                match rol {
+                    RolCalificado::Vendedor => {
+                        self.registrar_calificacion(&orden, caller, rol, calificacion, comentario.clone());
+                        self.env().emit_event(CompradorCalifico { orden_id, comprador: caller, vendedor: orden.vendedor, calificacion, comentario });
+                    }
+                    RolCalificado::Comprador => {
+                        self.registrar_calificacion(&orden, caller, rol, calificacion, comentario.clone());
+                        self.env().emit_event(VendedorCalifico { orden_id, vendedor: caller, comprador: orden.comprador, calificacion, comentario });
+                    }
+                }
+                Ok(())
+            }
+
+            /// Compromisos de calificación de una orden, si alguna parte ya se comprometió.
+            #[ink(message)]
+            pub fn obtener_compromisos_calificacion(&self, orden_id: u32) -> Option<CompromisosOrden> {
+                self.compromisos_calificacion.get(orden_id)
+            }
+
+        /// Lado que califica quien llama: el comprador al vendedor y el vendedor al comprador.
+        /// `caller` ya fue verificado como parte de la orden.
+        fn rol_calificado_por(caller: AccountId, orden: &Orden) -> RolCalificado {
+            if caller == orden.comprador { RolCalificado::Vendedor } else { RolCalificado::Comprador }
+        }
+
+        /// Guarda el registro de la calificación, lo indexa para el calificado y
+        /// actualiza su reputación simple y ponderada. `puntaje` ya viene validado entre 1 y 5.
+        fn registrar_calificacion(&mut self, orden: &Orden, calificador: AccountId, rol: RolCalificado, puntaje: u32, comentario: Option<String>) {
+            let orden_id = orden.id;
+            let calificado = match rol {
+                RolCalificado::Comprador => orden.comprador,
+                RolCalificado::Vendedor => orden.vendedor,
+            };
+            let bloque = self.env().block_number();
+            self.calificaciones.insert((orden_id, rol), &Calificacion {
+                orden_id,
+                calificador,
+                calificado,
+                rol,
+                puntaje: puntaje as u8,
+                bloque,
+                comentario,
+                respuesta: None,
+                comentario_oculto: false,
+                respuesta_oculta: false,
+            });
+            let Some(mut u) = self.usuario(calificado) else { return };
+            let (suma, cantidad) = match rol {
+                RolCalificado::Comprador => (&mut u.reputacion_como_comprador, &mut u.calificaciones_como_comprador),
+                RolCalificado::Vendedor => (&mut u.reputacion_como_vendedor, &mut u.calificaciones_como_vendedor),
+            };
+            self.calificaciones_recibidas.insert((calificado, rol, *cantidad), &orden_id);
+            *suma = suma.saturating_add(puntaje);
+            *cantidad = cantidad.saturating_add(1);
+            self.guardar_usuario(&u);
+
+            let mut ponderada = self.reputacion_ponderada.get((calificado, rol)).unwrap_or_default();
+            ponderada.acumular(puntaje, orden.total, bloque);
+            self.reputacion_ponderada.insert((calificado, rol), &ponderada);
+        }
+
+        /// Recorta el comentario y verifica que no quede vacío ni exceda el largo permitido.
+        fn normalizar_comentario(texto: &str) -> Result<String, SistemaError> {
+            let texto = texto.trim();
+            if texto.is_empty() || !Self::texto_valido(texto, MAX_LARGO_COMENTARIO_CALIFICACION) {
+                return Err(SistemaError::ComentarioInvalido);
+            }
+            Ok(String::from(texto))
+        }
+
+            /// Publica la única respuesta a la calificación que recibió quien llama en la orden.
+            ///
+            /// # Errores
+            /// * `ComentarioInvalido`: La respuesta está vacía o no es válida.
+            /// * `OrdenNoExiste`, `NoEsParteDeLaOrden`.
+            /// * `CalificacionNoExiste`: Quien llama todavía no fue calificado en esta orden.
+            /// * `YaRespondido`: Ya respondió (aunque la respuesta haya sido ocultada).
+            #[ink(message)]
+            pub fn responder_calificacion(&mut self, orden_id: u32, respuesta: String) -> Result<(), SistemaError> {
+                let caller = self.env().caller();
+                let respuesta = Self::normalizar_comentario(&respuesta)?;
+                let orden = self.ordenes.get(orden_id).ok_or(SistemaError::OrdenNoExiste)?;
+                Self::verificar_parte_de_orden(caller, &orden)?;
+                let rol = if orden.vendedor == caller { RolCalificado::Vendedor } else { RolCalificado::Comprador };
+
+                let mut calificacion = self.calificaciones.get((orden_id, rol)).ok_or(SistemaError::CalificacionNoExiste)?;
+                if calificacion.respuesta.is_some() || calificacion.respuesta_oculta {
+                    return Err(SistemaError::YaRespondido);
+                }
+                calificacion.respuesta = Some(respuesta.clone());
+                self.calificaciones.insert((orden_id, rol), &calificacion);
+
+                self.env().emit_event(CalificacionRespondida { orden_id, rol, autor: caller, respuesta });
+                Ok(())
+            }
+
+            /// Borra el comentario o la respuesta de una calificación sin tocar el puntaje.
+            ///
+            /// # Errores
+            /// * `NoEsModerador`: Quien llama no es moderador ni administrador.
+            /// * `CalificacionNoExiste`: No hay calificación para esa orden y lado, o no tiene ese texto.
+            #[ink(message)]
+            pub fn ocultar_texto_calificacion(&mut self, orden_id: u32, rol: RolCalificado, texto: TextoCalificacion) -> Result<(), SistemaError> {
+                let caller = self.env().caller();
+                if !self.es_moderador(caller) {
+                    return Err(SistemaError::NoEsModerador);
+                }
+                let mut calificacion = self.calificaciones.get((orden_id, rol)).ok_or(SistemaError::CalificacionNoExiste)?;
+                let (contenido, oculto) = match texto {
+                    TextoCalificacion::Comentario => (&mut calificacion.comentario, &mut calificacion.comentario_oculto),
+                    TextoCalificacion::Respuesta => (&mut calificacion.respuesta, &mut calificacion.respuesta_oculta),
+                };
+                if contenido.take().is_none() {
+                    return Err(SistemaError::CalificacionNoExiste);
+                }
+                *oculto = true;
+                self.calificaciones.insert((orden_id, rol), &calificacion);
+
+                self.env().emit_event(TextoCalificacionOculto { orden_id, rol, texto, moderador: caller });
+                Ok(())
+            }
+
+            /// `true` si la cuenta es moderador designado o el administrador.
+            #[ink(message)]
+            pub fn es_moderador(&self, cuenta: AccountId) -> bool {
+                cuenta == self.administrador || self.moderadores.contains(cuenta)
+            }
+
+            /// Designa (`activo = true`) o remueve un moderador.
+            ///
+            /// # Errores
+            /// * `NoEsAdministrador`: Quien llama no es el administrador.
+            #[ink(message)]
+            pub fn configurar_moderador(&mut self, cuenta: AccountId, activo: bool) -> Result<(), SistemaError> {
+                self.verificar_administrador(self.env().caller())?;
+                if activo {
+                    self.moderadores.insert(cuenta, &());
+                } else {
+                    self.moderadores.remove(cuenta);
+                }
+                self.env().emit_event(ModeradorActualizado { cuenta, activo });
+                Ok(())
+            }
+
+            /// Calificaciones de una orden: la recibida por el vendedor y la recibida
+            /// por el comprador, en ese orden, omitiendo las que aún no se emitieron.
+            #[ink(message)]
+            pub fn calificacion_de_orden(&self, orden_id: u32) -> Vec<Calificacion> {
+                [RolCalificado::Vendedor, RolCalificado::Comprador]
+                    .into_iter()
+                    .filter_map(|rol| self.calificaciones.get((orden_id, rol)))
+                    .collect()
+            }
+
+            /// Página de calificaciones recibidas por una cuenta en un rol, de la más
+            /// antigua a la más reciente.
+            ///
+            /// # Parámetros
+            /// * `cuenta` - Usuario calificado.
+            /// * `rol` - Lado en el que fue calificado.
+            /// * `desde` - Índice de la primera calificación.
+            /// * `limite` - Cantidad máxima a devolver (acotada a `MAX_PAGINA_CALIFICACIONES`).
+            #[ink(message)]
+            pub fn calificaciones_recibidas(&self, cuenta: AccountId, rol: RolCalificado, desde: u32, limite: u32) -> Vec<Calificacion> {
+                let total = self.usuario(cuenta).map(|u| match rol {
+                    RolCalificado::Comprador => u.calificaciones_como_comprador,
+                    RolCalificado::Vendedor => u.calificaciones_como_vendedor,
+                }).unwrap_or(0);
+                let hasta = desde.saturating_add(limite.min(MAX_PAGINA_CALIFICACIONES)).min(total);
+                (desde..hasta)
+                    .filter_map(|i| self.calificaciones_recibidas.get((cuenta, rol, i)))
+                    .filter_map(|orden_id| self.calificaciones.get((orden_id, rol)))
+                    .collect()
+            }
+
+
+            /// Permite al comprador reseñar el producto de una orden recibida. Si la orden es
+            /// de un paquete, la reseña se registra en cada componente que siga existiendo.
+            ///
+            /// # Parámetros
+            /// * `orden_id` - ID de la orden `Recibida`.
+            /// * `puntaje` - Valor entero entre 1 y 5.
+            /// * `contenido` - Texto de hasta `MAX_LARGO_RESENA` bytes o hash del contenido.
+            ///
+            /// # Errores
+            /// * `CalificacionInvalida`: Si el puntaje no está entre 1 y 5.
+            /// * `ResenaInvalida`: Si el texto está vacío, es largo o tiene caracteres de control.
+            /// * `OrdenNoExiste`, `NoEsParteDeLaOrden`.
+            /// * `NoEsRolCorrecto`: Si quien llama es el vendedor de la orden.
+            /// * `EstadoInvalido`: Si la orden no está `Recibida`.
+            /// * `YaResenado`: Si la orden ya tiene reseña.
+            #[ink(message)]
+            pub fn resenar_producto(&mut self, orden_id: u32, puntaje: u8, contenido: ContenidoResena) -> Result<(), SistemaError> {
+                let caller = self.env().caller();
+                if !(1..=5).contains(&puntaje) { return Err(SistemaError::CalificacionInvalida); }
+                let contenido = match contenido {
+                    ContenidoResena::Texto(texto) => {
+                        let texto = texto.trim();
+                        if texto.is_empty() || !Self::texto_valido(texto, MAX_LARGO_RESENA) {
+                            return Err(SistemaError::ResenaInvalida);
+                        }
+                        ContenidoResena::Texto(String::from(texto))
+                    }
+                    hash => hash,
+                };
+
+                let orden = self.ordenes.get(orden_id).ok_or(SistemaError::OrdenNoExiste)?;
+                Self::verificar_parte_de_orden(caller, &orden)?;
+                if orden.comprador != caller { return Err(SistemaError::NoEsRolCorrecto); }
+                if orden.estado != EstadoOrden::Recibida { return Err(SistemaError::EstadoInvalido); }
+                if self.ordenes_resenadas.contains(orden_id) { return Err(SistemaError::YaResenado); }
+
+                let productos: Vec<Producto> = match orden.paquete_id.and_then(|id| self.paquetes.get(id)) {
+                    Some(paquete) => paquete.componentes.iter().filter_map(|(id, _)| self.productos.get(id)).collect(),
+                    None => self.productos.get(orden.producto_id).into_iter().collect(),
+                };
+                if productos.is_empty() { return Err(SistemaError::ProductoNoExiste); }
+
+                let resena = Resena { orden_id, autor: caller, puntaje, contenido };
+                for mut producto in productos {
+                    let indice = producto.cantidad_resenas;
+                    producto.cantidad_resenas = indice.checked_add(1).ok_or(SistemaError::DesbordamientoId)?;
+                    producto.suma_puntajes_resenas = producto.suma_puntajes_resenas.saturating_add(puntaje as u32);
+
+                    self.resenas.insert((producto.id, indice), &resena);
+                    self.productos.insert(producto.id, &producto);
+                    self.env().emit_event(ResenaPublicada { producto_id: producto.id, orden_id, autor: caller, puntaje });
+                }
+                self.ordenes_resenadas.insert(orden_id, &());
+                Ok(())
+            }
+
+            /// Lista las reseñas de un producto, en orden de publicación.
+            ///
+            /// # Parámetros
+            /// * `desde` - Índice de la primera reseña a devolver.
+            /// * `limite` - Cantidad máxima a devolver (acotada a `MAX_PAGINA_RESENAS`).
+            #[ink(message)]
+            pub fn resenas_producto(&self, producto_id: u32, desde: u32, limite: u32) -> Vec<Resena> {
+                let total = self.productos.get(producto_id).map(|p| p.cantidad_resenas).unwrap_or(0);
+                let hasta = desde.saturating_add(limite.min(MAX_PAGINA_RESENAS)).min(total);
+                (desde..hasta).filter_map(|i| self.resenas.get((producto_id, i))).collect()
+            }
+
+        /// Lee un usuario; los guardados con el formato previo a `Permisos` se convierten.
+        fn usuario(&self, cuenta: AccountId) -> Option<Usuario> {
+            self.usuarios.get(cuenta).or_else(|| self.usuarios_legacy.get(cuenta).map(Usuario::from))
+        }
+
+        /// Si la cuenta está registrada, con cualquiera de los dos formatos.
+        fn existe_usuario(&self, cuenta: AccountId) -> bool {
+            self.usuarios.contains(cuenta) || self.usuarios_legacy.contains(cuenta)
+        }
+
+        /// Guarda un usuario con el formato actual y descarta su versión anterior.
+        fn guardar_usuario(&mut self, usuario: &Usuario) {
+            self.usuarios.insert(usuario.direccion, usuario);
+            self.usuarios_legacy.remove(usuario.direccion);
+        }
+
+        /// Verifica si un usuario está registrado.
+        fn verificar_registro(&self, usuario: AccountId) -> Result<(), SistemaError> {
+            if !self.existe_usuario(usuario) {
+                Err(SistemaError::UsuarioNoRegistrado)
+            } else {
+                Ok(())
+            }
+        }
+
+        /// Verifica que la cuenta sea el administrador del contrato.
+        fn verificar_administrador(&self, cuenta: AccountId) -> Result<(), SistemaError> {
+            if cuenta != self.administrador {
+                return Err(SistemaError::NoEsAdministrador);
+            }
+            Ok(())
+        }
+
+        /// Verifica si el usuario tiene los permisos del rol requerido
+        /// (`Ambos` exige poder comprar y vender).
+        fn verificar_rol(&self, usuario: AccountId, rol_requerido: RolUsuario) -> Result<(), SistemaError> {
+            let usuario_data = self.usuario(usuario)
+                .ok_or(SistemaError::UsuarioNoRegistrado)?;
+            self.verificar_cuenta_abierta(usuario)?;
+            let requeridos = Permisos::from(rol_requerido);
+
+            if (requeridos.comprar && !usuario_data.permisos.comprar) || (requeridos.vender && !usuario_data.permisos.vender) {
+                return Err(SistemaError::NoEsRolCorrecto);
+            }
+            Ok(())
+        }
+
+
+        fn verificar_cantidad(&self, cantidad: u32) -> Result<(), SistemaError> {
+            if cantidad == 0 {
+                Err(SistemaError::CantidadInsuficiente)
+            } else {
+                Ok(())
+            }
+        }
+
+
+        fn agregar_producto(
+            &mut self,
+            nombre: String,
+            descripcion: String,
+            precio: Balance,
+            cantidad: u32,
+            categoria: String,
+            vendedor: AccountId,
+        ) -> Result<u32, SistemaError> {
+            let id = self.next_producto_id;
+            self.next_producto_id = self.next_producto_id.checked_add(1).ok_or(SistemaError::DesbordamientoId)?;
+
+            let mut nuevo = Producto::nuevo(id, nombre, descripcion, precio, cantidad, categoria, vendedor);
+            nuevo.expira_en = self.env().block_timestamp().saturating_add(self.duracion_publicacion.vigencia);
+            self.productos.insert(id, &nuevo);
+            let mut propios = self.productos_vendedor.get(vendedor).unwrap_or_default();
+            propios.push(id);
+            self.productos_vendedor.insert(vendedor, &propios);
+
+            // Evento de publicación
+            self.env().emit_event(ProductoPublicado { vendedor, producto_id: id });
+
+            Ok(id)
+        }
+
+
+        fn verificar_permiso_orden(
+            &self,
+            caller: AccountId,
+            orden: &Orden,
+            nuevo_estado: &EstadoOrden
+        ) -> Result<(), SistemaError> {
+            Self::verificar_parte_de_orden(caller, orden)?;
+            match nuevo_estado {
+                EstadoOrden::Enviada if caller != orden.vendedor => Err(SistemaError::NoEsRolCorrecto),
+                EstadoOrden::Recibida if caller != orden.comprador => Err(SistemaError::NoEsRolCorrecto),
+                _ => self.verificar_transicion_estado(&orden.estado, nuevo_estado),
+            }
+        }
+
+        /// Verifica que quien llama sea el comprador o el vendedor de la orden.
+        fn verificar_parte_de_orden(caller: AccountId, orden: &Orden) -> Result<(), SistemaError> {
+            if caller != orden.comprador && caller != orden.vendedor {
+                return Err(SistemaError::NoEsParteDeLaOrden);
+            }
+            Ok(())
+        }
+
+        /// Verifica que la transición de estado de la orden sea válida.
+        fn verificar_transicion_estado(
+            &self,
+            actual: &EstadoOrden,
+            nuevo: &EstadoOrden
+        ) -> Result<(), SistemaError> {
+            match (actual, nuevo) {
+                (EstadoOrden::Pendiente, EstadoOrden::Enviada) => Ok(()),
+                (EstadoOrden::Enviada, EstadoOrden::Recibida) => Ok(()),
+                _ => Err(SistemaError::EstadoInvalido),
+            }
+        }
+
+            /// Inicia o completa el proceso de cancelación.
+            ///
+            /// Si el Comprador llama, marca su solicitud.
+            /// Si el Vendedor llama, marca su aceptación.
+            /// Cuando **ambos** han aceptado, la orden pasa a `Cancelada` y se devuelve el stock
+            /// (a la variante comprada o a cada componente del paquete, si corresponde). El depósito, si lo hay, queda
+            /// disponible para que el comprador lo retire.
+            ///
+            /// # Errores
+            /// * `EstadoInvalido`: Solo se pueden cancelar órdenes `Pendiente`.
+            /// * `NoEsParteDeLaOrden`: Quien llama no es comprador ni vendedor de la orden.
+            #[ink(message)]
+            pub fn solicitar_cancelacion_orden(&mut self, orden_id: u32) -> Result<(), SistemaError> {
+            let caller = self.env().caller();
+            if !self.existe_usuario(caller) { return Err(SistemaError::UsuarioNoRegistrado); }
+
+            let (prod_id, variante_id, cant, cancelar) = {
+                let mut orden = self.ordenes.get(orden_id).ok_or(SistemaError::OrdenNoExiste)?;
+                if orden.estado != EstadoOrden::Pendiente { return Err(SistemaError::EstadoInvalido); }
+
+                if caller == orden.comprador { orden.comprador_solicita_cancelacion = true; }
+                else if caller == orden.vendedor { orden.vendedor_acepta_cancelacion = true; }
+                else { return Err(SistemaError::NoEsParteDeLaOrden); }
+                
+                self.ordenes.insert(orden_id, &orden);
+
+                (orden.producto_id, orden.variante_id, orden.cantidad, orden.comprador_solicita_cancelacion && orden.vendedor_acepta_cancelacion)
+            };
+
+            if cancelar {
+                let mut orden = self.ordenes.get(orden_id).ok_or(SistemaError::OrdenNoExiste)?;
+                orden.estado = EstadoOrden::Cancelada;
+                self.ordenes.insert(orden_id, &orden);
+                self.acreditar_saldo(orden.comprador, orden.deposito);
+                
+                // Sin variantes el stock pasa primero por la cola de preventas
+                match orden.paquete_id.and_then(|id| self.paquetes.get(id)) {
+                    Some(paquete) => {
+                        for (componente_id, unidades) in paquete.componentes {
+                            self.distribuir_stock(componente_id, unidades.saturating_mul(cant));
+                        }
+                    }
+                    None if variante_id.is_none() => self.distribuir_stock(prod_id, cant),
+                    None => {
+                        if let Some(mut p) = self.productos.get(prod_id) {
+                            p.devolver_stock(variante_id, cant);
+                            self.productos.insert(prod_id, &p);
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+
+
+            /// Retorna todos los productos guardados, incluidos los despublicados y vencidos
+            /// (`reportes_view` los necesita para nombrar ventas pasadas).
+            #[ink(message)]
+            pub fn obtener_todos_los_productos(&self) -> Vec<Producto> {
+            let mut productos = Vec::new();
+            for id in 0..self.next_producto_id {
+                if let Some(prod) = self.productos.get(id) {
+                    productos.push(prod);
+                }
+            }
+            productos
+        }
+
+            /// Retorna los productos que se pueden comprar: activos y con la publicación vigente.
+            #[ink(message)]
+            pub fn obtener_productos_vigentes(&self) -> Vec<Producto> {
+                let ahora = self.env().block_timestamp();
+                self.obtener_todos_los_productos()
+                    .into_iter()
+                    .filter(|prod| prod.activo && prod.expira_en > ahora)
+                    .collect()
+            }
+
+        /// Retorna todas las órdenes.
+        #[ink(message)]
+        pub fn obtener_todas_las_ordenes(&self) -> Vec<Orden> {
+            let mut ordenes = Vec::new();
+            for id in 0..self.next_orden_id {
+                if let Some(orden) = self.ordenes.get(id) {
+                    ordenes.push(orden);
+                }
+            }
+            ordenes
+        }
+
+        /// Retorna todos los paquetes.
+        #[ink(message)]
+        pub fn obtener_todos_los_paquetes(&self) -> Vec<Paquete> {
+            (0..self.next_paquete_id).filter_map(|id| self.paquetes.get(id)).collect()
+        }
+
+        /// Retorna todos los usuarios.
+        #[ink(message)]
+        pub fn obtener_todos_los_usuarios(&self) -> Vec<Usuario> {
+            let mut lista_completa: Vec<Usuario> = Vec::new();
+            
+            for id in &self.lista_usuarios_ids {
+                if let Some(usuario) = self.usuario(*id) {
+                    lista_completa.push(usuario);
+                }
+            }
+            
+            lista_completa
+        }
+    }
+
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink::env::test;
+
+        fn setup_contract_con_vendedor() -> Marketplace {
+            let mut contrato = Marketplace::nuevo();
+            let caller = AccountId::from([0x10; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(caller);
+            let usuario = Usuario {
+                direccion: caller,
+                permisos: Permisos::from(RolUsuario::Vendedor),
+                reputacion_como_comprador: 0,
+                reputacion_como_vendedor: 0,
+                calificaciones_como_comprador: 0,
+                calificaciones_como_vendedor: 0,
+                perfil: PerfilUsuario::default(),
+                verificacion: None,
+            };
+            contrato.usuarios.insert(caller, &usuario);
+            contrato
+        }
+        
+     
+        #[ink::test]
+        fn registrar_usuario_comprador_ok() {
+            let mut contrato = Marketplace::nuevo();
+
+      
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let maria = cuentas.alice;
+            test::set_caller::<ink::env::DefaultEnvironment>(maria);
+
+            // Llamamos a la función registrar_usuario con el rol de comprador
+            let resultado = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Verificamos que devuelva OK
+            assert_eq!(resultado, Ok(()));
+
+            // Obtenemos el usuario usando la dirección del caller
+            let usuario_registrado = contrato.usuarios.get(maria);
+
+            // Confirmamos si se guardó el usuario
+            assert!(usuario_registrado.is_some());
+
+            // Verificamos los datos
+            let usuario = usuario_registrado.unwrap();
+            assert_eq!(usuario.permisos, Permisos::from(RolUsuario::Comprador));
+            assert_eq!(usuario.reputacion_como_comprador, 0);
+            assert_eq!(usuario.reputacion_como_vendedor, 0);
+        }
+
+        #[ink::test]
+        fn registrar_usuario_vendedor_ok() {
+            let mut contrato = Marketplace::nuevo();
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let juan = cuentas.bob;
+            test::set_caller::<ink::env::DefaultEnvironment>(juan);
+
+            // Llamamos a la función registrar_usuario con el rol de vendedor
+            let resultado = contrato.registrar_usuario(RolUsuario::Vendedor);
+
+            // Verificamos que devuelva OK
+            assert_eq!(resultado, Ok(()));
+
+            // Obtenemos el usuario usando la dirección del caller
+            let usuario_registrado = contrato.usuarios.get(juan);
+
+            // Confirmamos si se guardó el usuario
+            assert!(usuario_registrado.is_some());
+
+            // Verificamos los datos
+            let usuario = usuario_registrado.unwrap();
+            assert_eq!(usuario.permisos, Permisos::from(RolUsuario::Vendedor));
+            assert_eq!(usuario.reputacion_como_comprador, 0);
+            assert_eq!(usuario.reputacion_como_vendedor, 0);
+        }
+
+        #[ink::test]
+        fn registrar_usuario_ambos_ok() {
+            let mut contrato = Marketplace::nuevo();
+
+            // Simulamos que el caller es "Carlos"
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let carlos = cuentas.charlie;
+            test::set_caller::<ink::env::DefaultEnvironment>(carlos);
+
+            // Llamamos a la función registrar_usuario con el rol de ambos
+            let resultado = contrato.registrar_usuario(RolUsuario::Ambos);
+
+            // Verificamos que devuelva OK
+            assert_eq!(resultado, Ok(()));
+
+            // Obtenemos el usuario usando la dirección del caller
+            let usuario_registrado = contrato.usuarios.get(carlos);
+
+            // Confirmamos si se guardó el usuario
+            assert!(usuario_registrado.is_some());
+
+            // Verificamos los datos
+            let usuario = usuario_registrado.unwrap();
+            assert_eq!(usuario.permisos, Permisos::from(RolUsuario::Ambos));
+            assert_eq!(usuario.reputacion_como_comprador, 0);
+            assert_eq!(usuario.reputacion_como_vendedor, 0);
+        }
+
+        #[ink::test]
+        fn registrar_usuario_duplicado_falla() {
+            let mut contrato = Marketplace::nuevo();
+
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+
+            // Primer registro
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Segundo registro debería fallar porque ya está registrado
+            let resultado = contrato.registrar_usuario(RolUsuario::Vendedor);
+            assert_eq!(resultado, Err(SistemaError::UsuarioExistente));
+        }
+
+        // --- Modificación de roles ---
+        #[ink::test]
+        fn modificar_rol_usuario_comprador_a_vendedor_ok() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Cambia el caller a un usuario registrado como Comprador
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let maria = cuentas.alice;
+            test::set_caller::<ink::env::DefaultEnvironment>(maria);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Modifica el rol a Vendedor
+            let resultado = contrato.modificar_rol_usuario(RolUsuario::Vendedor);
+            assert!(resultado.is_ok());
+
+            // Verifica que el rol se haya actualizado correctamente
+            let usuario = contrato.obtener_usuario(maria).unwrap();
+            assert_eq!(usuario.permisos, Permisos::from(RolUsuario::Vendedor));
+        }
+
+        #[ink::test]
+        fn modificar_rol_usuario_vendedor_a_comprador_ok() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Cambia el caller a un usuario registrado como Vendedor
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let juan = cuentas.bob;
+            test::set_caller::<ink::env::DefaultEnvironment>(juan);
+            let _ = contrato.registrar_usuario(RolUsuario::Vendedor);
+
+            // Modifica el rol a Comprador
+            let resultado = contrato.modificar_rol_usuario(RolUsuario::Comprador);
+            assert!(resultado.is_ok());
+
+            // Verifica que el rol se haya actualizado correctamente
+            let usuario = contrato.obtener_usuario(juan).unwrap();
+            assert_eq!(usuario.permisos, Permisos::from(RolUsuario::Comprador));
+        }
+
+        #[ink::test]
+        fn modificar_rol_usuario_ambos_a_comprador_ok() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Cambia el caller a un usuario registrado como Ambos
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let carlos = cuentas.charlie;
+            test::set_caller::<ink::env::DefaultEnvironment>(carlos);
+            let _ = contrato.registrar_usuario(RolUsuario::Ambos);
+
+            // Modifica el rol a Comprador
+            let resultado = contrato.modificar_rol_usuario(RolUsuario::Comprador);
+            assert!(resultado.is_ok());
+
+            // Verifica que el rol se haya actualizado correctamente
+            let usuario = contrato.obtener_usuario(carlos).unwrap();
+            assert_eq!(usuario.permisos, Permisos::from(RolUsuario::Comprador));
+        }
+
+        #[ink::test]
+        fn emite_evento_rol_actualizado() {
+            let mut c = Marketplace::nuevo();
+            let cuentas = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let maria = cuentas.alice;
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(maria);
+
+            c.registrar_usuario(RolUsuario::Comprador).unwrap();
+
+            // Grabamos eventos durante la llamada que cambia el rol
+            c.modificar_rol_usuario(RolUsuario::Vendedor).unwrap();
+            let eventos = ink::env::test::recorded_events().collect::<Vec<_>>();
+            assert!(!eventos.is_empty(), "Debe emitirse al menos un evento");
+        }
+
+
+        #[ink::test]
+        fn modificar_rol_usuario_no_registrado_falla() {
+            let mut contrato = Marketplace::nuevo();
+
+            // Cambia el caller a un usuario no registrado
+            let caller = AccountId::from([0x05; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(caller);
+
+            // Intenta modificar el rol
+            let resultado = contrato.modificar_rol_usuario(RolUsuario::Vendedor);
+            assert!(matches!(resultado, Err(SistemaError::UsuarioNoRegistrado)));
+        }
+
+        #[ink::test]
+        fn modificar_rol_usuario_mismo_rol_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Cambia el caller a un usuario registrado como Comprador
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let maria = cuentas.alice;
+            test::set_caller::<ink::env::DefaultEnvironment>(maria);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Intenta cambiar a Comprador nuevamente
+            let resultado = contrato.modificar_rol_usuario(RolUsuario::Comprador);
+            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+        }
+
+        #[ink::test]
+        fn modificar_rol_usuario_no_puede_cambiar_a_vendedor_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Cambia el caller a un usuario registrado como Vendedor
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let juan = cuentas.bob;
+            test::set_caller::<ink::env::DefaultEnvironment>(juan);
+            let _ = contrato.registrar_usuario(RolUsuario::Vendedor);
+
+            // Intenta cambiar a Vendedor, lo cual no es permitido
+            let resultado = contrato.modificar_rol_usuario(RolUsuario::Vendedor);
+            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+        }
+
+        #[ink::test]
+        fn modificar_rol_usuario_no_puede_cambiar_a_comprador_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Cambia el caller a un usuario registrado como Comprador
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let maria = cuentas.alice;
+            test::set_caller::<ink::env::DefaultEnvironment>(maria);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Intenta cambiar a Comprador, lo cual no es permitido
+            let resultado = contrato.modificar_rol_usuario(RolUsuario::Comprador);
+            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+        }   
+
+        // --- Publicación de productos ---
+        #[ink::test]
+        fn publicar_producto_ok() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            let resultado = contrato.publicar_producto(
+                "Celular".to_string(),
+                "Un buen celular".to_string(),
+                1000,
+                5,
+                "Tecnología".to_string(),
+            );
+
+            assert!(resultado.is_ok());
+            assert_eq!(contrato.obtener_cantidad_productos(), 1);
+
+            let producto = contrato.productos.get(0).unwrap();
+            assert_eq!(producto.nombre, "Celular");
+            assert_eq!(producto.precio, 1000);
+        }
+
+        #[ink::test]
+        fn publicar_producto_no_registrado_falla() {
+            let mut contrato = Marketplace::nuevo();
+
+            let caller = AccountId::from([0x02; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(caller);
+
+            let resultado = contrato.publicar_producto(
+                "Producto".to_string(),
+                "Sin registro".to_string(),
+                500,
+                1,
+                "Otros".to_string(),
+            );
+
+            assert!(matches!(resultado, Err(SistemaError::UsuarioNoRegistrado)));
+        }
+
+        #[ink::test]
+        fn publicar_producto_no_es_vendedor_falla() {
+            let mut contrato = Marketplace::nuevo();
+
+            let caller = AccountId::from([0x03; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(caller);
+
+            let usuario = Usuario {
+                direccion: caller,
+                permisos: Permisos::from(RolUsuario::Comprador), // Rol no válido para publicar productos
+                reputacion_como_comprador: 0,
+                reputacion_como_vendedor: 0,
+                calificaciones_como_comprador: 0,
+                calificaciones_como_vendedor: 0,
+                perfil: PerfilUsuario::default(),
+                verificacion: None,
+            };
+            contrato.usuarios.insert(caller, &usuario);
+
+            let resultado = contrato.publicar_producto(
+                "Producto".to_string(),
+                "No autorizado".to_string(),
+                100,
+                2,
+                "Otros".to_string(),
+            );
+
+            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+        }
+
+        #[ink::test]
+        fn publicar_producto_cantidad_cero_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            let resultado = contrato.publicar_producto(
+                "Producto".to_string(),
+                "Cantidad cero".to_string(),
+                100,
+                0, // Cantidad inválida
+                "Otros".to_string(),
+            );
+
+            assert!(matches!(resultado, Err(SistemaError::CantidadInsuficiente)));
+        }
+
+        #[ink::test]
+        fn publicar_producto_precio_cero_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            let resultado = contrato.publicar_producto("Producto".to_string(), "Gratis".to_string(), 0, 1, "Otros".to_string());
+
+            assert_eq!(resultado, Err(SistemaError::PrecioInvalido));
+        }
+
+        #[ink::test]
+        fn publicar_producto_nombre_vacio_o_largo_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            let vacio = contrato.publicar_producto("   ".to_string(), "D".to_string(), 100, 1, "C".to_string());
+            assert_eq!(vacio, Err(SistemaError::NombreInvalido));
+
+            let largo = contrato.publicar_producto("x".repeat(65), "D".to_string(), 100, 1, "C".to_string());
+            assert_eq!(largo, Err(SistemaError::NombreInvalido));
+
+            let control = contrato.publicar_producto("Cel\u{0}ular".to_string(), "D".to_string(), 100, 1, "C".to_string());
+            assert_eq!(control, Err(SistemaError::NombreInvalido));
+            assert_eq!(contrato.obtener_cantidad_productos(), 0);
+        }
+
+        #[ink::test]
+        fn publicar_producto_descripcion_y_categoria_largas_fallan() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            let descripcion = contrato.publicar_producto("P".to_string(), "d".repeat(513), 100, 1, "C".to_string());
+            assert_eq!(descripcion, Err(SistemaError::DescripcionDemasiadoLarga));
+
+            // El límite se mide en bytes: 17 caracteres de 2 bytes superan los 32
+            let categoria = contrato.publicar_producto("P".to_string(), "D".to_string(), 100, 1, "ñ".repeat(17));
+            assert_eq!(categoria, Err(SistemaError::CategoriaDemasiadoLarga));
+        }
+
+        #[ink::test]
+        fn publicar_producto_recorta_espacios() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            contrato.publicar_producto("  Celular ".to_string(), " Nuevo\n".to_string(), 100, 1, " Tecnología ".to_string()).unwrap();
+
+            let producto = contrato.productos.get(0).unwrap();
+            assert_eq!(producto.nombre, "Celular");
+            assert_eq!(producto.descripcion, "Nuevo");
+            assert_eq!(producto.categoria, "Tecnología");
+        }
+
+        #[ink::test]
+        fn configurar_limites_texto_solo_administrador() {
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.alice);
+            let mut contrato = Marketplace::nuevo();
+            assert_eq!(contrato.obtener_administrador(), cuentas.alice);
+
+            let limites = LimitesTexto { max_nombre: 4, max_descripcion: 10, max_categoria: 5 };
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.bob);
+            assert_eq!(contrato.configurar_limites_texto(limites.clone()), Err(SistemaError::NoEsAdministrador));
+
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.alice);
+            let invalidos = LimitesTexto { max_nombre: 0, ..limites.clone() };
+            assert_eq!(contrato.configurar_limites_texto(invalidos), Err(SistemaError::ConfiguracionInvalida));
+            contrato.configurar_limites_texto(limites.clone()).unwrap();
+            assert_eq!(contrato.obtener_limites_texto(), limites);
+
+            contrato.registrar_usuario(RolUsuario::Vendedor).unwrap();
+            let resultado = contrato.publicar_producto("Celular".to_string(), "D".to_string(), 100, 1, "C".to_string());
+            assert_eq!(resultado, Err(SistemaError::NombreInvalido));
+            assert!(contrato.publicar_producto("Cel".to_string(), "D".to_string(), 100, 1, "C".to_string()).is_ok());
+        }
+
+        // --- Listar productos ---
+         #[ink::test]
+        fn listar_interno_ok_para_vendedor() {
+            let mut c = setup_contract_con_vendedor();
+
+            // El caller ya está registrado como Vendedor por el helper
+            c.publicar_producto("P1".into(), "D".into(), 100, 5, "Cat".into()).unwrap();
+            c.publicar_producto("P2".into(), "D".into(), 200, 3, "Cat".into()).unwrap();
+
+            let caller = ink::env::caller::<ink::env::DefaultEnvironment>();
+            let v = c.listar_productos_interno(caller).unwrap();
+            assert_eq!(v.len(), 2); // Debe devolver exactamente 2 productos del seller
+            assert!(v.iter().all(|p| p.vendedor == caller)); //"Todos los productos deben pertenecer al seller
+        }
+
+        /// Error: usuario no registrado intenta listar.
+        #[ink::test]
+        fn listar_interno_falla_si_no_registrado() {
+            let c = Marketplace::nuevo();
+            let no_reg = AccountId::from([9u8; 32]);
+
+            let res = c.listar_productos_interno(no_reg);
+            assert!(matches!(res, Err(SistemaError::UsuarioNoRegistrado)));
+        }
+
+        /// Error: registrado como Comprador (no Vendedor/Ambos) intenta listar.
+        #[ink::test]
+        fn listar_interno_falla_si_no_es_vendedor() {
+            let mut c = Marketplace::nuevo();
+
+            let comprador = AccountId::from([2u8; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(comprador);
+            c.registrar_usuario(RolUsuario::Comprador).unwrap();
+
+            let res = c.listar_productos_interno(comprador);
+            assert!(matches!(res, Err(SistemaError::NoEsRolCorrecto)));
+        }
+
+        /// Error: vendedor válido pero sin productos publicados.
+        #[ink::test]
+        fn listar_interno_falla_si_no_tiene_productos() {
+            let c = setup_contract_con_vendedor();
+
+            let caller = ink::env::caller::<ink::env::DefaultEnvironment>();
+            let res = c.listar_productos_interno(caller);
+            assert!(matches!(res, Err(SistemaError::ProductosVacios)));
+        }
+
+
+        // --- Compra y órdenes ---
+        #[ink::test]
+        fn crear_orden_ok() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Publica un producto y obtiene el ID
+            let _ = contrato.publicar_producto(
+                "Laptop".to_string(),
+                "Una laptop potente".to_string(),
+                2000,
+                10,
+                "Tecnología".to_string(),
+            );
+
+            // Cambia el caller a un usuario comprador y regístralo
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // El producto publicado tendrá id = 0 (si es el primero)
+            let resultado = contrato.crear_orden(0, None, 2);
+
+            assert!(resultado.is_ok());
+            let orden_id = resultado.unwrap();
+            assert_eq!(contrato.obtener_cantidad_ordenes(), 1);
+
+            let orden = contrato.ordenes.get(0).unwrap();
+            assert_eq!(orden.id, orden_id);
+            assert_eq!(orden.cantidad, 2);
+            assert_eq!(orden.estado, EstadoOrden::Pendiente);
+        }
+
+        #[ink::test]
+        fn crear_orden_no_registrado_falla() {
+            let mut contrato = Marketplace::nuevo();
+
+            let caller = AccountId::from([0x04; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(caller);
+
+            let resultado = contrato.crear_orden(0, None, 1);
+
+            assert!(matches!(resultado, Err(SistemaError::UsuarioNoRegistrado)));
+        }
+
+        #[ink::test]
+        fn verificar_registro_antes_de_crear_orden() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Publica un producto
+            let _ = contrato.publicar_producto(
+                "Producto Test".to_string(),
+                "Descripción Test".to_string(),
+                1000,
+                10,
+                "Test".to_string(),
+            );
+
+            // Cambia el caller a un usuario NO registrado
+            let nuevo_usuario = AccountId::from([0x99; 32]);
+            // El comentario original decía "Cambia el caller...", mantenemos el estilo
+            test::set_caller::<ink::env::DefaultEnvironment>(nuevo_usuario);
+
+            // Verifica que el usuario no está registrado
+            assert!(!contrato.esta_registrado(nuevo_usuario));
+            assert!(contrato.obtener_usuario(nuevo_usuario).is_none());
+
+            // Intenta crear una orden y falla porque no está registrado
+            let resultado = contrato.crear_orden(0, None, 1);
+            assert!(matches!(resultado, Err(SistemaError::UsuarioNoRegistrado)));
+
+            // Registra al usuario como comprador
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Verifica que ahora está registrado
+            assert!(contrato.esta_registrado(nuevo_usuario));
+            let usuario_info = contrato.obtener_usuario(nuevo_usuario).unwrap();
+            assert_eq!(usuario_info.permisos, Permisos::from(RolUsuario::Comprador));
+
+            // Ahora puede crear una orden exitosamente
+            let resultado = contrato.crear_orden(0, None, 1);
+            assert!(resultado.is_ok());
+        }
+
+        #[ink::test]
+        fn crear_orden_no_es_comprador_falla() {
+            let mut contrato = Marketplace::nuevo();
+
+            let caller = AccountId::from([0x05; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(caller);
+
+            let usuario = Usuario {
                 direccion: caller,
-                rol: RolUsuario::Vendedor,
+                permisos: Permisos::from(RolUsuario::Vendedor), // Rol no válido para crear órdenes
                 reputacion_como_comprador: 0,
                 reputacion_como_vendedor: 0,
+                calificaciones_como_comprador: 0,
+                calificaciones_como_vendedor: 0,
+                perfil: PerfilUsuario::default(),
+                verificacion: None,
             };
             contrato.usuarios.insert(caller, &usuario);
-            contrato
+
+            // Primero, publica un producto para poder comprarlo
+            let _ = contrato.publicar_producto(
+                "Tablet".to_string(),
+                "Una tablet versátil".to_string(),
+                1500,
+                7,
+                "Tecnología".to_string(),
+            );
+
+            let resultado = contrato.crear_orden(0, None, 1);
+
+            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+        }
+
+        #[ink::test]
+        fn crear_orden_con_rol_ambos_ok() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Publica un producto
+            let _ = contrato.publicar_producto(
+                "Producto Test".to_string(),
+                "Descripción Test".to_string(),
+                1000,
+                10,
+                "Test".to_string(),
+            );
+
+            // Cambia el caller a un usuario con rol Ambos
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let carlos = cuentas.charlie;
+            test::set_caller::<ink::env::DefaultEnvironment>(carlos);
+            let _ = contrato.registrar_usuario(RolUsuario::Ambos);
+
+            // Debería poder crear una orden exitosamente
+            let resultado = contrato.crear_orden(0, None, 1);
+            assert!(resultado.is_ok());
+        }
+
+        #[ink::test]
+        fn crear_orden_cantidad_insuficiente_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Primero, publica un producto con cantidad insuficiente
+            let _ = contrato.publicar_producto(
+                "Smartwatch".to_string(),
+                "Un smartwatch elegante".to_string(),
+                500,
+                2, // Solo hay 2 disponibles
+                "Tecnología".to_string(),
+            );
+
+            // Cambia el caller a un usuario comprador y regístralo
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let juan = cuentas.bob;
+            test::set_caller::<ink::env::DefaultEnvironment>(juan);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Intenta crear una orden de compra de 3 unidades
+            let resultado = contrato.crear_orden(0, None, 3); // Compra 3 unidades
+
+            assert!(matches!(resultado, Err(SistemaError::StockInsuficiente)));
+        }
+
+        #[ink::test]
+        fn crear_orden_producto_inexistente_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.bob);
+            contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
+
+            let resultado = contrato.crear_orden(7, None, 1);
+            assert_eq!(resultado, Err(SistemaError::ProductoNoExiste));
+        }
+
+        #[ink::test]
+        fn crear_orden_sin_ids_disponibles_no_descuenta_stock() {
+            let mut contrato = setup_contract_con_vendedor();
+            contrato.publicar_producto("P".to_string(), "D".to_string(), 100, 5, "C".to_string()).unwrap();
+
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.bob);
+            contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
+            contrato.next_orden_id = u32::MAX;
+
+            assert_eq!(contrato.crear_orden(0, None, 2), Err(SistemaError::DesbordamientoId));
+            assert_eq!(contrato.productos.get(0).unwrap().cantidad, 5);
+        }
+
+        #[ink::test]
+        fn publicar_producto_sin_ids_disponibles_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+            contrato.next_producto_id = u32::MAX;
+
+            let resultado = contrato.publicar_producto("P".to_string(), "D".to_string(), 100, 5, "C".to_string());
+            assert_eq!(resultado, Err(SistemaError::DesbordamientoId));
+        }
+
+        #[ink::test]
+        fn crear_orden_cantidad_cero_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Publica un producto
+            let _ = contrato.publicar_producto(
+                "Producto Test".to_string(),
+                "Descripción Test".to_string(),
+                1000,
+                10,
+                "Test".to_string(),
+            );
+
+            // Cambia el caller a un usuario comprador y regístralo
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let juan = cuentas.bob;
+            test::set_caller::<ink::env::DefaultEnvironment>(juan);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Intenta crear una orden con cantidad 0
+            let resultado = contrato.crear_orden(0, None, 0);
+
+            assert!(matches!(resultado, Err(SistemaError::CantidadInsuficiente)));
+        }
+
+        #[ink::test]
+        fn crear_orden_descuenta_stock() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Primero, publica un producto con cantidad suficiente
+            let _ = contrato.publicar_producto(
+                "Auriculares".to_string(),
+                "Auriculares inalámbricos".to_string(),
+                800,
+                10, // 10 disponibles
+                "Tecnología".to_string(),
+            );
+
+            // Cambia el caller a un usuario comprador y regístralo
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let juan = cuentas.bob;
+            test::set_caller::<ink::env::DefaultEnvironment>(juan);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Crea una orden de compra
+            let resultado = contrato.crear_orden(0, None, 3); // Compra 3 unidades
+
+            assert!(resultado.is_ok());
+            assert_eq!(contrato.obtener_cantidad_ordenes(), 1);
+
+            // Verifica que el stock se haya descontado correctamente
+            let producto = contrato.productos.get(0).unwrap();
+            assert_eq!(producto.cantidad, 7); // Debería quedar 7 después de la compra
         }
+
         
-     
-        #[ink::test]
-        fn registrar_usuario_comprador_ok() {
-            let mut contrato = Marketplace::nuevo();
 
-      
-            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
-            let maria = cuentas.alice;
-            test::set_caller::<ink::env::DefaultEnvironment>(maria);
+        // --- Variantes ---
+        fn variante(talle: &str, precio: Option<Balance>, cantidad: u32) -> NuevaVariante {
+            NuevaVariante { atributos: vec![("talle".to_string(), talle.to_string())], precio, cantidad }
+        }
 
-            // Llamamos a la función registrar_usuario con el rol de comprador
-            let resultado = contrato.registrar_usuario(RolUsuario::Comprador);
+        fn setup_remera_con_talles() -> Marketplace {
+            let mut contrato = setup_contract_con_vendedor();
+            contrato
+                .publicar_producto_con_variantes(
+                    "Remera".to_string(),
+                    "Algodón".to_string(),
+                    100,
+                    "Ropa".to_string(),
+                    vec![variante("S", None, 3), variante("M", Some(150), 2)],
+                )
+                .unwrap();
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.bob);
+            contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
+            contrato
+        }
 
-            // Verificamos que devuelva OK
-            assert_eq!(resultado, Ok(()));
+        #[ink::test]
+        fn crear_orden_con_variante_descuenta_su_stock_y_usa_su_precio() {
+            let mut contrato = setup_remera_con_talles();
+            assert_eq!(contrato.productos.get(0).unwrap().cantidad, 5);
 
-            // Obtenemos el usuario usando la dirección del caller
-            let usuario_registrado = contrato.usuarios.get(&maria);
+            let orden_id = contrato.crear_orden(0, Some(1), 2).unwrap();
 
-            // Confirmamos si se guardó el usuario
-            assert!(usuario_registrado.is_some());
+            let orden = contrato.ordenes.get(orden_id).unwrap();
+            assert_eq!(orden.variante_id, Some(1));
+            assert_eq!(orden.total, 300);
+            let producto = contrato.productos.get(0).unwrap();
+            assert_eq!(producto.variantes[1].cantidad, 0);
+            assert_eq!(producto.variantes[0].cantidad, 3);
+            assert_eq!(producto.cantidad, 3);
 
-            // Verificamos los datos
-            let usuario = usuario_registrado.unwrap();
-            assert_eq!(usuario.rol, RolUsuario::Comprador);
-            assert_eq!(usuario.reputacion_como_comprador, 0);
-            assert_eq!(usuario.reputacion_como_vendedor, 0);
+            // Talle S usa el precio base
+            let orden_s = contrato.crear_orden(0, Some(0), 1).unwrap();
+            assert_eq!(contrato.ordenes.get(orden_s).unwrap().total, 100);
         }
 
         #[ink::test]
-        fn registrar_usuario_vendedor_ok() {
-            let mut contrato = Marketplace::nuevo();
-            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
-            let juan = cuentas.bob;
-            test::set_caller::<ink::env::DefaultEnvironment>(juan);
-
-            // Llamamos a la función registrar_usuario con el rol de vendedor
-            let resultado = contrato.registrar_usuario(RolUsuario::Vendedor);
+        fn crear_orden_variante_invalida_falla() {
+            let mut contrato = setup_remera_con_talles();
 
-            // Verificamos que devuelva OK
-            assert_eq!(resultado, Ok(()));
+            assert_eq!(contrato.crear_orden(0, None, 1), Err(SistemaError::VarianteRequerida));
+            assert_eq!(contrato.crear_orden(0, Some(5), 1), Err(SistemaError::VarianteNoExiste));
+            assert_eq!(contrato.crear_orden(0, Some(1), 3), Err(SistemaError::StockInsuficiente));
+        }
 
-            // Obtenemos el usuario usando la dirección del caller
-            let usuario_registrado = contrato.usuarios.get(&juan);
+        #[ink::test]
+        fn crear_orden_con_variante_en_producto_simple_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+            contrato.publicar_producto("P".to_string(), "D".to_string(), 100, 5, "C".to_string()).unwrap();
 
-            // Confirmamos si se guardó el usuario
-            assert!(usuario_registrado.is_some());
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.bob);
+            contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
 
-            // Verificamos los datos
-            let usuario = usuario_registrado.unwrap();
-            assert_eq!(usuario.rol, RolUsuario::Vendedor);
-            assert_eq!(usuario.reputacion_como_comprador, 0);
-            assert_eq!(usuario.reputacion_como_vendedor, 0);
+            assert_eq!(contrato.crear_orden(0, Some(0), 1), Err(SistemaError::VarianteNoExiste));
         }
 
         #[ink::test]
-        fn registrar_usuario_ambos_ok() {
-            let mut contrato = Marketplace::nuevo();
+        fn cancelacion_devuelve_stock_a_la_variante() {
+            let mut contrato = setup_remera_con_talles();
+            let orden_id = contrato.crear_orden(0, Some(0), 2).unwrap();
+            contrato.solicitar_cancelacion_orden(orden_id).unwrap();
 
-            // Simulamos que el caller es "Carlos"
-            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
-            let carlos = cuentas.charlie;
-            test::set_caller::<ink::env::DefaultEnvironment>(carlos);
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            contrato.solicitar_cancelacion_orden(orden_id).unwrap();
 
-            // Llamamos a la función registrar_usuario con el rol de ambos
-            let resultado = contrato.registrar_usuario(RolUsuario::Ambos);
+            let producto = contrato.productos.get(0).unwrap();
+            assert_eq!(producto.variantes[0].cantidad, 3);
+            assert_eq!(producto.variantes[1].cantidad, 2);
+            assert_eq!(producto.cantidad, 5);
+        }
 
-            // Verificamos que devuelva OK
-            assert_eq!(resultado, Ok(()));
+        #[ink::test]
+        fn agregar_variante_valida_dueno_y_atributos() {
+            let mut contrato = setup_remera_con_talles();
 
-            // Obtenemos el usuario usando la dirección del caller
-            let usuario_registrado = contrato.usuarios.get(&carlos);
+            // El comprador no es dueño del producto
+            assert_eq!(contrato.agregar_variante(0, variante("L", None, 1)), Err(SistemaError::NoEsVendedorDelProducto));
 
-            // Confirmamos si se guardó el usuario
-            assert!(usuario_registrado.is_some());
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            assert_eq!(contrato.agregar_variante(0, variante(" S ", None, 1)), Err(SistemaError::AtributosInvalidos));
+            assert_eq!(contrato.agregar_variante(0, variante("L", Some(0), 1)), Err(SistemaError::PrecioInvalido));
+            let sin_atributos = NuevaVariante { atributos: Vec::new(), precio: None, cantidad: 1 };
+            assert_eq!(contrato.agregar_variante(0, sin_atributos), Err(SistemaError::AtributosInvalidos));
 
-            // Verificamos los datos
-            let usuario = usuario_registrado.unwrap();
-            assert_eq!(usuario.rol, RolUsuario::Ambos);
-            assert_eq!(usuario.reputacion_como_comprador, 0);
-            assert_eq!(usuario.reputacion_como_vendedor, 0);
+            assert_eq!(contrato.agregar_variante(0, variante("L", None, 4)), Ok(2));
+            assert_eq!(contrato.productos.get(0).unwrap().cantidad, 9);
+
+            contrato.publicar_producto("Gorra".to_string(), "D".to_string(), 50, 1, "Ropa".to_string()).unwrap();
+            assert_eq!(contrato.agregar_variante(1, variante("L", None, 1)), Err(SistemaError::VarianteRequerida));
         }
 
         #[ink::test]
-        fn registrar_usuario_duplicado_falla() {
-            let mut contrato = Marketplace::nuevo();
+        fn publicar_producto_con_variantes_sin_stock_falla() {
+            let mut contrato = setup_contract_con_vendedor();
 
-            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
-            test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            let resultado = contrato.publicar_producto_con_variantes(
+                "Remera".to_string(), "D".to_string(), 100, "Ropa".to_string(),
+                vec![variante("S", None, 0)],
+            );
+            assert_eq!(resultado, Err(SistemaError::CantidadInsuficiente));
 
-            // Primer registro
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+            let vacio = contrato.publicar_producto_con_variantes(
+                "Remera".to_string(), "D".to_string(), 100, "Ropa".to_string(), Vec::new(),
+            );
+            assert_eq!(vacio, Err(SistemaError::VarianteRequerida));
+        }
 
-            // Segundo registro debería fallar porque ya está registrado
-            let resultado = contrato.registrar_usuario(RolUsuario::Vendedor);
-            assert_eq!(resultado, Err(SistemaError::UsuarioExistente));
+        // --- Publicación en lote ---
+        fn nuevo_producto(nombre: &str, precio: Balance, cantidad: u32) -> NuevoProducto {
+            NuevoProducto {
+                nombre: nombre.to_string(),
+                descripcion: "D".to_string(),
+                precio,
+                cantidad,
+                categoria: "C".to_string(),
+            }
         }
 
-        // --- Modificación de roles ---
         #[ink::test]
-        fn modificar_rol_usuario_comprador_a_vendedor_ok() {
+        fn publicar_productos_lote_ok() {
             let mut contrato = setup_contract_con_vendedor();
+            contrato.publicar_producto("Previo".to_string(), "D".to_string(), 1, 1, "C".to_string()).unwrap();
+            let eventos_antes = test::recorded_events().count();
 
-            // Cambia el caller a un usuario registrado como Comprador
-            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
-            let maria = cuentas.alice;
-            test::set_caller::<ink::env::DefaultEnvironment>(maria);
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+            let ids = contrato
+                .publicar_productos_lote(vec![nuevo_producto(" A ", 10, 1), nuevo_producto("B", 20, 2), nuevo_producto("C", 30, 3)])
+                .unwrap();
 
-            // Modifica el rol a Vendedor
-            let resultado = contrato.modificar_rol_usuario(RolUsuario::Vendedor);
-            assert!(resultado.is_ok());
+            assert_eq!(ids, vec![1, 2, 3]);
+            assert_eq!(contrato.productos.get(1).unwrap().nombre, "A");
+            assert_eq!(contrato.productos.get(3).unwrap().cantidad, 3);
+            assert_eq!(test::recorded_events().count() - eventos_antes, 3);
+        }
 
-            // Verifica que el rol se haya actualizado correctamente
-            let usuario = contrato.obtener_usuario(maria).unwrap();
-            assert_eq!(usuario.rol, RolUsuario::Vendedor);
+        #[ink::test]
+        fn publicar_productos_lote_es_todo_o_nada() {
+            let mut contrato = setup_contract_con_vendedor();
+            let resultado = contrato.publicar_productos_lote(vec![nuevo_producto("A", 10, 1), nuevo_producto("B", 0, 1)]);
+            assert_eq!(resultado, Err(SistemaError::PrecioInvalido));
+            assert_eq!(contrato.obtener_cantidad_productos(), 0);
+            assert_eq!(test::recorded_events().count(), 0);
+
+            assert_eq!(contrato.publicar_productos_lote(Vec::new()), Err(SistemaError::TamanoLoteInvalido));
+            let grande = (0..=MAX_LOTE_PRODUCTOS).map(|_| nuevo_producto("X", 1, 1)).collect();
+            assert_eq!(contrato.publicar_productos_lote(grande), Err(SistemaError::TamanoLoteInvalido));
+
+            contrato.next_producto_id = u32::MAX - 1;
+            let resultado = contrato.publicar_productos_lote(vec![nuevo_producto("A", 1, 1), nuevo_producto("B", 1, 1)]);
+            assert_eq!(resultado, Err(SistemaError::DesbordamientoId));
+            assert!(contrato.productos.get(u32::MAX - 1).is_none());
+        }
+
+        // --- Medios (CIDs) ---
+        fn cid_v0(semilla: u8) -> Cid {
+            let mut cid = vec![0x12, 0x20];
+            cid.extend_from_slice(&[semilla; 32]);
+            cid
         }
 
         #[ink::test]
-        fn modificar_rol_usuario_vendedor_a_comprador_ok() {
+        fn cid_valido_acepta_v0_y_v1() {
+            assert!(cid_valido(&cid_v0(1)));
+
+            // CIDv1 raw (0x55) + sha2-256 y dag-pb (0x70) + blake3
+            let mut v1 = vec![0x01, 0x55, 0x12, 0x20];
+            v1.extend_from_slice(&[7; 32]);
+            assert!(cid_valido(&v1));
+            let mut v1_blake3 = vec![0x01, 0x70, 0x1e, 0x20];
+            v1_blake3.extend_from_slice(&[7; 32]);
+            assert!(cid_valido(&v1_blake3));
+
+            // Digest corto, versión desconocida, texto plano
+            assert!(!cid_valido(&cid_v0(1)[..33]));
+            let mut v2 = v1.clone();
+            v2[0] = 0x02;
+            assert!(!cid_valido(&v2));
+            assert!(!cid_valido(b"QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbdG"));
+            assert!(!cid_valido(&[]));
+        }
+
+        #[ink::test]
+        fn agregar_eliminar_y_reordenar_medios() {
             let mut contrato = setup_contract_con_vendedor();
+            contrato.publicar_producto("P".to_string(), "D".to_string(), 100, 5, "C".to_string()).unwrap();
 
-            // Cambia el caller a un usuario registrado como Vendedor
-            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
-            let juan = cuentas.bob;
-            test::set_caller::<ink::env::DefaultEnvironment>(juan);
-            let _ = contrato.registrar_usuario(RolUsuario::Vendedor);
+            for semilla in 1..=3 {
+                contrato.agregar_medio(0, cid_v0(semilla)).unwrap();
+            }
+            assert_eq!(contrato.agregar_medio(0, cid_v0(2)), Err(SistemaError::MedioDuplicado));
+            assert_eq!(contrato.agregar_medio(0, vec![0x12, 0x20]), Err(SistemaError::CidInvalido));
 
-            // Modifica el rol a Comprador
-            let resultado = contrato.modificar_rol_usuario(RolUsuario::Comprador);
-            assert!(resultado.is_ok());
+            contrato.reordenar_medios(0, vec![2, 0, 1]).unwrap();
+            assert_eq!(contrato.obtener_medios_producto(0), vec![cid_v0(3), cid_v0(1), cid_v0(2)]);
+            assert_eq!(contrato.reordenar_medios(0, vec![0, 0, 1]), Err(SistemaError::ReordenamientoInvalido));
+            assert_eq!(contrato.reordenar_medios(0, vec![0, 1]), Err(SistemaError::ReordenamientoInvalido));
+            assert_eq!(contrato.reordenar_medios(0, vec![0, 1, 3]), Err(SistemaError::ReordenamientoInvalido));
 
-            // Verifica que el rol se haya actualizado correctamente
-            let usuario = contrato.obtener_usuario(juan).unwrap();
-            assert_eq!(usuario.rol, RolUsuario::Comprador);
+            contrato.eliminar_medio(0, cid_v0(1)).unwrap();
+            assert_eq!(contrato.obtener_medios_producto(0), vec![cid_v0(3), cid_v0(2)]);
+            assert_eq!(contrato.eliminar_medio(0, cid_v0(1)), Err(SistemaError::MedioNoExiste));
+
+            // Alta, reorden válido y baja emiten evento
+            assert!(test::recorded_events().count() >= 6);
         }
 
         #[ink::test]
-        fn modificar_rol_usuario_ambos_a_comprador_ok() {
+        fn medios_respetan_limite_y_dueno() {
             let mut contrato = setup_contract_con_vendedor();
+            contrato.publicar_producto("P".to_string(), "D".to_string(), 100, 5, "C".to_string()).unwrap();
+
+            for semilla in 0..MAX_MEDIOS_POR_PRODUCTO as u8 {
+                contrato.agregar_medio(0, cid_v0(semilla)).unwrap();
+            }
+            assert_eq!(contrato.agregar_medio(0, cid_v0(0xFF)), Err(SistemaError::LimiteMediosAlcanzado));
 
-            // Cambia el caller a un usuario registrado como Ambos
             let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
-            let carlos = cuentas.charlie;
-            test::set_caller::<ink::env::DefaultEnvironment>(carlos);
-            let _ = contrato.registrar_usuario(RolUsuario::Ambos);
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.bob);
+            assert_eq!(contrato.eliminar_medio(0, cid_v0(0)), Err(SistemaError::NoEsVendedorDelProducto));
+            assert_eq!(contrato.agregar_medio(9, cid_v0(0)), Err(SistemaError::ProductoNoExiste));
+        }
 
-            // Modifica el rol a Comprador
-            let resultado = contrato.modificar_rol_usuario(RolUsuario::Comprador);
-            assert!(resultado.is_ok());
+        // --- Reseñas ---
+        /// Publica un producto, lo compra bob y lo recibe. Deja a bob como caller.
+        fn setup_orden_recibida() -> (Marketplace, u32) {
+            let mut contrato = setup_contract_con_vendedor();
+            contrato.publicar_producto("P".to_string(), "D".to_string(), 100, 5, "C".to_string()).unwrap();
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.bob);
+            contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
+            let orden_id = contrato.crear_orden(0, None, 1).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            contrato.marcar_orden_como_enviada(orden_id).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.bob);
+            contrato.marcar_como_recibida(orden_id).unwrap();
+            (contrato, orden_id)
+        }
 
-            // Verifica que el rol se haya actualizado correctamente
-            let usuario = contrato.obtener_usuario(carlos).unwrap();
-            assert_eq!(usuario.rol, RolUsuario::Comprador);
+        const SAL_TEST: [u8; 32] = [0x5a; 32];
+
+        /// Compromete la calificación de quien llama, sin comentario y con `SAL_TEST`.
+        fn comprometer(contrato: &mut Marketplace, orden_id: u32, calificacion: u32) -> Result<(), SistemaError> {
+            let caller = ink::env::caller::<ink::env::DefaultEnvironment>();
+            contrato.comprometer_calificacion(orden_id, hash_calificacion(orden_id, caller, calificacion, &None, &SAL_TEST))
         }
 
-        #[ink::test]
-        fn emite_evento_rol_actualizado() {
-            let mut c = Marketplace::nuevo();
-            let cuentas = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
-            let maria = cuentas.alice;
-            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(maria);
+        fn revelar(contrato: &mut Marketplace, orden_id: u32, calificacion: u32) -> Result<(), SistemaError> {
+            contrato.revelar_calificacion(orden_id, calificacion, None, SAL_TEST)
+        }
 
-            c.registrar_usuario(RolUsuario::Comprador).unwrap();
+        fn cerrar_plazo_compromiso(contrato: &Marketplace) {
+            for _ in 0..=contrato.obtener_plazo_compromiso() {
+                test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+        }
 
-            // Grabamos eventos durante la llamada que cambia el rol
-            c.modificar_rol_usuario(RolUsuario::Vendedor).unwrap();
-            let eventos = ink::env::test::recorded_events().collect::<Vec<_>>();
-            assert!(!eventos.is_empty(), "Debe emitirse al menos un evento");
+        /// Califica solo desde el lado de quien llama: compromete, deja cerrar el plazo y revela.
+        fn calificar_solo(contrato: &mut Marketplace, orden_id: u32, calificacion: u32) -> Result<(), SistemaError> {
+            comprometer(contrato, orden_id, calificacion)?;
+            cerrar_plazo_compromiso(contrato);
+            revelar(contrato, orden_id, calificacion)
         }
 
+        /// Ambas partes comprometen y luego revelan; deja el caller como estaba.
+        fn calificar_ambos(contrato: &mut Marketplace, orden_id: u32, al_vendedor: u32, al_comprador: u32) {
+            let caller = ink::env::caller::<ink::env::DefaultEnvironment>();
+            let orden = contrato.ordenes.get(orden_id).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(orden.comprador);
+            comprometer(contrato, orden_id, al_vendedor).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(orden.vendedor);
+            comprometer(contrato, orden_id, al_comprador).unwrap();
+            revelar(contrato, orden_id, al_comprador).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(orden.comprador);
+            revelar(contrato, orden_id, al_vendedor).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(caller);
+        }
 
         #[ink::test]
-        fn modificar_rol_usuario_no_registrado_falla() {
-            let mut contrato = Marketplace::nuevo();
+        fn resenar_producto_actualiza_agregado() {
+            let (mut contrato, orden_id) = setup_orden_recibida();
+            contrato.resenar_producto(orden_id, 4, ContenidoResena::Texto("  Muy bueno ".to_string())).unwrap();
+
+            let orden2 = contrato.crear_orden(0, None, 1).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            contrato.marcar_orden_como_enviada(orden2).unwrap();
+            let bob = test::default_accounts::<ink::env::DefaultEnvironment>().bob;
+            test::set_caller::<ink::env::DefaultEnvironment>(bob);
+            contrato.marcar_como_recibida(orden2).unwrap();
+            contrato.resenar_producto(orden2, 1, ContenidoResena::Hash([9; 32])).unwrap();
 
-            // Cambia el caller a un usuario no registrado
-            let caller = AccountId::from([0x05; 32]);
-            test::set_caller::<ink::env::DefaultEnvironment>(caller);
+            let producto = contrato.productos.get(0).unwrap();
+            assert_eq!((producto.suma_puntajes_resenas, producto.cantidad_resenas), (5, 2));
+
+            let resenas = contrato.resenas_producto(0, 0, 10);
+            assert_eq!(resenas.len(), 2);
+            assert_eq!(resenas[0].contenido, ContenidoResena::Texto("Muy bueno".to_string()));
+            assert_eq!(resenas[1].orden_id, orden2);
+            assert_eq!(contrato.resenas_producto(0, 1, 10).len(), 1);
+            assert!(contrato.resenas_producto(0, 5, 10).is_empty());
+        }
 
-            // Intenta modificar el rol
-            let resultado = contrato.modificar_rol_usuario(RolUsuario::Vendedor);
-            assert!(matches!(resultado, Err(SistemaError::UsuarioNoRegistrado)));
+        #[ink::test]
+        fn resenar_producto_una_vez_por_orden() {
+            let (mut contrato, orden_id) = setup_orden_recibida();
+            contrato.resenar_producto(orden_id, 5, ContenidoResena::Hash([1; 32])).unwrap();
+            assert_eq!(
+                contrato.resenar_producto(orden_id, 5, ContenidoResena::Hash([1; 32])),
+                Err(SistemaError::YaResenado)
+            );
         }
 
         #[ink::test]
-        fn modificar_rol_usuario_mismo_rol_falla() {
-            let mut contrato = setup_contract_con_vendedor();
+        fn resenar_producto_valida_entrada_y_estado() {
+            let (mut contrato, orden_id) = setup_orden_recibida();
+            let hash = ContenidoResena::Hash([0; 32]);
+            assert_eq!(contrato.resenar_producto(orden_id, 6, hash.clone()), Err(SistemaError::CalificacionInvalida));
+            assert_eq!(
+                contrato.resenar_producto(orden_id, 3, ContenidoResena::Texto("   ".to_string())),
+                Err(SistemaError::ResenaInvalida)
+            );
+            assert_eq!(
+                contrato.resenar_producto(orden_id, 3, ContenidoResena::Texto("x".repeat(281))),
+                Err(SistemaError::ResenaInvalida)
+            );
 
-            // Cambia el caller a un usuario registrado como Comprador
-            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
-            let maria = cuentas.alice;
-            test::set_caller::<ink::env::DefaultEnvironment>(maria);
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+            // Orden pendiente
+            let pendiente = contrato.crear_orden(0, None, 1).unwrap();
+            assert_eq!(contrato.resenar_producto(pendiente, 3, hash.clone()), Err(SistemaError::EstadoInvalido));
 
-            // Intenta cambiar a Comprador nuevamente
-            let resultado = contrato.modificar_rol_usuario(RolUsuario::Comprador);
-            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+            // El vendedor no puede reseñar
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            assert_eq!(contrato.resenar_producto(orden_id, 3, hash), Err(SistemaError::NoEsRolCorrecto));
         }
 
+        // --- Descuentos ---
         #[ink::test]
-        fn modificar_rol_usuario_no_puede_cambiar_a_vendedor_falla() {
-            let mut contrato = setup_contract_con_vendedor();
+        fn descuento_rige_solo_en_su_periodo() {
+            let mut contrato = setup_oferta();
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            contrato.programar_descuento(0, 80, 1_000, 2_000, None).unwrap();
+
+            // Antes del inicio: precio de lista
+            assert_eq!(contrato.obtener_producto(0).unwrap().precio_efectivo, 100);
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000);
+            let vista = contrato.obtener_producto(0).unwrap();
+            assert_eq!((vista.precio_lista, vista.precio_efectivo), (100, 80));
+
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().bob);
+            let orden = contrato.crear_orden(0, None, 2).unwrap();
+            assert_eq!(contrato.ordenes.get(orden).unwrap().total, 160);
+
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(2_000);
+            let orden = contrato.crear_orden(0, None, 1).unwrap();
+            assert_eq!(contrato.ordenes.get(orden).unwrap().total, 100);
+        }
 
-            // Cambia el caller a un usuario registrado como Vendedor
-            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
-            let juan = cuentas.bob;
-            test::set_caller::<ink::env::DefaultEnvironment>(juan);
-            let _ = contrato.registrar_usuario(RolUsuario::Vendedor);
+        #[ink::test]
+        fn descuento_con_tope_de_unidades_se_agota() {
+            let mut contrato = setup_oferta();
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            contrato.programar_descuento(0, 50, 0, 10_000, Some(3)).unwrap();
+
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().bob);
+            let orden = contrato.crear_orden(0, None, 2).unwrap();
+            assert_eq!(contrato.ordenes.get(orden).unwrap().total, 100);
+            // Queda 1 unidad con descuento: 50 + 100
+            let orden = contrato.crear_orden(0, None, 2).unwrap();
+            assert_eq!(contrato.ordenes.get(orden).unwrap().total, 150);
+            assert_eq!(contrato.obtener_descuento(0).unwrap().unidades_vendidas, 3);
+            assert_eq!(contrato.obtener_producto(0).unwrap().precio_efectivo, 100);
+        }
 
-            // Intenta cambiar a Vendedor, lo cual no es permitido
-            let resultado = contrato.modificar_rol_usuario(RolUsuario::Vendedor);
-            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+        #[ink::test]
+        fn descuento_no_aplica_a_variantes_con_precio_propio() {
+            let mut contrato = setup_remera_con_talles();
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            contrato.programar_descuento(0, 80, 0, 10_000, None).unwrap();
+
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().bob);
+            // Talle M tiene precio propio (150): se cobra sin descuento
+            let orden_m = contrato.crear_orden(0, Some(1), 1).unwrap();
+            assert_eq!(contrato.ordenes.get(orden_m).unwrap().total, 150);
+            assert_eq!(contrato.obtener_descuento(0).unwrap().unidades_vendidas, 0);
+            // Talle S usa el precio base (100): rige el descuento
+            let orden_s = contrato.crear_orden(0, Some(0), 1).unwrap();
+            assert_eq!(contrato.ordenes.get(orden_s).unwrap().total, 80);
+            assert_eq!(contrato.obtener_descuento(0).unwrap().unidades_vendidas, 1);
         }
 
         #[ink::test]
-        fn modificar_rol_usuario_no_puede_cambiar_a_comprador_falla() {
-            let mut contrato = setup_contract_con_vendedor();
+        fn descuento_notifica_inicio_y_fin_una_sola_vez() {
+            let mut contrato = setup_oferta();
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            contrato.programar_descuento(0, 80, 1_000, 2_000, None).unwrap();
+            let eventos = || test::recorded_events().count();
+
+            // Antes del inicio no hay nada que notificar
+            let antes = eventos();
+            contrato.sincronizar_descuento(0).unwrap();
+            assert_eq!(eventos(), antes);
+            assert!(!contrato.descuento_vigente(0));
+
+            // Al pasar `inicio` se emite `DescuentoIniciado` una vez
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000);
+            assert!(contrato.descuento_vigente(0));
+            contrato.sincronizar_descuento(0).unwrap();
+            contrato.sincronizar_descuento(0).unwrap();
+            assert_eq!(eventos(), antes + 1);
+            assert!(contrato.obtener_descuento(0).unwrap().inicio_notificado);
+
+            // La primera orden después de `fin` emite `DescuentoFinalizado`
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(2_000);
+            assert!(!contrato.descuento_vigente(0));
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().bob);
+            contrato.crear_orden(0, None, 1).unwrap();
+            assert!(contrato.obtener_descuento(0).unwrap().fin_notificado);
+            let despues = eventos();
+            contrato.sincronizar_descuento(0).unwrap();
+            assert_eq!(eventos(), despues);
+            assert_eq!(contrato.sincronizar_descuento(1), Err(SistemaError::DescuentoNoExiste));
+        }
 
-            // Cambia el caller a un usuario registrado como Comprador
-            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
-            let maria = cuentas.alice;
-            test::set_caller::<ink::env::DefaultEnvironment>(maria);
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+        #[ink::test]
+        fn programar_descuento_valida_parametros() {
+            let mut contrato = setup_oferta();
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(5_000);
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            assert_eq!(contrato.programar_descuento(0, 100, 6_000, 7_000, None), Err(SistemaError::PrecioInvalido));
+            assert_eq!(contrato.programar_descuento(0, 90, 7_000, 7_000, None), Err(SistemaError::PeriodoInvalido));
+            assert_eq!(contrato.programar_descuento(0, 90, 1_000, 5_000, None), Err(SistemaError::PeriodoInvalido));
+            assert_eq!(contrato.programar_descuento(0, 90, 6_000, 7_000, Some(0)), Err(SistemaError::CantidadInsuficiente));
+            assert_eq!(contrato.cancelar_descuento(0), Err(SistemaError::DescuentoNoExiste));
+
+            contrato.programar_descuento(0, 90, 6_000, 7_000, None).unwrap();
+            contrato.cancelar_descuento(0).unwrap();
+            assert!(contrato.obtener_descuento(0).is_none());
+
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().bob);
+            assert_eq!(contrato.programar_descuento(0, 90, 6_000, 7_000, None), Err(SistemaError::NoEsVendedorDelProducto));
+        }
 
-            // Intenta cambiar a Comprador, lo cual no es permitido
-            let resultado = contrato.modificar_rol_usuario(RolUsuario::Comprador);
-            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
-        }   
+        // --- Tramos de precio ---
+        fn tramo(cantidad_minima: u32, precio: Balance) -> TramoPrecio {
+            TramoPrecio { cantidad_minima, precio }
+        }
 
-        // --- Publicación de productos ---
         #[ink::test]
-        fn publicar_producto_ok() {
+        fn crear_orden_usa_el_tramo_alcanzado() {
             let mut contrato = setup_contract_con_vendedor();
+            contrato.publicar_producto("Tornillo".to_string(), "D".to_string(), 100, 500, "C".to_string()).unwrap();
+            contrato.configurar_tramos_precio(0, vec![tramo(10, 90), tramo(100, 80)]).unwrap();
 
-            let resultado = contrato.publicar_producto(
-                "Celular".to_string(),
-                "Un buen celular".to_string(),
-                1000,
-                5,
-                "Tecnología".to_string(),
-            );
-
-            assert!(resultado.is_ok());
-            assert_eq!(contrato.obtener_cantidad_productos(), 1);
-
-            let producto = contrato.productos.get(0).unwrap();
-            assert_eq!(producto.nombre, "Celular");
-            assert_eq!(producto.precio, 1000);
+            let bob = test::default_accounts::<ink::env::DefaultEnvironment>().bob;
+            test::set_caller::<ink::env::DefaultEnvironment>(bob);
+            contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
+            let totales: Vec<Balance> = [9, 10, 99, 100]
+                .iter()
+                .map(|&c| {
+                    let id = contrato.crear_orden(0, None, c).unwrap();
+                    contrato.ordenes.get(id).unwrap().total
+                })
+                .collect();
+            assert_eq!(totales, vec![900, 900, 8_910, 8_000]);
+
+            // Con descuento más barato que el tramo, gana el descuento
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            contrato.programar_descuento(0, 85, 0, 10_000, None).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(bob);
+            let id = contrato.crear_orden(0, None, 100).unwrap();
+            assert_eq!(contrato.ordenes.get(id).unwrap().total, 8_000);
+            let id = contrato.crear_orden(0, None, 10).unwrap();
+            assert_eq!(contrato.ordenes.get(id).unwrap().total, 850);
         }
 
         #[ink::test]
-        fn publicar_producto_no_registrado_falla() {
-            let mut contrato = Marketplace::nuevo();
-
-            let caller = AccountId::from([0x02; 32]);
-            test::set_caller::<ink::env::DefaultEnvironment>(caller);
+        fn configurar_tramos_rechaza_no_monotonos() {
+            let mut contrato = setup_contract_con_vendedor();
+            contrato.publicar_producto("P".to_string(), "D".to_string(), 100, 5, "C".to_string()).unwrap();
 
-            let resultado = contrato.publicar_producto(
-                "Producto".to_string(),
-                "Sin registro".to_string(),
-                500,
-                1,
-                "Otros".to_string(),
-            );
+            for invalidos in [
+                vec![tramo(1, 90)],
+                vec![tramo(10, 100)],
+                vec![tramo(10, 90), tramo(10, 80)],
+                vec![tramo(10, 90), tramo(20, 95)],
+                vec![tramo(10, 0)],
+            ] {
+                assert_eq!(contrato.configurar_tramos_precio(0, invalidos), Err(SistemaError::TramosInvalidos));
+            }
+            let demasiados = (0..=MAX_TRAMOS_PRECIO).map(|i| tramo(i + 2, 99 - Balance::from(i))).collect();
+            assert_eq!(contrato.configurar_tramos_precio(0, demasiados), Err(SistemaError::TramosInvalidos));
 
-            assert!(matches!(resultado, Err(SistemaError::UsuarioNoRegistrado)));
+            contrato.configurar_tramos_precio(0, vec![tramo(2, 95)]).unwrap();
+            assert_eq!(contrato.obtener_tramos_precio(0).len(), 1);
+            contrato.configurar_tramos_precio(0, Vec::new()).unwrap();
+            assert!(contrato.obtener_tramos_precio(0).is_empty());
         }
 
         #[ink::test]
-        fn publicar_producto_no_es_vendedor_falla() {
-            let mut contrato = Marketplace::nuevo();
+        fn tramos_no_aplican_a_variantes_con_precio_propio() {
+            let mut contrato = setup_remera_con_talles();
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            contrato.configurar_tramos_precio(0, vec![tramo(2, 90)]).unwrap();
+
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().bob);
+            let base = contrato.crear_orden(0, Some(0), 2).unwrap();
+            assert_eq!(contrato.ordenes.get(base).unwrap().total, 180);
+            let propio = contrato.crear_orden(0, Some(1), 2).unwrap();
+            assert_eq!(contrato.ordenes.get(propio).unwrap().total, 300);
+        }
 
-            let caller = AccountId::from([0x03; 32]);
-            test::set_caller::<ink::env::DefaultEnvironment>(caller);
+        // --- Cupones ---
+        #[ink::test]
+        fn crear_orden_con_cupon_aplica_y_cuenta_usos() {
+            let mut contrato = setup_oferta();
+            let vendedor = AccountId::from([0x10; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            let hash = hash_codigo_cupon(b"VERANO10");
+            contrato.registrar_cupon(hash, ValorCupon::Porcentaje(10), 1_000, 2, None, None).unwrap();
+            contrato.registrar_cupon(hash_codigo_cupon(b"MENOS30"), ValorCupon::Fijo(30), 1_000, 1, Some(0), None).unwrap();
+            assert_eq!(
+                contrato.registrar_cupon(hash, ValorCupon::Fijo(5), 1_000, 1, None, None),
+                Err(SistemaError::CuponExistente)
+            );
 
-            let usuario = Usuario {
-                direccion: caller,
-                rol: RolUsuario::Comprador, // Rol no válido para publicar productos
-                reputacion_como_comprador: 0,
-                reputacion_como_vendedor: 0,
-            };
-            contrato.usuarios.insert(caller, &usuario);
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().bob);
+            let orden = contrato.crear_orden_con_cupon(0, None, 2, b"VERANO10".to_vec()).unwrap();
+            assert_eq!(contrato.ordenes.get(orden).unwrap().total, 180);
+            let orden = contrato.crear_orden_con_cupon(0, None, 1, b"MENOS30".to_vec()).unwrap();
+            assert_eq!(contrato.ordenes.get(orden).unwrap().total, 70);
 
-            let resultado = contrato.publicar_producto(
-                "Producto".to_string(),
-                "No autorizado".to_string(),
-                100,
-                2,
-                "Otros".to_string(),
+            contrato.crear_orden_con_cupon(0, None, 1, b"VERANO10".to_vec()).unwrap();
+            assert_eq!(contrato.obtener_cupon(vendedor, hash).unwrap().usos, 2);
+            assert_eq!(contrato.crear_orden_con_cupon(0, None, 1, b"VERANO10".to_vec()), Err(SistemaError::CuponAgotado));
+            assert_eq!(contrato.crear_orden_con_cupon(0, None, 1, b"OTRO".to_vec()), Err(SistemaError::CuponNoExiste));
+        }
+
+        #[ink::test]
+        fn cupon_respeta_vencimiento_restricciones_y_atomicidad() {
+            let mut contrato = setup_oferta();
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            contrato.publicar_producto("Otro".to_string(), "D".to_string(), 100, 5, "Hogar".to_string()).unwrap();
+            contrato.registrar_cupon(hash_codigo_cupon(b"HOGAR"), ValorCupon::Porcentaje(50), 1_000, 5, None, Some("Hogar".to_string())).unwrap();
+            assert_eq!(
+                contrato.registrar_cupon(hash_codigo_cupon(b"X"), ValorCupon::Porcentaje(101), 1_000, 1, None, None),
+                Err(SistemaError::CuponInvalido)
             );
 
-            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().bob);
+            assert_eq!(contrato.crear_orden_con_cupon(0, None, 1, b"HOGAR".to_vec()), Err(SistemaError::CuponNoAplica));
+            // Sin stock suficiente no se consume el cupón
+            assert_eq!(contrato.crear_orden_con_cupon(1, None, 6, b"HOGAR".to_vec()), Err(SistemaError::StockInsuficiente));
+            assert_eq!(contrato.obtener_cupon(AccountId::from([0x10; 32]), hash_codigo_cupon(b"HOGAR")).unwrap().usos, 0);
+
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000);
+            assert_eq!(contrato.crear_orden_con_cupon(1, None, 1, b"HOGAR".to_vec()), Err(SistemaError::CuponExpirado));
         }
 
-        #[ink::test]
-        fn publicar_producto_cantidad_cero_falla() {
+        // --- Paquetes ---
+        /// Publica dos productos (ids 0 y 1, 10 unidades c/u) y arma un paquete 2x0 + 1x1.
+        /// Deja a bob (comprador registrado) como caller.
+        fn setup_paquete() -> Marketplace {
             let mut contrato = setup_contract_con_vendedor();
+            contrato.publicar_producto("Mate".to_string(), "D".to_string(), 100, 10, "C".to_string()).unwrap();
+            contrato.publicar_producto("Bombilla".to_string(), "D".to_string(), 50, 10, "C".to_string()).unwrap();
+            contrato.crear_paquete("Kit".to_string(), vec![(0, 2), (1, 1)], 200).unwrap();
+            let bob = test::default_accounts::<ink::env::DefaultEnvironment>().bob;
+            test::set_caller::<ink::env::DefaultEnvironment>(bob);
+            contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
+            contrato
+        }
 
-            let resultado = contrato.publicar_producto(
-                "Producto".to_string(),
-                "Cantidad cero".to_string(),
-                100,
-                0, // Cantidad inválida
-                "Otros".to_string(),
-            );
+        #[ink::test]
+        fn comprar_y_cancelar_paquete_mueve_stock_de_componentes() {
+            let mut contrato = setup_paquete();
+            let orden_id = contrato.comprar_paquete(0, 3).unwrap();
 
-            assert!(matches!(resultado, Err(SistemaError::CantidadInsuficiente)));
+            let orden = contrato.ordenes.get(orden_id).unwrap();
+            assert_eq!((orden.paquete_id, orden.cantidad, orden.total), (Some(0), 3, 600));
+            assert_eq!(orden.producto_id, PRODUCTO_PAQUETE);
+            assert_eq!(contrato.productos.get(0).unwrap().cantidad, 4);
+            assert_eq!(contrato.productos.get(1).unwrap().cantidad, 7);
+
+            contrato.solicitar_cancelacion_orden(orden_id).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            contrato.solicitar_cancelacion_orden(orden_id).unwrap();
+            assert_eq!(contrato.productos.get(0).unwrap().cantidad, 10);
+            assert_eq!(contrato.productos.get(1).unwrap().cantidad, 10);
         }
 
-        // --- Listar productos ---
-         #[ink::test]
-        fn listar_interno_ok_para_vendedor() {
-            let mut c = setup_contract_con_vendedor();
+        #[ink::test]
+        fn resena_de_paquete_se_registra_en_cada_componente() {
+            let mut contrato = setup_paquete();
+            let bob = test::default_accounts::<ink::env::DefaultEnvironment>().bob;
+            let orden_id = contrato.comprar_paquete(0, 1).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            contrato.marcar_orden_como_enviada(orden_id).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(bob);
+            contrato.marcar_como_recibida(orden_id).unwrap();
 
-            // El caller ya está registrado como Vendedor por el helper
-            c.publicar_producto("P1".into(), "D".into(), 100, 5, "Cat".into()).unwrap();
-            c.publicar_producto("P2".into(), "D".into(), 200, 3, "Cat".into()).unwrap();
+            contrato.resenar_producto(orden_id, 4, ContenidoResena::Texto("Completo".to_string())).unwrap();
+            for producto_id in [0, 1] {
+                let producto = contrato.productos.get(producto_id).unwrap();
+                assert_eq!((producto.cantidad_resenas, producto.suma_puntajes_resenas), (1, 4));
+            }
+            assert_eq!(contrato.obtener_todos_los_paquetes().len(), 1);
+        }
 
-            let caller = ink::env::caller::<ink::env::DefaultEnvironment>();
-            let v = c.listar_productos_interno(caller).unwrap();
-            assert_eq!(v.len(), 2); // Debe devolver exactamente 2 productos del seller
-            assert!(v.iter().all(|p| p.vendedor == caller)); //"Todos los productos deben pertenecer al seller
+        #[ink::test]
+        fn paquete_no_disponible_sin_stock_o_despublicado() {
+            let mut contrato = setup_paquete();
+            // 6 paquetes requieren 12 unidades del producto 0
+            assert_eq!(contrato.comprar_paquete(0, 6), Err(SistemaError::PaqueteNoDisponible));
+            assert_eq!(contrato.productos.get(1).unwrap().cantidad, 10);
+            assert_eq!(contrato.obtener_cantidad_ordenes(), 0);
+
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            contrato.despublicar_producto(1).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().bob);
+            assert_eq!(contrato.comprar_paquete(0, 1), Err(SistemaError::PaqueteNoDisponible));
+            assert_eq!(contrato.crear_orden(1, None, 1), Err(SistemaError::ProductoNoDisponible));
+            assert_eq!(contrato.comprar_paquete(7, 1), Err(SistemaError::PaqueteNoExiste));
+
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            contrato.republicar_producto(1).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().bob);
+            assert!(contrato.comprar_paquete(0, 1).is_ok());
         }
 
-        /// Error: usuario no registrado intenta listar.
         #[ink::test]
-        fn listar_interno_falla_si_no_registrado() {
-            let c = Marketplace::nuevo();
-            let no_reg = AccountId::from([9u8; 32]);
+        fn crear_paquete_valida_componentes() {
+            let mut contrato = setup_paquete();
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            for invalidos in [Vec::new(), vec![(0, 1), (0, 2)], vec![(0, 0)], vec![(9, 1)]] {
+                assert_eq!(contrato.crear_paquete("K".to_string(), invalidos, 10), Err(SistemaError::ComponentesInvalidos));
+            }
+            assert_eq!(contrato.crear_paquete("K".to_string(), vec![(0, 1)], 0), Err(SistemaError::PrecioInvalido));
 
-            let res = c.listar_productos_interno(no_reg);
-            assert!(matches!(res, Err(SistemaError::UsuarioNoRegistrado)));
+            // Producto digital propio (id 2)
+            contrato.publicar_producto_digital("Licencia".to_string(), "D".to_string(), 100, 3, "Software".to_string()).unwrap();
+            assert_eq!(contrato.crear_paquete("K".to_string(), vec![(0, 1), (2, 1)], 10), Err(SistemaError::ComponenteDigital));
+
+            // Producto de otro vendedor
+            let otro = AccountId::from([0x20; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(otro);
+            contrato.registrar_usuario(RolUsuario::Vendedor).unwrap();
+            assert_eq!(contrato.crear_paquete("K".to_string(), vec![(0, 1)], 10), Err(SistemaError::ComponentesInvalidos));
+        }
+
+        // --- Preventas ---
+        /// Producto 0 con 2 unidades y preventa de hasta 10; deja a bob (comprador) como caller.
+        fn setup_preventa() -> Marketplace {
+            let mut contrato = setup_contract_con_vendedor();
+            contrato.publicar_producto("Consola".to_string(), "D".to_string(), 100, 2, "C".to_string()).unwrap();
+            contrato.habilitar_preventa(0, 5_000, 10).unwrap();
+            for cuenta in [test::default_accounts::<ink::env::DefaultEnvironment>().charlie, test::default_accounts::<ink::env::DefaultEnvironment>().bob] {
+                test::set_caller::<ink::env::DefaultEnvironment>(cuenta);
+                contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
+            }
+            contrato
         }
 
-        /// Error: registrado como Comprador (no Vendedor/Ambos) intenta listar.
         #[ink::test]
-        fn listar_interno_falla_si_no_es_vendedor() {
-            let mut c = Marketplace::nuevo();
+        fn preventas_se_cumplen_en_orden_de_llegada() {
+            let mut contrato = setup_preventa();
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            // bob toma las 2 unidades y queda debiendo 1
+            let primera = contrato.crear_orden(0, None, 3).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.charlie);
+            let segunda = contrato.crear_orden(0, None, 2).unwrap();
 
-            let comprador = AccountId::from([2u8; 32]);
-            test::set_caller::<ink::env::DefaultEnvironment>(comprador);
-            c.registrar_usuario(RolUsuario::Comprador).unwrap();
+            assert_eq!(contrato.ordenes.get(primera).unwrap().estado, EstadoOrden::Preventa);
+            assert_eq!(contrato.productos.get(0).unwrap().cantidad, 0);
+            let preventa = contrato.obtener_preventa(0).unwrap();
+            assert_eq!((preventa.unidades_comprometidas, preventa.cola), (3, vec![(primera, 1), (segunda, 2)]));
 
-            let res = c.listar_productos_interno(comprador);
-            assert!(matches!(res, Err(SistemaError::NoEsRolCorrecto)));
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            contrato.reponer_stock(0, 2).unwrap();
+            assert_eq!(contrato.ordenes.get(primera).unwrap().estado, EstadoOrden::Pendiente);
+            assert_eq!(contrato.ordenes.get(segunda).unwrap().estado, EstadoOrden::Preventa);
+            assert_eq!(contrato.obtener_preventa(0).unwrap().cola, vec![(segunda, 1)]);
+
+            contrato.reponer_stock(0, 4).unwrap();
+            assert_eq!(contrato.ordenes.get(segunda).unwrap().estado, EstadoOrden::Pendiente);
+            assert_eq!(contrato.productos.get(0).unwrap().cantidad, 3);
+            assert_eq!(contrato.obtener_preventa(0).unwrap().unidades_comprometidas, 0);
+
+            // Una vez cumplida sigue el flujo normal
+            contrato.marcar_orden_como_enviada(primera).unwrap();
         }
 
-        /// Error: vendedor válido pero sin productos publicados.
         #[ink::test]
-        fn listar_interno_falla_si_no_tiene_productos() {
-            let c = setup_contract_con_vendedor();
-
-            let caller = ink::env::caller::<ink::env::DefaultEnvironment>();
-            let res = c.listar_productos_interno(caller);
-            assert!(matches!(res, Err(SistemaError::ProductosVacios)));
+        fn cancelar_preventa_reasigna_unidades() {
+            let mut contrato = setup_preventa();
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let primera = contrato.crear_orden(0, None, 3).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.charlie);
+            let segunda = contrato.crear_orden(0, None, 2).unwrap();
+            assert_eq!(contrato.cancelar_preventa(primera), Err(SistemaError::NoEsParteDeLaOrden));
+
+            // Las 2 unidades que tenía bob pasan a charlie
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.bob);
+            contrato.cancelar_preventa(primera).unwrap();
+            assert_eq!(contrato.ordenes.get(primera).unwrap().estado, EstadoOrden::Cancelada);
+            assert_eq!(contrato.ordenes.get(segunda).unwrap().estado, EstadoOrden::Pendiente);
+            assert_eq!(contrato.obtener_preventa(0).unwrap().unidades_comprometidas, 0);
+            assert_eq!(contrato.cancelar_preventa(primera), Err(SistemaError::EstadoInvalido));
         }
 
+        #[ink::test]
+        fn cancelar_orden_entrega_stock_a_preventas_en_espera() {
+            let mut contrato = setup_preventa();
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.charlie);
+            let normal = contrato.crear_orden(0, None, 2).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.bob);
+            let preventa = contrato.crear_orden(0, None, 1).unwrap();
+            assert_eq!(contrato.ordenes.get(preventa).unwrap().estado, EstadoOrden::Preventa);
+
+            // Aunque hubiera stock, con la cola no vacía la orden nueva no se lo adelanta
+            let mut prod = contrato.productos.get(0).unwrap();
+            prod.cantidad = 1;
+            contrato.productos.insert(0, &prod);
+            let tardia = contrato.crear_orden(0, None, 1).unwrap();
+            assert_eq!(contrato.ordenes.get(tardia).unwrap().estado, EstadoOrden::Preventa);
+            assert_eq!(contrato.productos.get(0).unwrap().cantidad, 1);
+            prod.cantidad = 0;
+            contrato.productos.insert(0, &prod);
+
+            // Las 2 unidades de la orden cancelada cumplen las preventas en orden de llegada
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.charlie);
+            contrato.solicitar_cancelacion_orden(normal).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            contrato.solicitar_cancelacion_orden(normal).unwrap();
+            assert_eq!(contrato.ordenes.get(normal).unwrap().estado, EstadoOrden::Cancelada);
+            assert_eq!(contrato.ordenes.get(preventa).unwrap().estado, EstadoOrden::Pendiente);
+            assert_eq!(contrato.ordenes.get(tardia).unwrap().estado, EstadoOrden::Pendiente);
+            assert_eq!(contrato.productos.get(0).unwrap().cantidad, 0);
+            assert!(contrato.obtener_preventa(0).unwrap().cola.is_empty());
+        }
 
-        // --- Compra y órdenes ---
         #[ink::test]
-        fn crear_orden_ok() {
-            let mut contrato = setup_contract_con_vendedor();
+        fn preventa_respeta_tope_y_habilitacion() {
+            let mut contrato = setup_preventa();
+            assert_eq!(contrato.crear_orden(0, None, 13), Err(SistemaError::LimitePreventaAlcanzado));
+            contrato.crear_orden(0, None, 12).unwrap();
+
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            contrato.deshabilitar_preventa(0).unwrap();
+            contrato.publicar_producto("Sin preventa".to_string(), "D".to_string(), 100, 1, "C".to_string()).unwrap();
+
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().bob);
+            assert_eq!(contrato.crear_orden(0, None, 1), Err(SistemaError::StockInsuficiente));
+            assert_eq!(contrato.crear_orden(1, None, 2), Err(SistemaError::StockInsuficiente));
+        }
 
-            // Publica un producto y obtiene el ID
-            let _ = contrato.publicar_producto(
-                "Laptop".to_string(),
-                "Una laptop potente".to_string(),
-                2000,
-                10,
-                "Tecnología".to_string(),
-            );
+        // --- Vencimiento de publicaciones ---
+        const DIA_MS: Timestamp = 24 * 60 * 60 * 1000;
 
-            // Cambia el caller a un usuario comprador y regístralo
-            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
-            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+        #[ink::test]
+        fn publicacion_vencida_no_se_vende_ni_lista_hasta_renovar() {
+            let mut contrato = setup_oferta();
+            let vendedor = AccountId::from([0x10; 32]);
+            assert_eq!(contrato.productos.get(0).unwrap().expira_en, 30 * DIA_MS);
 
-            // El producto publicado tendrá id = 0 (si es el primero)
-            let resultado = contrato.crear_orden(0, 2);
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(30 * DIA_MS);
+            assert_eq!(contrato.crear_orden(0, None, 1), Err(SistemaError::PublicacionVencida));
+            assert_eq!(contrato.listar_productos_por_vendedor(vendedor), Err(SistemaError::ProductosVacios));
+            assert!(contrato.obtener_productos_vigentes().is_empty());
+            assert_eq!(contrato.obtener_todos_los_productos().len(), 1);
 
-            assert!(resultado.is_ok());
-            let orden_id = resultado.unwrap();
-            assert_eq!(contrato.obtener_cantidad_ordenes(), 1);
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            assert_eq!(contrato.renovar_publicacion(0), Ok(60 * DIA_MS));
+            assert_eq!(contrato.listar_productos_por_vendedor(vendedor).unwrap().len(), 1);
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().bob);
+            assert!(contrato.crear_orden(0, None, 1).is_ok());
+        }
 
-            let orden = contrato.ordenes.get(0).unwrap();
-            assert_eq!(orden.id, orden_id);
-            assert_eq!(orden.cantidad, 2);
-            assert_eq!(orden.estado, EstadoOrden::Pendiente);
+        #[ink::test]
+        fn limpiar_publicaciones_respeta_periodo_de_gracia() {
+            let mut contrato = setup_oferta();
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            contrato.agregar_medio(0, cid_v0(1)).unwrap();
+            contrato.publicar_producto("Nuevo".to_string(), "D".to_string(), 100, 1, "C".to_string()).unwrap();
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(10 * DIA_MS);
+            contrato.renovar_publicacion(1).unwrap();
+
+            // Cualquiera puede limpiar; el producto 0 todavía está en gracia
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().charlie);
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(60 * DIA_MS - 1);
+            assert_eq!(contrato.limpiar_publicaciones_vencidas(vec![0, 1, 7]), Ok(Vec::new()));
+
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(60 * DIA_MS);
+            assert_eq!(contrato.limpiar_publicaciones_vencidas(vec![0, 1]), Ok(vec![0]));
+            assert!(contrato.productos.get(0).is_none());
+            assert!(contrato.obtener_medios_producto(0).is_empty());
+            assert!(contrato.productos.get(1).is_some());
         }
 
         #[ink::test]
-        fn crear_orden_no_registrado_falla() {
-            let mut contrato = Marketplace::nuevo();
+        fn limpiar_publicaciones_conserva_productos_con_ordenes_abiertas() {
+            let mut contrato = setup_paquete();
+            let vendedor = AccountId::from([0x10; 32]);
+            let bob = test::default_accounts::<ink::env::DefaultEnvironment>().bob;
+            let directa = contrato.crear_orden(1, None, 1).unwrap();
+            let paquete = contrato.comprar_paquete(0, 1).unwrap();
 
-            let caller = AccountId::from([0x04; 32]);
-            test::set_caller::<ink::env::DefaultEnvironment>(caller);
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(60 * DIA_MS);
+            assert_eq!(contrato.limpiar_publicaciones_vencidas(vec![0, 1]), Ok(Vec::new()));
 
-            let resultado = contrato.crear_orden(0, 1);
+            // Cerrada la orden directa, el producto 1 sigue en el paquete pendiente
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            contrato.marcar_orden_como_enviada(directa).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(bob);
+            contrato.marcar_como_recibida(directa).unwrap();
+            assert_eq!(contrato.limpiar_publicaciones_vencidas(vec![0, 1]), Ok(Vec::new()));
 
-            assert!(matches!(resultado, Err(SistemaError::UsuarioNoRegistrado)));
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            contrato.marcar_orden_como_enviada(paquete).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(bob);
+            contrato.marcar_como_recibida(paquete).unwrap();
+            assert_eq!(contrato.limpiar_publicaciones_vencidas(vec![0, 1]), Ok(vec![0, 1]));
         }
 
         #[ink::test]
-        fn verificar_registro_antes_de_crear_orden() {
+        fn configurar_duracion_publicacion_solo_administrador() {
             let mut contrato = setup_contract_con_vendedor();
+            let nueva = DuracionPublicacion { vigencia: 1_000, gracia: 0 };
+            assert_eq!(contrato.configurar_duracion_publicacion(nueva.clone()), Err(SistemaError::NoEsAdministrador));
 
-            // Publica un producto
-            let _ = contrato.publicar_producto(
-                "Producto Test".to_string(),
-                "Descripción Test".to_string(),
-                1000,
-                10,
-                "Test".to_string(),
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().alice);
+            assert_eq!(
+                contrato.configurar_duracion_publicacion(DuracionPublicacion { vigencia: 0, gracia: 0 }),
+                Err(SistemaError::ConfiguracionInvalida)
             );
+            contrato.configurar_duracion_publicacion(nueva.clone()).unwrap();
+            assert_eq!(contrato.obtener_duracion_publicacion(), nueva);
 
-            // Cambia el caller a un usuario NO registrado
-            let nuevo_usuario = AccountId::from([0x99; 32]);
-            // El comentario original decía "Cambia el caller...", mantenemos el estilo
-            test::set_caller::<ink::env::DefaultEnvironment>(nuevo_usuario);
-
-            // Verifica que el usuario no está registrado
-            assert!(!contrato.esta_registrado(nuevo_usuario));
-            assert!(contrato.obtener_usuario(nuevo_usuario).is_none());
-
-            // Intenta crear una orden y falla porque no está registrado
-            let resultado = contrato.crear_orden(0, 1);
-            assert!(matches!(resultado, Err(SistemaError::UsuarioNoRegistrado)));
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            contrato.publicar_producto("P".to_string(), "D".to_string(), 100, 1, "C".to_string()).unwrap();
+            assert_eq!(contrato.productos.get(0).unwrap().expira_en, 1_000);
+        }
 
-            // Registra al usuario como comprador
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+        // --- Productos digitales ---
+        /// Producto digital 0 (3 licencias); bob registrado como comprador con clave. Deja a bob como caller.
+        fn setup_digital() -> Marketplace {
+            let mut contrato = setup_contract_con_vendedor();
+            contrato.publicar_producto_digital("Licencia".to_string(), "D".to_string(), 100, 3, "Software".to_string()).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().bob);
+            contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
+            contrato
+        }
 
-            // Verifica que ahora está registrado
-            assert!(contrato.esta_registrado(nuevo_usuario));
-            let usuario_info = contrato.obtener_usuario(nuevo_usuario).unwrap();
-            assert_eq!(usuario_info.rol, RolUsuario::Comprador);
+        #[ink::test]
+        fn entrega_digital_cuenta_como_envio() {
+            let mut contrato = setup_digital();
+            assert_eq!(contrato.crear_orden(0, None, 1), Err(SistemaError::ClavePublicaRequerida));
+            contrato.registrar_clave_publica([7; 32]).unwrap();
+            let orden_id = contrato.crear_orden(0, None, 1).unwrap();
+
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            assert_eq!(contrato.marcar_orden_como_enviada(orden_id), Err(SistemaError::EntregaDigitalRequerida));
+            assert_eq!(contrato.entregar_contenido_digital(orden_id, Vec::new()), Err(SistemaError::ContenidoInvalido));
+            contrato.entregar_contenido_digital(orden_id, vec![1, 2, 3]).unwrap();
+
+            assert_eq!(contrato.ordenes.get(orden_id).unwrap().estado, EstadoOrden::Enviada);
+            let entrega = contrato.obtener_entrega_digital(orden_id).unwrap();
+            assert_eq!((entrega.clave_destino, entrega.contenido_cifrado), ([7; 32], vec![1, 2, 3]));
+
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().bob);
+            contrato.marcar_como_recibida(orden_id).unwrap();
+        }
 
-            // Ahora puede crear una orden exitosamente
-            let resultado = contrato.crear_orden(0, 1);
-            assert!(resultado.is_ok());
+        #[ink::test]
+        fn disputa_digital_la_resuelve_el_administrador() {
+            let mut contrato = setup_digital();
+            contrato.registrar_clave_publica([7; 32]).unwrap();
+            let perdida = contrato.crear_orden(0, None, 1).unwrap();
+            let ganada = contrato.crear_orden(0, None, 1).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            for orden_id in [perdida, ganada] {
+                contrato.entregar_contenido_digital(orden_id, vec![0xAA]).unwrap();
+            }
+            assert_eq!(contrato.abrir_disputa(perdida), Err(SistemaError::NoEsRolCorrecto));
+
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().bob);
+            contrato.abrir_disputa(perdida).unwrap();
+            contrato.abrir_disputa(ganada).unwrap();
+            assert_eq!(contrato.resolver_disputa(perdida, true), Err(SistemaError::NoEsAdministrador));
+
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().alice);
+            contrato.resolver_disputa(perdida, true).unwrap();
+            contrato.resolver_disputa(ganada, false).unwrap();
+            assert_eq!(contrato.ordenes.get(perdida).unwrap().estado, EstadoOrden::Cancelada);
+            assert_eq!(contrato.ordenes.get(ganada).unwrap().estado, EstadoOrden::Recibida);
+            assert_eq!(contrato.resolver_disputa(ganada, true), Err(SistemaError::EstadoInvalido));
         }
 
         #[ink::test]
-        fn crear_orden_no_es_comprador_falla() {
-            let mut contrato = Marketplace::nuevo();
+        fn disputa_solo_para_ordenes_digitales() {
+            let (mut contrato, orden_id) = setup_orden_recibida();
+            assert_eq!(contrato.abrir_disputa(orden_id), Err(SistemaError::EstadoInvalido));
+            let otra = contrato.crear_orden(0, None, 1).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            assert_eq!(contrato.entregar_contenido_digital(otra, vec![1]), Err(SistemaError::NoEsProductoDigital));
+            contrato.marcar_orden_como_enviada(otra).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().bob);
+            assert_eq!(contrato.abrir_disputa(otra), Err(SistemaError::NoEsProductoDigital));
+        }
 
-            let caller = AccountId::from([0x05; 32]);
-            test::set_caller::<ink::env::DefaultEnvironment>(caller);
+        // --- Subastas ---
+        /// Publica una subasta de 3 bloques y registra a bob y charlie como compradores.
+        fn setup_subasta() -> Marketplace {
+            let mut contrato = setup_contract_con_vendedor();
+            contrato.publicar_subasta("Cuadro".to_string(), "Óleo".to_string(), 100, 1, "Arte".to_string(), 3).unwrap();
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            for cuenta in [cuentas.bob, cuentas.charlie] {
+                test::set_caller::<ink::env::DefaultEnvironment>(cuenta);
+                contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
+            }
+            contrato
+        }
 
-            let usuario = Usuario {
-                direccion: caller,
-                rol: RolUsuario::Vendedor, // Rol no válido para crear órdenes
-                reputacion_como_comprador: 0,
-                reputacion_como_vendedor: 0,
-            };
-            contrato.usuarios.insert(caller, &usuario);
+        fn pujar_como(contrato: &mut Marketplace, postor: AccountId, monto: Balance) -> Result<(), SistemaError> {
+            test::set_caller::<ink::env::DefaultEnvironment>(postor);
+            test::set_value_transferred::<ink::env::DefaultEnvironment>(monto);
+            contrato.pujar(0)
+        }
 
-            // Primero, publica un producto para poder comprarlo
-            let _ = contrato.publicar_producto(
-                "Tablet".to_string(),
-                "Una tablet versátil".to_string(),
-                1500,
-                7,
-                "Tecnología".to_string(),
-            );
+        #[ink::test]
+        fn pujar_rechaza_publicacion_inactiva_o_vencida() {
+            let mut contrato = setup_subasta();
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
 
-            let resultado = contrato.crear_orden(0, 1);
+            let mut prod = contrato.productos.get(0).unwrap();
+            prod.activo = false;
+            contrato.productos.insert(0, &prod);
+            assert_eq!(pujar_como(&mut contrato, cuentas.bob, 100), Err(SistemaError::ProductoNoDisponible));
 
-            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+            prod.activo = true;
+            contrato.productos.insert(0, &prod);
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(prod.expira_en);
+            assert_eq!(pujar_como(&mut contrato, cuentas.bob, 100), Err(SistemaError::PublicacionVencida));
         }
 
         #[ink::test]
-        fn crear_orden_con_rol_ambos_ok() {
-            let mut contrato = setup_contract_con_vendedor();
+        fn subasta_completa_crea_orden_y_libera_deposito() {
+            let mut contrato = setup_subasta();
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let vendedor = AccountId::from([0x10; 32]);
 
-            // Publica un producto
-            let _ = contrato.publicar_producto(
-                "Producto Test".to_string(),
-                "Descripción Test".to_string(),
-                1000,
-                10,
-                "Test".to_string(),
-            );
+            assert_eq!(pujar_como(&mut contrato, cuentas.bob, 99), Err(SistemaError::PujaInsuficiente));
+            pujar_como(&mut contrato, cuentas.bob, 100).unwrap();
+            assert_eq!(pujar_como(&mut contrato, cuentas.charlie, 100), Err(SistemaError::PujaInsuficiente));
+            pujar_como(&mut contrato, cuentas.charlie, 150).unwrap();
+            assert_eq!(contrato.obtener_saldo_pendiente(cuentas.bob), 100);
 
-            // Cambia el caller a un usuario con rol Ambos
-            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
-            let carlos = cuentas.charlie;
-            test::set_caller::<ink::env::DefaultEnvironment>(carlos);
-            let _ = contrato.registrar_usuario(RolUsuario::Ambos);
+            // No se puede comprar a precio fijo ni finalizar antes de tiempo
+            assert_eq!(contrato.crear_orden(0, None, 1), Err(SistemaError::ProductoEnSubasta));
+            assert_eq!(contrato.finalizar_subasta(0), Err(SistemaError::SubastaEnCurso));
 
-            // Debería poder crear una orden exitosamente
-            let resultado = contrato.crear_orden(0, 1);
-            assert!(resultado.is_ok());
+            for _ in 0..3 {
+                test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            assert_eq!(pujar_como(&mut contrato, cuentas.bob, 500), Err(SistemaError::SubastaCerrada));
+            let orden_id = contrato.finalizar_subasta(0).unwrap().unwrap();
+            assert_eq!(contrato.finalizar_subasta(0), Err(SistemaError::SubastaCerrada));
+
+            let orden = contrato.ordenes.get(orden_id).unwrap();
+            assert_eq!((orden.comprador, orden.total, orden.deposito), (cuentas.charlie, 150, 150));
+            assert_eq!(contrato.productos.get(0).unwrap().cantidad, 0);
+
+            // Flujo normal: envío y recepción liberan el depósito al vendedor
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            contrato.marcar_orden_como_enviada(orden_id).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.charlie);
+            contrato.marcar_como_recibida(orden_id).unwrap();
+            assert_eq!(contrato.obtener_saldo_pendiente(vendedor), 150);
         }
 
         #[ink::test]
-        fn crear_orden_cantidad_insuficiente_falla() {
-            let mut contrato = setup_contract_con_vendedor();
+        fn subasta_cancelada_devuelve_deposito_al_ganador() {
+            let mut contrato = setup_subasta();
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            pujar_como(&mut contrato, cuentas.bob, 120).unwrap();
+            for _ in 0..3 {
+                test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            let orden_id = contrato.finalizar_subasta(0).unwrap().unwrap();
 
-            // Primero, publica un producto con cantidad insuficiente
-            let _ = contrato.publicar_producto(
-                "Smartwatch".to_string(),
-                "Un smartwatch elegante".to_string(),
-                500,
-                2, // Solo hay 2 disponibles
-                "Tecnología".to_string(),
-            );
+            contrato.solicitar_cancelacion_orden(orden_id).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            contrato.solicitar_cancelacion_orden(orden_id).unwrap();
 
-            // Cambia el caller a un usuario comprador y regístralo
+            assert_eq!(contrato.obtener_saldo_pendiente(cuentas.bob), 120);
+            assert_eq!(contrato.obtener_saldo_pendiente(AccountId::from([0x10; 32])), 0);
+        }
+
+        #[ink::test]
+        fn subasta_sin_pujas_cierra_sin_orden() {
+            let mut contrato = setup_subasta();
+            for _ in 0..3 {
+                test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            assert_eq!(contrato.finalizar_subasta(0), Ok(None));
+            assert_eq!(contrato.obtener_cantidad_ordenes(), 0);
+            assert!(contrato.obtener_subasta(0).unwrap().finalizada);
+        }
+
+        #[ink::test]
+        fn retirar_fondos_transfiere_saldo_pendiente() {
+            let mut contrato = setup_subasta();
             let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
-            let juan = cuentas.bob;
-            test::set_caller::<ink::env::DefaultEnvironment>(juan);
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+            pujar_como(&mut contrato, cuentas.bob, 100).unwrap();
+            pujar_como(&mut contrato, cuentas.charlie, 200).unwrap();
 
-            // Intenta crear una orden de compra de 3 unidades
-            let resultado = contrato.crear_orden(0, 3); // Compra 3 unidades
+            let contrato_id = test::callee::<ink::env::DefaultEnvironment>();
+            test::set_account_balance::<ink::env::DefaultEnvironment>(contrato_id, 1_000);
+            let antes = test::get_account_balance::<ink::env::DefaultEnvironment>(cuentas.bob).unwrap();
 
-            assert!(matches!(resultado, Err(SistemaError::StockInsuficiente)));
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.bob);
+            assert_eq!(contrato.retirar_fondos(), Ok(100));
+            assert_eq!(test::get_account_balance::<ink::env::DefaultEnvironment>(cuentas.bob).unwrap(), antes + 100);
+            assert_eq!(contrato.retirar_fondos(), Err(SistemaError::SinFondos));
         }
 
         #[ink::test]
-        fn crear_orden_cantidad_cero_falla() {
+        fn publicar_subasta_valida_duracion_y_vendedor_no_puja() {
+            let mut contrato = setup_subasta();
+            let vendedor = AccountId::from([0x10; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            assert_eq!(
+                contrato.publicar_subasta("X".to_string(), "D".to_string(), 10, 1, "C".to_string(), 0),
+                Err(SistemaError::DuracionInvalida)
+            );
+            // Vendedor con rol Vendedor no puede pujar
+            assert_eq!(pujar_como(&mut contrato, vendedor, 500), Err(SistemaError::NoEsRolCorrecto));
+        }
+
+        // --- Ofertas ---
+        /// Publica un producto de 100 con 5 unidades y registra a bob como comprador (queda como caller).
+        fn setup_oferta() -> Marketplace {
             let mut contrato = setup_contract_con_vendedor();
+            contrato.publicar_producto("P".to_string(), "D".to_string(), 100, 5, "C".to_string()).unwrap();
+            let bob = test::default_accounts::<ink::env::DefaultEnvironment>().bob;
+            test::set_caller::<ink::env::DefaultEnvironment>(bob);
+            contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
+            contrato
+        }
 
-            // Publica un producto
-            let _ = contrato.publicar_producto(
-                "Producto Test".to_string(),
-                "Descripción Test".to_string(),
-                1000,
-                10,
-                "Test".to_string(),
-            );
+        #[ink::test]
+        fn contraoferta_aceptada_crea_orden_al_precio_acordado() {
+            let mut contrato = setup_oferta();
+            let bob = test::default_accounts::<ink::env::DefaultEnvironment>().bob;
+            let vendedor = AccountId::from([0x10; 32]);
+            let oferta_id = contrato.ofertar(0, None, 70, 2).unwrap();
 
-            // Cambia el caller a un usuario comprador y regístralo
-            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
-            let juan = cuentas.bob;
-            test::set_caller::<ink::env::DefaultEnvironment>(juan);
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+            // No es el turno del comprador
+            assert_eq!(contrato.aceptar_oferta(oferta_id), Err(SistemaError::NoEsRolCorrecto));
 
-            // Intenta crear una orden con cantidad 0
-            let resultado = contrato.crear_orden(0, 0);
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            contrato.contraofertar(oferta_id, 85).unwrap();
+            assert_eq!(contrato.aceptar_oferta(oferta_id), Err(SistemaError::NoEsRolCorrecto));
 
-            assert!(matches!(resultado, Err(SistemaError::CantidadInsuficiente)));
+            test::set_caller::<ink::env::DefaultEnvironment>(bob);
+            let orden_id = contrato.aceptar_oferta(oferta_id).unwrap();
+
+            let orden = contrato.ordenes.get(orden_id).unwrap();
+            assert_eq!((orden.comprador, orden.cantidad, orden.total), (bob, 2, 170));
+            assert_eq!(contrato.productos.get(0).unwrap().cantidad, 3);
+            let oferta = contrato.obtener_oferta(oferta_id).unwrap();
+            assert_eq!((oferta.estado, oferta.orden_id), (EstadoOferta::Aceptada, Some(orden_id)));
+            assert_eq!(contrato.aceptar_oferta(oferta_id), Err(SistemaError::EstadoInvalido));
         }
 
         #[ink::test]
-        fn crear_orden_descuenta_stock() {
-            let mut contrato = setup_contract_con_vendedor();
-
-            // Primero, publica un producto con cantidad suficiente
-            let _ = contrato.publicar_producto(
-                "Auriculares".to_string(),
-                "Auriculares inalámbricos".to_string(),
-                800,
-                10, // 10 disponibles
-                "Tecnología".to_string(),
-            );
+        fn oferta_expira_y_se_puede_rechazar() {
+            let mut contrato = setup_oferta();
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().alice);
+            contrato.configurar_expiracion_ofertas(2).unwrap();
+
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().bob);
+            let vencida = contrato.ofertar(0, None, 50, 1).unwrap();
+            let rechazada = contrato.ofertar(0, None, 60, 1).unwrap();
+            for _ in 0..3 {
+                test::advance_block::<ink::env::DefaultEnvironment>();
+            }
 
-            // Cambia el caller a un usuario comprador y regístralo
-            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
-            let juan = cuentas.bob;
-            test::set_caller::<ink::env::DefaultEnvironment>(juan);
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            assert_eq!(contrato.aceptar_oferta(vencida), Err(SistemaError::OfertaExpirada));
+            contrato.rechazar_oferta(rechazada).unwrap();
+            assert_eq!(contrato.obtener_oferta(rechazada).unwrap().estado, EstadoOferta::Rechazada);
+            assert_eq!(contrato.rechazar_oferta(rechazada), Err(SistemaError::EstadoInvalido));
 
-            // Crea una orden de compra
-            let resultado = contrato.crear_orden(0, 3); // Compra 3 unidades
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().charlie);
+            assert_eq!(contrato.rechazar_oferta(vencida), Err(SistemaError::NoEsParteDeLaOferta));
+        }
 
-            assert!(resultado.is_ok());
+        #[ink::test]
+        fn ofertar_valida_stock_y_aceptar_revalida() {
+            let mut contrato = setup_oferta();
+            assert_eq!(contrato.ofertar(0, None, 50, 6), Err(SistemaError::StockInsuficiente));
+            assert_eq!(contrato.ofertar(0, None, 0, 1), Err(SistemaError::PrecioInvalido));
+            let oferta_id = contrato.ofertar(0, None, 50, 4).unwrap();
+            contrato.crear_orden(0, None, 3).unwrap();
+
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            assert_eq!(contrato.aceptar_oferta(oferta_id), Err(SistemaError::StockInsuficiente));
             assert_eq!(contrato.obtener_cantidad_ordenes(), 1);
-
-            // Verifica que el stock se haya descontado correctamente
-            let producto = contrato.productos.get(0).unwrap();
-            assert_eq!(producto.cantidad, 7); // Debería quedar 7 después de la compra
         }
 
-        
+        #[ink::test]
+        fn aceptar_oferta_aplica_validaciones_de_crear_orden() {
+            let mut contrato = setup_oferta();
+            let bob = test::default_accounts::<ink::env::DefaultEnvironment>().bob;
+            let vendedor = AccountId::from([0x10; 32]);
+            let oferta_id = contrato.ofertar(0, None, 70, 2).unwrap();
+
+            // El comprador deja de poder comprar antes de que el vendedor acepte
+            contrato.actualizar_permisos(Permisos { comprar: false, vender: true }).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            assert_eq!(contrato.aceptar_oferta(oferta_id), Err(SistemaError::NoEsRolCorrecto));
+
+            // El límite para vendedores sin verificar también rige para el precio acordado
+            test::set_caller::<ink::env::DefaultEnvironment>(bob);
+            contrato.actualizar_permisos(Permisos { comprar: true, vender: false }).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(contrato.administrador);
+            contrato.configurar_limite_orden_no_verificado(Some(100)).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            assert_eq!(contrato.aceptar_oferta(oferta_id), Err(SistemaError::VendedorNoVerificado));
+            assert_eq!(contrato.obtener_cantidad_ordenes(), 0);
+        }
 
         // --- Gestión de órdenes ---
         #[ink::test]
@@ -1353,7 +6096,7 @@
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             let _ = contrato.registrar_usuario(RolUsuario::Comprador);
 
-            let resultado = contrato.crear_orden(0, 1);
+            let resultado = contrato.crear_orden(0, None, 1);
             assert!(resultado.is_ok());
             let orden_id = resultado.unwrap();
 
@@ -1387,7 +6130,7 @@
             let _ = contrato.registrar_usuario(RolUsuario::Comprador);
 
             // Crea una orden
-            let resultado = contrato.crear_orden(0, 1);
+            let resultado = contrato.crear_orden(0, None, 1);
             assert!(resultado.is_ok());
             let orden_id = resultado.unwrap();
 
@@ -1419,7 +6162,7 @@
             let _ = contrato.registrar_usuario(RolUsuario::Comprador);
 
             // Crea una orden
-            let resultado = contrato.crear_orden(0, 1);
+            let resultado = contrato.crear_orden(0, None, 1);
             assert!(resultado.is_ok());
             let orden_id = resultado.unwrap();
 
@@ -1452,7 +6195,7 @@
             let _ = contrato.registrar_usuario(RolUsuario::Comprador);
 
             // Crea una orden
-            let resultado = contrato.crear_orden(0, 1);
+            let resultado = contrato.crear_orden(0, None, 1);
             assert!(resultado.is_ok());
             let orden_id = resultado.unwrap();
 
@@ -1464,7 +6207,7 @@
 
             // Intenta marcar la orden como enviada (no debería poder porque no es el vendedor de esta orden)
             let resultado = contrato.marcar_orden_como_enviada(orden_id);
-            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+            assert!(matches!(resultado, Err(SistemaError::NoEsParteDeLaOrden)));
         }
 
         #[ink::test]
@@ -1505,7 +6248,7 @@
             let _ = contrato.registrar_usuario(RolUsuario::Comprador);
 
             // Crea una orden
-            let resultado = contrato.crear_orden(0, 1);
+            let resultado = contrato.crear_orden(0, None, 1);
             assert!(resultado.is_ok());
             let orden_id = resultado.unwrap();
 
@@ -1549,7 +6292,7 @@
             let _ = contrato.registrar_usuario(RolUsuario::Comprador);
 
             // Crea una orden (estado inicial: Pendiente)
-            let resultado = contrato.crear_orden(0, 1);
+            let resultado = contrato.crear_orden(0, None, 1);
             assert!(resultado.is_ok());
             let orden_id = resultado.unwrap();
 
@@ -1580,7 +6323,7 @@
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             let _ = contrato.registrar_usuario(RolUsuario::Comprador);
 
-            let resultado = contrato.crear_orden(0, 1);
+            let resultado = contrato.crear_orden(0, None, 1);
             assert!(resultado.is_ok());
             let orden_id = resultado.unwrap();
 
@@ -1591,7 +6334,7 @@
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             let _ = contrato.marcar_como_recibida(orden_id);
 
-            let resultado = contrato.calificar_vendedor(orden_id, 5);
+            let resultado = calificar_solo(&mut contrato, orden_id, 5);
             assert!(resultado.is_ok());
 
             let vendor = contrato.obtener_usuario(vendedor).unwrap();
@@ -1615,7 +6358,7 @@
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             let _ = contrato.registrar_usuario(RolUsuario::Comprador);
 
-            let resultado = contrato.crear_orden(0, 1);
+            let resultado = contrato.crear_orden(0, None, 1);
             assert!(resultado.is_ok());
             let orden_id = resultado.unwrap();
 
@@ -1626,7 +6369,7 @@
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             let _ = contrato.marcar_como_recibida(orden_id);
 
-            let resultado = contrato.calificar_vendedor(orden_id, 6);
+            let resultado = calificar_solo(&mut contrato, orden_id, 6);
             assert!(matches!(resultado, Err(SistemaError::CalificacionInvalida)));
         }
 
@@ -1647,7 +6390,7 @@
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             let _ = contrato.registrar_usuario(RolUsuario::Comprador);
 
-            let resultado = contrato.crear_orden(0, 1);
+            let resultado = contrato.crear_orden(0, None, 1);
             assert!(resultado.is_ok());
             let orden_id = resultado.unwrap();
 
@@ -1658,8 +6401,8 @@
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             let _ = contrato.marcar_como_recibida(orden_id);
 
-            let _ = contrato.calificar_vendedor(orden_id, 5);
-            let resultado = contrato.calificar_vendedor(orden_id, 3);
+            let _ = calificar_solo(&mut contrato, orden_id, 5);
+            let resultado = calificar_solo(&mut contrato, orden_id, 3);
             assert!(matches!(resultado, Err(SistemaError::YaCalificado)));
         }
 
@@ -1680,7 +6423,7 @@
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             let _ = contrato.registrar_usuario(RolUsuario::Comprador);
 
-            let resultado = contrato.crear_orden(0, 1);
+            let resultado = contrato.crear_orden(0, None, 1);
             assert!(resultado.is_ok());
             let orden_id = resultado.unwrap();
 
@@ -1692,13 +6435,38 @@
             let _ = contrato.marcar_como_recibida(orden_id);
 
             test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
-            let resultado = contrato.calificar_comprador(orden_id, 4);
+            let resultado = calificar_solo(&mut contrato, orden_id, 4);
             assert!(resultado.is_ok());
 
             let comprador = contrato.obtener_usuario(juan).unwrap();
             assert_eq!(comprador.reputacion_como_comprador, 4);
         }
 
+        #[ink::test]
+        fn calificar_sin_ser_parte_o_sin_compromiso_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+            contrato.publicar_producto("P".to_string(), "D".to_string(), 100, 5, "C".to_string()).unwrap();
+
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let juan = cuentas.bob;
+            test::set_caller::<ink::env::DefaultEnvironment>(juan);
+            contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
+            let orden_id = contrato.crear_orden(0, None, 1).unwrap();
+
+            let vendedor = AccountId::from([0x10; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            contrato.marcar_orden_como_enviada(orden_id).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(juan);
+            contrato.marcar_como_recibida(orden_id).unwrap();
+
+            // No se puede revelar sin haberse comprometido
+            assert_eq!(revelar(&mut contrato, orden_id, 5), Err(SistemaError::CompromisoNoExiste));
+
+            // Un tercero no participa de la orden
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.charlie);
+            assert_eq!(comprometer(&mut contrato, orden_id, 5), Err(SistemaError::NoEsParteDeLaOrden));
+        }
+
         #[ink::test]
         fn calificar_comprador_dos_veces_falla() {
             let mut contrato = setup_contract_con_vendedor();
@@ -1716,7 +6484,7 @@
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             let _ = contrato.registrar_usuario(RolUsuario::Comprador);
 
-            let resultado = contrato.crear_orden(0, 1);
+            let resultado = contrato.crear_orden(0, None, 1);
             assert!(resultado.is_ok());
             let orden_id = resultado.unwrap();
 
@@ -1728,8 +6496,8 @@
             let _ = contrato.marcar_como_recibida(orden_id);
 
             test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
-            let _ = contrato.calificar_comprador(orden_id, 4);
-            let resultado = contrato.calificar_comprador(orden_id, 3);
+            let _ = calificar_solo(&mut contrato, orden_id, 4);
+            let resultado = calificar_solo(&mut contrato, orden_id, 3);
             assert!(matches!(resultado, Err(SistemaError::YaCalificado)));
         }
 
@@ -1751,7 +6519,7 @@
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             let _ = contrato.registrar_usuario(RolUsuario::Comprador);
 
-            let resultado = contrato.crear_orden(0, 1);
+            let resultado = contrato.crear_orden(0, None, 1);
             assert!(resultado.is_ok());
             let orden_id = resultado.unwrap();
 
@@ -1766,7 +6534,7 @@
             let _ = contrato.registrar_usuario(RolUsuario::Comprador);
 
             let resultado = contrato.marcar_como_recibida(orden_id);
-            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+            assert!(matches!(resultado, Err(SistemaError::NoEsParteDeLaOrden)));
         }
 
                 // --- Flujos completos de compra y calificación ---
@@ -1788,7 +6556,7 @@
             let juan = cuentas.bob;
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             let _ = contrato.registrar_usuario(RolUsuario::Comprador);
-            let orden_id = contrato.crear_orden(0, 1).unwrap();
+            let orden_id = contrato.crear_orden(0, None, 1).unwrap();
 
             // Vendedor marca como enviada
             let vendedor = AccountId::from([0x10; 32]);
@@ -1799,14 +6567,10 @@
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             let _ = contrato.marcar_como_recibida(orden_id);
 
-            // Comprador califica vendedor
-            let _ = contrato.calificar_vendedor(orden_id, 5);
+            // Ambos se califican
+            calificar_ambos(&mut contrato, orden_id, 5, 4);
             let vendor = contrato.obtener_usuario(vendedor).unwrap();
             assert_eq!(vendor.reputacion_como_vendedor, 5);
-
-            // Vendedor califica comprador
-            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
-            let _ = contrato.calificar_comprador(orden_id, 4);
             let comprador = contrato.obtener_usuario(juan).unwrap();
             assert_eq!(comprador.reputacion_como_comprador, 4);
         }
@@ -1829,19 +6593,19 @@
             let _ = contrato.registrar_usuario(RolUsuario::Comprador);
 
             // Primera orden: 3 unidades
-            let _ = contrato.crear_orden(0, 3);
+            let _ = contrato.crear_orden(0, None, 3);
             assert_eq!(contrato.obtener_cantidad_ordenes(), 1);
 
             // Segunda orden: 2 unidades
-            let _ = contrato.crear_orden(0, 2);
+            let _ = contrato.crear_orden(0, None, 2);
             assert_eq!(contrato.productos.get(0).unwrap().cantidad, 5);
 
             // Tercera orden: 5 unidades
-            let _ = contrato.crear_orden(0, 5);
+            let _ = contrato.crear_orden(0, None, 5);
             assert_eq!(contrato.productos.get(0).unwrap().cantidad, 0);
 
             // Cuarta orden debería fallar por stock insuficiente
-            let resultado = contrato.crear_orden(0, 1);
+            let resultado = contrato.crear_orden(0, None, 1);
             assert!(matches!(resultado, Err(SistemaError::StockInsuficiente)));
         }
 
@@ -1878,8 +6642,8 @@
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             let _ = contrato.registrar_usuario(RolUsuario::Comprador);
 
-            let orden1 = contrato.crear_orden(0, 1).unwrap();
-            let orden2 = contrato.crear_orden(1, 1).unwrap();
+            let orden1 = contrato.crear_orden(0, None, 1).unwrap();
+            let orden2 = contrato.crear_orden(1, None, 1).unwrap();
 
             // Completar ambas órdenes
             test::set_caller::<ink::env::DefaultEnvironment>(vendedor1);
@@ -1892,8 +6656,8 @@
             let _ = contrato.marcar_como_recibida(orden2);
 
             // Calificar ambos
-            let _ = contrato.calificar_vendedor(orden1, 5);
-            let _ = contrato.calificar_vendedor(orden2, 3);
+            let _ = calificar_solo(&mut contrato, orden1, 5);
+            let _ = calificar_solo(&mut contrato, orden2, 3);
 
             let v1 = contrato.obtener_usuario(vendedor1).unwrap();
             let v2 = contrato.obtener_usuario(vendedor2).unwrap();
@@ -1901,6 +6665,366 @@
             assert_eq!(v2.reputacion_como_vendedor, 3);
         }
 
+        #[ink::test]
+        fn reputacion_expone_promedio_y_cantidad() {
+            let (mut contrato, orden1) = setup_orden_recibida();
+            let bob = test::default_accounts::<ink::env::DefaultEnvironment>().bob;
+            let vendedor = AccountId::from([0x10; 32]);
+            let orden2 = contrato.crear_orden(0, None, 1).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            contrato.marcar_orden_como_enviada(orden2).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(bob);
+            contrato.marcar_como_recibida(orden2).unwrap();
+
+            calificar_ambos(&mut contrato, orden1, 5, 4);
+            calificar_solo(&mut contrato, orden2, 4).unwrap();
+
+            let rep_vendedor = contrato.obtener_reputacion(vendedor).unwrap();
+            assert_eq!((rep_vendedor.promedio_como_vendedor, rep_vendedor.calificaciones_como_vendedor), (450, 2));
+            assert_eq!((rep_vendedor.promedio_como_comprador, rep_vendedor.calificaciones_como_comprador), (0, 0));
+            let rep_bob = contrato.obtener_reputacion(bob).unwrap();
+            assert_eq!((rep_bob.promedio_como_comprador, rep_bob.calificaciones_como_comprador), (400, 1));
+            assert!(contrato.obtener_reputacion(AccountId::from([0x99; 32])).is_none());
+        }
+
+        #[ink::test]
+        fn verificacion_de_vendedor_y_limite_de_orden() {
+            let mut contrato = setup_contract_con_vendedor();
+            contrato.publicar_producto("P".to_string(), "D".to_string(), 100, 20, "C".to_string()).unwrap();
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let vendedor = AccountId::from([0x10; 32]);
+
+            assert_eq!(contrato.verificar_usuario(vendedor, 1, 1_000), Err(SistemaError::NoEsVerificador));
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.alice);
+            contrato.configurar_verificador(cuentas.eve, true).unwrap();
+            contrato.configurar_limite_orden_no_verificado(Some(300)).unwrap();
+
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.bob);
+            contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
+            contrato.crear_orden(0, None, 3).unwrap();
+            assert_eq!(contrato.crear_orden(0, None, 4), Err(SistemaError::VendedorNoVerificado));
+
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.eve);
+            assert_eq!(contrato.verificar_usuario(vendedor, 0, 1_000), Err(SistemaError::VerificacionInvalida));
+            contrato.verificar_usuario(vendedor, 2, 1_000).unwrap();
+            let verificacion = contrato.obtener_usuario(vendedor).unwrap().verificacion.unwrap();
+            assert_eq!((verificacion.nivel, verificacion.verificador), (2, cuentas.eve));
+
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.bob);
+            contrato.crear_orden(0, None, 4).unwrap();
+
+            // Vencida o revocada, vuelve a aplicar el límite
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000);
+            assert_eq!(contrato.crear_orden(0, None, 4), Err(SistemaError::VendedorNoVerificado));
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.eve);
+            contrato.revocar_verificacion(vendedor).unwrap();
+            assert!(contrato.obtener_usuario(vendedor).unwrap().verificacion.is_none());
+        }
+
+        #[ink::test]
+        fn limite_no_verificado_aplica_a_paquetes_y_pujas() {
+            let mut contrato = setup_paquete();
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            contrato.publicar_subasta("Cuadro".to_string(), "Óleo".to_string(), 100, 1, "Arte".to_string(), 3).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.alice);
+            contrato.configurar_limite_orden_no_verificado(Some(300)).unwrap();
+
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.bob);
+            assert_eq!(contrato.comprar_paquete(0, 2), Err(SistemaError::VendedorNoVerificado));
+            contrato.comprar_paquete(0, 1).unwrap();
+
+            test::set_value_transferred::<ink::env::DefaultEnvironment>(301);
+            assert_eq!(contrato.pujar(2), Err(SistemaError::VendedorNoVerificado));
+            test::set_value_transferred::<ink::env::DefaultEnvironment>(300);
+            contrato.pujar(2).unwrap();
+        }
+
+        #[ink::test]
+        fn actualizar_perfil_y_consultar_campos_publicos() {
+            let mut contrato = setup_contract_con_vendedor();
+            let vendedor = AccountId::from([0x10; 32]);
+            let perfil = PerfilUsuario {
+                nombre_visible: "  Tienda Sur ".to_string(),
+                avatar: Some(cid_v0(1)),
+                hash_contacto: Some([7; 32]),
+                region_envio: Some(*b"AR"),
+            };
+            contrato.actualizar_perfil(perfil.clone()).unwrap();
+
+            let guardado = contrato.obtener_usuario(vendedor).unwrap().perfil;
+            assert_eq!(guardado.nombre_visible, "Tienda Sur");
+            assert_eq!(guardado.hash_contacto, Some([7; 32]));
+            let publico = contrato.obtener_perfil_publico(vendedor).unwrap();
+            assert_eq!(publico.nombre_visible, "Tienda Sur");
+            assert_eq!((publico.avatar, publico.region_envio), (Some(cid_v0(1)), Some(*b"AR")));
+
+            let invalidos = [
+                PerfilUsuario { nombre_visible: "x".repeat(MAX_LARGO_NOMBRE_VISIBLE as usize + 1), ..perfil.clone() },
+                PerfilUsuario { avatar: Some(vec![1, 2, 3]), ..perfil.clone() },
+                PerfilUsuario { region_envio: Some(*b"ar"), ..perfil.clone() },
+            ];
+            for invalido in invalidos {
+                assert_eq!(contrato.actualizar_perfil(invalido), Err(SistemaError::PerfilInvalido));
+            }
+
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x99; 32]));
+            assert_eq!(contrato.actualizar_perfil(perfil), Err(SistemaError::UsuarioNoRegistrado));
+            assert!(contrato.obtener_perfil_publico(AccountId::from([0x99; 32])).is_none());
+        }
+
+        #[ink::test]
+        fn reputacion_ponderada_pesa_importe() {
+            let (mut contrato, orden_barata) = setup_orden_recibida();
+            let vendedor = AccountId::from([0x10; 32]);
+            let bob = test::default_accounts::<ink::env::DefaultEnvironment>().bob;
+            let orden_cara = contrato.crear_orden(0, None, 4).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            contrato.marcar_orden_como_enviada(orden_cara).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(bob);
+            contrato.marcar_como_recibida(orden_cara).unwrap();
+
+            calificar_solo(&mut contrato, orden_barata, 1).unwrap();
+            calificar_solo(&mut contrato, orden_cara, 5).unwrap();
+
+            let reputacion = contrato.obtener_reputacion(vendedor).unwrap();
+            assert_eq!(reputacion.promedio_como_vendedor, 300);
+            // (1 * 100 + 5 * 400) / 500
+            assert_eq!(reputacion.ponderada_como_vendedor, 420);
+            assert_eq!(reputacion.ponderada_como_comprador, 0);
+        }
+
+        #[ink::test]
+        fn reputacion_ponderada_decae_con_la_antiguedad() {
+            let mut reputacion = ReputacionPonderada::default();
+            reputacion.acumular(5, 100, 0);
+            assert_eq!(reputacion.puntaje(), 500);
+
+            // Tras una vida media la calificación vieja pesa la mitad que la nueva
+            reputacion.acumular(1, 100, VIDA_MEDIA_REPUTACION);
+            assert_eq!((reputacion.suma_ponderada, reputacion.peso_total), (350, 150));
+            assert_eq!(reputacion.puntaje(), 233);
+
+            // Media vida se aproxima por 3/4
+            let mut reputacion = ReputacionPonderada::default();
+            reputacion.acumular(4, 1_000, 0);
+            reputacion.acumular(4, 0, VIDA_MEDIA_REPUTACION / 2);
+            assert_eq!(reputacion.peso_total, 751);
+            assert_eq!(reputacion.puntaje(), 400);
+        }
+
+        #[ink::test]
+        fn reputacion_ponderada_acota_el_peso_de_ordenes_enormes() {
+            let mut reputacion = ReputacionPonderada::default();
+            for _ in 0..3 {
+                reputacion.acumular(1, PESO_MAXIMO_CALIFICACION, 0);
+            }
+            reputacion.acumular(5, Balance::MAX - 1, 0);
+            assert_eq!(reputacion.peso_total, 4 * PESO_MAXIMO_CALIFICACION);
+            assert_eq!(reputacion.puntaje(), 200);
+
+            // Sumas cerca del máximo de u128 no desbordan el cálculo
+            let saturada = ReputacionPonderada { suma_ponderada: u128::MAX, peso_total: 1 << 127, actualizado_en: 0 };
+            assert_eq!(saturada.puntaje(), 200);
+            let saturada = ReputacionPonderada { suma_ponderada: u128::MAX, peso_total: 1, actualizado_en: 0 };
+            assert_eq!(saturada.puntaje(), 500);
+        }
+
+        #[ink::test]
+        fn calificaciones_quedan_registradas_por_orden_y_usuario() {
+            let (mut contrato, orden_id) = setup_orden_recibida();
+            let bob = test::default_accounts::<ink::env::DefaultEnvironment>().bob;
+            let vendedor = AccountId::from([0x10; 32]);
+            assert!(contrato.calificacion_de_orden(orden_id).is_empty());
+
+            test::advance_block::<ink::env::DefaultEnvironment>();
+            calificar_ambos(&mut contrato, orden_id, 5, 3);
+
+            let registros = contrato.calificacion_de_orden(orden_id);
+            assert_eq!(registros.len(), 2);
+            assert_eq!(registros[0], Calificacion {
+                orden_id,
+                calificador: bob,
+                calificado: vendedor,
+                rol: RolCalificado::Vendedor,
+                puntaje: 5,
+                bloque: 1,
+                comentario: None,
+                respuesta: None,
+                comentario_oculto: false,
+                respuesta_oculta: false,
+            });
+            assert_eq!((registros[1].calificador, registros[1].puntaje), (vendedor, 3));
+
+            let recibidas = contrato.calificaciones_recibidas(vendedor, RolCalificado::Vendedor, 0, 10);
+            assert_eq!(recibidas, vec![registros[0].clone()]);
+            assert!(contrato.calificaciones_recibidas(vendedor, RolCalificado::Comprador, 0, 10).is_empty());
+            assert!(contrato.calificaciones_recibidas(vendedor, RolCalificado::Vendedor, 1, 10).is_empty());
+            assert_eq!(contrato.calificaciones_recibidas(bob, RolCalificado::Comprador, 0, 10), vec![registros[1].clone()]);
+        }
+
+        #[ink::test]
+        fn calificacion_ciega_no_se_revela_antes_que_la_contraparte() {
+            let (mut contrato, orden_id) = setup_orden_recibida();
+            let bob = test::default_accounts::<ink::env::DefaultEnvironment>().bob;
+            let vendedor = AccountId::from([0x10; 32]);
+
+            comprometer(&mut contrato, orden_id, 2).unwrap();
+            assert_eq!(revelar(&mut contrato, orden_id, 2), Err(SistemaError::RevelacionAnticipada));
+            assert_eq!(contrato.obtener_usuario(vendedor).unwrap().calificaciones_como_vendedor, 0);
+
+            // Con ambos compromisos cualquiera revela; los datos deben coincidir
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            comprometer(&mut contrato, orden_id, 4).unwrap();
+            assert_eq!(revelar(&mut contrato, orden_id, 5), Err(SistemaError::CompromisoInvalido));
+            assert_eq!(
+                contrato.revelar_calificacion(orden_id, 4, None, [0; 32]),
+                Err(SistemaError::CompromisoInvalido)
+            );
+            revelar(&mut contrato, orden_id, 4).unwrap();
+            assert_eq!(revelar(&mut contrato, orden_id, 4), Err(SistemaError::YaCalificado));
+            assert_eq!(contrato.obtener_usuario(bob).unwrap().reputacion_como_comprador, 4);
+
+            test::set_caller::<ink::env::DefaultEnvironment>(bob);
+            revelar(&mut contrato, orden_id, 2).unwrap();
+            assert_eq!(contrato.obtener_usuario(vendedor).unwrap().reputacion_como_vendedor, 2);
+        }
+
+        #[ink::test]
+        fn calificacion_ciega_cierra_plazo_si_la_contraparte_no_califica() {
+            let (mut contrato, orden_id) = setup_orden_recibida();
+            let vendedor = AccountId::from([0x10; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().alice);
+            contrato.configurar_plazo_compromiso(3).unwrap();
+            assert_eq!(contrato.configurar_plazo_compromiso(0), Err(SistemaError::ConfiguracionInvalida));
+
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            comprometer(&mut contrato, orden_id, 5).unwrap();
+            assert_eq!(contrato.obtener_compromisos_calificacion(orden_id).unwrap().cierre, 3);
+            for _ in 0..3 {
+                test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            assert_eq!(revelar(&mut contrato, orden_id, 5), Err(SistemaError::RevelacionAnticipada));
+            test::advance_block::<ink::env::DefaultEnvironment>();
+
+            // Cerrado el plazo, el comprador ya no puede comprometerse y el vendedor revela solo
+            test::set_caller::<ink::env::DefaultEnvironment>(test::default_accounts::<ink::env::DefaultEnvironment>().bob);
+            assert_eq!(comprometer(&mut contrato, orden_id, 1), Err(SistemaError::CompromisoCerrado));
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            revelar(&mut contrato, orden_id, 5).unwrap();
+            assert_eq!(contrato.calificacion_de_orden(orden_id).len(), 1);
+        }
+
+        #[ink::test]
+        fn calificar_fuera_de_plazo_falla() {
+            let (mut contrato, orden_id) = setup_orden_recibida();
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            assert_eq!(contrato.ordenes.get(orden_id).unwrap().recibida_en, Some(0));
+
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.alice);
+            contrato.configurar_plazo_calificacion(2).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.bob);
+            for _ in 0..3 {
+                test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            assert_eq!(comprometer(&mut contrato, orden_id, 5), Err(SistemaError::PlazoVencido));
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            assert_eq!(comprometer(&mut contrato, orden_id, 5), Err(SistemaError::PlazoVencido));
+            assert!(contrato.obtener_compromisos_calificacion(orden_id).is_none());
+        }
+
+        #[ink::test]
+        fn segundo_compromiso_rige_por_el_cierre_aunque_venza_el_plazo() {
+            let (mut contrato, orden_id) = setup_orden_recibida();
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let vendedor = AccountId::from([0x10; 32]);
+
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.alice);
+            contrato.configurar_plazo_calificacion(2).unwrap();
+            contrato.configurar_plazo_compromiso(3).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.bob);
+            test::advance_block::<ink::env::DefaultEnvironment>();
+            test::advance_block::<ink::env::DefaultEnvironment>();
+            comprometer(&mut contrato, orden_id, 5).unwrap();
+            assert_eq!(contrato.obtener_compromisos_calificacion(orden_id).unwrap().cierre, 5);
+
+            // Vencido el plazo desde la recepción (bloque 2) pero dentro del cierre
+            for _ in 0..3 {
+                test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            comprometer(&mut contrato, orden_id, 4).unwrap();
+            revelar(&mut contrato, orden_id, 4).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.bob);
+            revelar(&mut contrato, orden_id, 5).unwrap();
+            assert_eq!(contrato.calificacion_de_orden(orden_id).len(), 2);
+        }
+
+        #[ink::test]
+        fn plazo_de_orden_sin_bloque_de_recepcion_empieza_al_comprometer() {
+            let (mut contrato, orden_id) = setup_orden_recibida();
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut orden = contrato.ordenes.get(orden_id).unwrap();
+            orden.recibida_en = None;
+            contrato.ordenes.insert(orden_id, &orden);
+
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.alice);
+            contrato.configurar_plazo_calificacion(2).unwrap();
+            for _ in 0..5 {
+                test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.bob);
+            comprometer(&mut contrato, orden_id, 5).unwrap();
+            assert_eq!(contrato.ordenes.get(orden_id).unwrap().recibida_en, Some(5));
+        }
+
+        #[ink::test]
+        fn comentario_respuesta_y_moderacion_de_calificacion() {
+            let (mut contrato, orden_id) = setup_orden_recibida();
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let vendedor = AccountId::from([0x10; 32]);
+
+            let comentario = Some(" Nunca llegó la factura ".to_string());
+            contrato.comprometer_calificacion(orden_id, hash_calificacion(orden_id, cuentas.bob, 1, &comentario, &SAL_TEST)).unwrap();
+            cerrar_plazo_compromiso(&contrato);
+            contrato.revelar_calificacion(orden_id, 1, comentario, SAL_TEST).unwrap();
+            // Solo el calificado responde
+            assert_eq!(contrato.responder_calificacion(orden_id, "Hola".to_string()), Err(SistemaError::CalificacionNoExiste));
+
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            assert_eq!(contrato.responder_calificacion(orden_id, "   ".to_string()), Err(SistemaError::ComentarioInvalido));
+            contrato.responder_calificacion(orden_id, "Se envió por mail".to_string()).unwrap();
+            assert_eq!(contrato.responder_calificacion(orden_id, "Otra".to_string()), Err(SistemaError::YaRespondido));
+
+            let calificacion = contrato.calificacion_de_orden(orden_id)[0].clone();
+            assert_eq!(calificacion.comentario.as_deref(), Some("Nunca llegó la factura"));
+            assert_eq!(calificacion.respuesta.as_deref(), Some("Se envió por mail"));
+
+            // Moderación: el vendedor no modera, un moderador designado sí
+            assert_eq!(
+                contrato.ocultar_texto_calificacion(orden_id, RolCalificado::Vendedor, TextoCalificacion::Comentario),
+                Err(SistemaError::NoEsModerador)
+            );
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.alice);
+            contrato.configurar_moderador(cuentas.charlie, true).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.charlie);
+            contrato.ocultar_texto_calificacion(orden_id, RolCalificado::Vendedor, TextoCalificacion::Comentario).unwrap();
+            assert_eq!(
+                contrato.ocultar_texto_calificacion(orden_id, RolCalificado::Vendedor, TextoCalificacion::Comentario),
+                Err(SistemaError::CalificacionNoExiste)
+            );
+
+            let calificacion = contrato.calificacion_de_orden(orden_id)[0].clone();
+            assert_eq!((calificacion.comentario, calificacion.comentario_oculto), (None, true));
+            assert_eq!(calificacion.puntaje, 1);
+            assert_eq!(calificacion.respuesta.as_deref(), Some("Se envió por mail"));
+            assert_eq!(contrato.obtener_usuario(vendedor).unwrap().reputacion_como_vendedor, 1);
+
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.alice);
+            contrato.configurar_moderador(cuentas.charlie, false).unwrap();
+            assert!(!contrato.es_moderador(cuentas.charlie));
+        }
+
         #[ink::test]
         fn cambio_rol_ambos_a_comprador_puede_comprar() {
             let mut contrato = setup_contract_con_vendedor();
@@ -1922,12 +7046,12 @@
             let _ = contrato.modificar_rol_usuario(RolUsuario::Comprador);
 
             // Debe poder comprar (aunque sea Comprador)
-            let resultado = contrato.crear_orden(0, 1);
+            let resultado = contrato.crear_orden(0, None, 1);
             assert!(resultado.is_ok());
         }
 
         #[ink::test]
-        fn vendedor_no_puede_cambiar_a_ambos_directamente() {
+        fn vendedor_puede_cambiar_a_ambos_directamente() {
             let mut contrato = Marketplace::nuevo();
 
             let caller = AccountId::from([0x07; 32]);
@@ -1935,13 +7059,13 @@
 
             let _ = contrato.registrar_usuario(RolUsuario::Vendedor);
 
-            // Intenta cambiar de Vendedor a Ambos (no está permitido)
-            let resultado = contrato.modificar_rol_usuario(RolUsuario::Ambos);
-            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+            // Cambia de Vendedor a Ambos sin pasar por Comprador
+            assert_eq!(contrato.modificar_rol_usuario(RolUsuario::Ambos), Ok(()));
+            assert_eq!(contrato.usuarios.get(caller).unwrap().permisos, Permisos { comprar: true, vender: true });
         }
 
         #[ink::test]
-        fn comprador_no_puede_cambiar_a_ambos_directamente() {
+        fn comprador_puede_cambiar_a_ambos_directamente() {
             let mut contrato = Marketplace::nuevo();
 
             let caller = AccountId::from([0x08; 32]);
@@ -1949,9 +7073,72 @@
 
             let _ = contrato.registrar_usuario(RolUsuario::Comprador);
 
-            // Intenta cambiar de Comprador a Ambos (no está permitido)
-            let resultado = contrato.modificar_rol_usuario(RolUsuario::Ambos);
-            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+            // Cambia de Comprador a Ambos directamente
+            assert_eq!(contrato.modificar_rol_usuario(RolUsuario::Ambos), Ok(()));
+            assert_eq!(contrato.usuarios.get(caller).unwrap().permisos, Permisos { comprar: true, vender: true });
+        }
+
+        #[ink::test]
+        fn actualizar_permisos_habilita_y_deshabilita_capacidades() {
+            let mut contrato = setup_contract_con_vendedor();
+            let vendedor = AccountId::from([0x10; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+
+            // Habilita la compra sin perder la venta; emite `RolActualizado`
+            let eventos_antes = test::recorded_events().count();
+            assert_eq!(contrato.actualizar_permisos(Permisos { comprar: true, vender: true }), Ok(()));
+            assert_eq!(test::recorded_events().count() - eventos_antes, 1);
+            assert_eq!(contrato.usuarios.get(vendedor).unwrap().permisos.rol(), RolUsuario::Ambos);
+
+            // Deshabilita la venta: ya no puede publicar, pero sí comprar
+            assert_eq!(contrato.actualizar_permisos(Permisos { comprar: true, vender: false }), Ok(()));
+            assert_eq!(
+                contrato.publicar_producto("P".to_string(), "D".to_string(), 1, 1, "C".to_string()),
+                Err(SistemaError::NoEsRolCorrecto)
+            );
+            assert_eq!(contrato.obtener_perfil_publico(vendedor).unwrap().permisos.rol(), RolUsuario::Comprador);
+        }
+
+        #[ink::test]
+        fn actualizar_permisos_rechaza_vacios_y_repetidos() {
+            let mut contrato = setup_contract_con_vendedor();
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+
+            assert_eq!(
+                contrato.actualizar_permisos(Permisos { comprar: false, vender: false }),
+                Err(SistemaError::PermisosInvalidos)
+            );
+            assert_eq!(
+                contrato.actualizar_permisos(Permisos::from(RolUsuario::Vendedor)),
+                Err(SistemaError::NoEsRolCorrecto)
+            );
+        }
+
+        #[ink::test]
+        fn usuario_con_formato_anterior_se_lee_y_se_reescribe() {
+            let mut contrato = setup_contract_con_vendedor();
+            assert_eq!(Ok(CLAVE_USUARIOS_LEGACY), ink::primitives::KeyComposer::compute_key("Marketplace", "", "usuarios"));
+            assert_ne!(CLAVE_USUARIOS, CLAVE_USUARIOS_LEGACY);
+
+            // Bytes de un `Usuario` de la versión original: {direccion, rol, reputacion_como_comprador,
+            // reputacion_como_vendedor}, en la celda que le daba el campo `usuarios`
+            let cuenta = AccountId::from([0x21; 32]);
+            ink::env::set_contract_storage(&(CLAVE_USUARIOS_LEGACY, cuenta), &(cuenta, 0u8, 8u32, 3u32));
+            contrato.lista_usuarios_ids.push(cuenta);
+
+            assert!(contrato.esta_registrado(cuenta));
+            let leido = contrato.obtener_usuario(cuenta).unwrap();
+            assert_eq!(leido.permisos, Permisos::from(RolUsuario::Comprador));
+            assert_eq!((leido.reputacion_como_comprador, leido.calificaciones_como_comprador), (0, 0));
+            assert_eq!(leido.perfil, PerfilUsuario::default());
+            assert_eq!(contrato.obtener_todos_los_usuarios().len(), 1);
+
+            test::set_caller::<ink::env::DefaultEnvironment>(cuenta);
+            assert_eq!(contrato.registrar_usuario(RolUsuario::Vendedor), Err(SistemaError::UsuarioExistente));
+            contrato.actualizar_permisos(Permisos { comprar: true, vender: true }).unwrap();
+            assert!(contrato.usuarios_legacy.get(cuenta).is_none());
+            let migrado = contrato.usuarios.get(cuenta).unwrap();
+            assert_eq!((migrado.direccion, migrado.permisos.rol()), (cuenta, RolUsuario::Ambos));
         }
 
         #[ink::test]
@@ -2002,7 +7189,7 @@
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             let _ = contrato.registrar_usuario(RolUsuario::Comprador);
 
-            let orden_id = contrato.crear_orden(0, 1).unwrap();
+            let orden_id = contrato.crear_orden(0, None, 1).unwrap();
 
             let vendedor = AccountId::from([0x10; 32]);
             test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
@@ -2011,7 +7198,7 @@
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             let _ = contrato.marcar_como_recibida(orden_id);
 
-            let resultado = contrato.calificar_vendedor(orden_id, 0);
+            let resultado = calificar_solo(&mut contrato, orden_id, 0);
             assert!(matches!(resultado, Err(SistemaError::CalificacionInvalida)));
         }
 
@@ -2046,7 +7233,7 @@
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             let _ = contrato.registrar_usuario(RolUsuario::Comprador);
 
-            let resultado = contrato.calificar_vendedor(999, 5);
+            let resultado = comprometer(&mut contrato, 999, 5);
             assert!(matches!(resultado, Err(SistemaError::OrdenNoExiste)));
         }
 
@@ -2054,7 +7241,7 @@
         fn calificar_comprador_orden_no_existe() {
             let mut contrato = setup_contract_con_vendedor();
 
-            let resultado = contrato.calificar_comprador(999, 5);
+            let resultado = comprometer(&mut contrato, 999, 5);
             assert!(matches!(resultado, Err(SistemaError::OrdenNoExiste)));
         }
 
@@ -2097,7 +7284,7 @@
             let juan = cuentas.bob;
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
-            let orden_id = contrato.crear_orden(0, 2).unwrap();
+            let orden_id = contrato.crear_orden(0, None, 2).unwrap();
 
             // Stock debería quedar en 3
             assert_eq!(contrato.productos.get(0).unwrap().cantidad, 3);
@@ -2136,7 +7323,7 @@
             let juan = cuentas.bob;
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
-            let orden_id = contrato.crear_orden(0, 1).unwrap();
+            let orden_id = contrato.crear_orden(0, None, 1).unwrap();
 
             // Comprador solicita cancelación
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
@@ -2149,6 +7336,21 @@
             assert_eq!(contrato.productos.get(0).unwrap().cantidad, 3);
         }
 
+        #[ink::test]
+        fn solicitar_cancelacion_tercero_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+            contrato.publicar_producto("P".to_string(), "D".to_string(), 100, 5, "C".to_string()).unwrap();
+
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.bob);
+            contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
+            let orden_id = contrato.crear_orden(0, None, 1).unwrap();
+
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.charlie);
+            contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
+            assert_eq!(contrato.solicitar_cancelacion_orden(orden_id), Err(SistemaError::NoEsParteDeLaOrden));
+        }
+
         #[ink::test]
         fn listar_productos_por_vendedor_message_funciona() {
             let mut contrato = setup_contract_con_vendedor();
@@ -2184,7 +7386,7 @@
                 )
                 .unwrap();
             let final_len = test::recorded_events().collect::<Vec<_>>().len();
-            assert!(final_len >= inicial + 1);
+            assert!(final_len > inicial);
         }
 
         #[ink::test]
@@ -2199,7 +7401,7 @@
 
             // Verifica storage
             let u = contrato.obtener_usuario(maria).unwrap();
-            assert_eq!(u.rol, RolUsuario::Vendedor);
+            assert_eq!(u.permisos, Permisos::from(RolUsuario::Vendedor));
 
             // Verifica que se emitió evento
             let eventos = test::recorded_events().collect::<Vec<_>>();
@@ -2230,8 +7432,8 @@
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
             
-            contrato.crear_orden(0, 2).unwrap();
-            contrato.crear_orden(0, 1).unwrap();
+            contrato.crear_orden(0, None, 2).unwrap();
+            contrato.crear_orden(0, None, 1).unwrap();
             
             let ordenes = contrato.obtener_todas_las_ordenes();
             assert_eq!(ordenes.len(), 2);
@@ -2258,6 +7460,114 @@
             assert_eq!(usuarios.len(), 3);
         }
 
+        /// Charlie se registra como vendedor y publica los productos 0 y 1; bob se registra
+        /// como comprador. Deja a bob como caller.
+        fn setup_baja() -> Marketplace {
+            let mut contrato = Marketplace::nuevo();
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.charlie);
+            contrato.registrar_usuario(RolUsuario::Vendedor).unwrap();
+            contrato.publicar_producto("P".to_string(), "D".to_string(), 100, 5, "C".to_string()).unwrap();
+            contrato.publicar_producto("Q".to_string(), "D".to_string(), 100, 5, "C".to_string()).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.bob);
+            contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
+            contrato
+        }
+
+        #[ink::test]
+        fn darse_de_baja_espera_ordenes_abiertas_y_conserva_historial() {
+            let mut contrato = setup_baja();
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let vendedor = cuentas.charlie;
+            let orden_id = contrato.crear_orden(0, None, 1).unwrap();
+
+            // Con la orden pendiente ninguna de las partes puede irse
+            assert_eq!(contrato.darse_de_baja(), Err(SistemaError::OperacionesAbiertas));
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            assert_eq!(contrato.darse_de_baja(), Err(SistemaError::OperacionesAbiertas));
+            contrato.marcar_orden_como_enviada(orden_id).unwrap();
+            assert_eq!(contrato.darse_de_baja(), Err(SistemaError::OperacionesAbiertas));
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.bob);
+            contrato.marcar_como_recibida(orden_id).unwrap();
+
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            assert_eq!(contrato.darse_de_baja(), Ok(2));
+            assert!(contrato.cuenta_dada_de_baja(vendedor));
+            assert!(!contrato.productos.get(1).unwrap().activo);
+            assert!(!contrato.obtener_todos_los_usuarios().iter().any(|u| u.direccion == vendedor));
+            assert_eq!(contrato.obtener_todos_los_usuarios().len(), 1);
+            assert_eq!(contrato.ordenes.get(orden_id).unwrap().estado, EstadoOrden::Recibida);
+            assert!(contrato.obtener_perfil_publico(vendedor).is_some());
+
+            // La cuenta cerrada no puede volver a operar
+            assert_eq!(contrato.darse_de_baja(), Err(SistemaError::CuentaDadaDeBaja));
+            assert_eq!(contrato.republicar_producto(1), Err(SistemaError::CuentaDadaDeBaja));
+            assert_eq!(
+                contrato.publicar_producto("R".to_string(), "D".to_string(), 1, 1, "C".to_string()),
+                Err(SistemaError::CuentaDadaDeBaja)
+            );
+            assert_eq!(contrato.registrar_usuario(RolUsuario::Vendedor), Err(SistemaError::UsuarioExistente));
+        }
+
+        #[ink::test]
+        fn darse_de_baja_rechaza_ofertas_abiertas_y_corta_paquetes() {
+            let mut contrato = setup_baja();
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let oferta_id = contrato.ofertar(0, None, 80, 1).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.charlie);
+            contrato.contraofertar(oferta_id, 90).unwrap();
+            contrato.crear_paquete("Kit".to_string(), vec![(0, 1), (1, 1)], 150).unwrap();
+
+            let eventos_antes = test::recorded_events().count();
+            assert_eq!(contrato.darse_de_baja(), Ok(2));
+            // OfertaRechazada + UsuarioDadoDeBaja
+            assert_eq!(test::recorded_events().count(), eventos_antes + 2);
+            assert_eq!(contrato.obtener_oferta(oferta_id).unwrap().estado, EstadoOferta::Rechazada);
+
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.bob);
+            assert_eq!(contrato.aceptar_oferta(oferta_id), Err(SistemaError::EstadoInvalido));
+            assert_eq!(contrato.comprar_paquete(0, 1), Err(SistemaError::CuentaDadaDeBaja));
+            assert_eq!(contrato.ofertar(0, None, 80, 1), Err(SistemaError::CuentaDadaDeBaja));
+            assert_eq!(contrato.crear_orden(0, None, 1), Err(SistemaError::CuentaDadaDeBaja));
+        }
+
+        #[ink::test]
+        fn darse_de_baja_espera_subastas_del_vendedor_y_del_mejor_postor() {
+            let mut contrato = setup_subasta();
+            let cuentas = test::default_accounts::<ink::env::DefaultEnvironment>();
+            pujar_como(&mut contrato, cuentas.bob, 100).unwrap();
+            assert_eq!(contrato.darse_de_baja(), Err(SistemaError::OperacionesAbiertas));
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x10; 32]));
+            assert_eq!(contrato.darse_de_baja(), Err(SistemaError::OperacionesAbiertas));
+
+            // Superado, bob solo queda con la puja por retirar
+            pujar_como(&mut contrato, cuentas.charlie, 150).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.bob);
+            assert_eq!(contrato.darse_de_baja(), Err(SistemaError::SaldoPendiente));
+            test::set_caller::<ink::env::DefaultEnvironment>(cuentas.charlie);
+            assert_eq!(contrato.darse_de_baja(), Err(SistemaError::OperacionesAbiertas));
+
+            for _ in 0..3 {
+                test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            let orden_id = contrato.finalizar_subasta(0).unwrap().unwrap();
+            assert_eq!(contrato.subastas_liderando.get(cuentas.charlie), Some(0));
+            assert_eq!(contrato.darse_de_baja(), Err(SistemaError::OperacionesAbiertas));
+            assert_eq!(contrato.ordenes.get(orden_id).unwrap().comprador, cuentas.charlie);
+        }
+
+        #[ink::test]
+        fn darse_de_baja_rechaza_saldo_pendiente_y_no_registrados() {
+            let mut contrato = setup_baja();
+            let vendedor = test::default_accounts::<ink::env::DefaultEnvironment>().charlie;
+            contrato.saldos_pendientes.insert(vendedor, &5);
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            assert_eq!(contrato.darse_de_baja(), Err(SistemaError::SaldoPendiente));
+
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x33; 32]));
+            assert_eq!(contrato.darse_de_baja(), Err(SistemaError::UsuarioNoRegistrado));
+        }
+
         #[ink::test]
         fn esta_registrado_verifica_correctamente() {
             let mut contrato = Marketplace::nuevo();
@@ -2288,7 +7598,7 @@
             let usuario_opt = contrato.obtener_usuario(maria);
             assert!(usuario_opt.is_some());
             let usuario = usuario_opt.unwrap();
-            assert_eq!(usuario.rol, RolUsuario::Comprador);
+            assert_eq!(usuario.permisos, Permisos::from(RolUsuario::Comprador));
         }
 
         #[ink::test]
@@ -2301,7 +7611,7 @@
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
             
-            let resultado = contrato.crear_orden(0, 0);
+            let resultado = contrato.crear_orden(0, None, 0);
             assert!(resultado.is_err());
         }
 
@@ -2316,7 +7626,7 @@
             contrato.registrar_usuario(RolUsuario::Vendedor).unwrap();
             
             // Vendedor no puede comprar
-            let resultado = contrato.crear_orden(0, 1);
+            let resultado = contrato.crear_orden(0, None, 1);
             assert!(resultado.is_err());
         }
 
@@ -2328,7 +7638,7 @@
             let usuario_no_registrado = AccountId::from([0xEE; 32]);
             test::set_caller::<ink::env::DefaultEnvironment>(usuario_no_registrado);
             
-            let resultado = contrato.crear_orden(0, 1);
+            let resultado = contrato.crear_orden(0, None, 1);
             assert!(resultado.is_err());
         }
 
@@ -2365,7 +7675,7 @@
                 let usuario_no_registrado = AccountId::from([0xBB; 32]);
                 test::set_caller::<ink::env::DefaultEnvironment>(usuario_no_registrado);
                 
-                contrato.calificar_vendedor(0, 5)
+                comprometer(&mut contrato, 0, 5)
             };
             assert!(resultado_calificar.is_err());
         }
@@ -2379,7 +7689,7 @@
                 let usuario_no_registrado = AccountId::from([0xAA; 32]);
                 test::set_caller::<ink::env::DefaultEnvironment>(usuario_no_registrado);
                 
-                contrato.calificar_comprador(0, 5)
+                comprometer(&mut contrato, 0, 5)
             };
             assert!(resultado_calificar.is_err());
         }
@@ -2429,7 +7739,7 @@
             let juan = cuentas.bob;
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
-            contrato.crear_orden(0, 1).unwrap();
+            contrato.crear_orden(0, None, 1).unwrap();
             
             // Comprador intenta marcar como enviada (solo vendedor puede)
             let resultado = contrato.marcar_orden_como_enviada(0);
@@ -2460,7 +7770,7 @@
             let juan = cuentas.bob;
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
-            let orden_id = contrato.crear_orden(0, 1).unwrap();
+            let orden_id = contrato.crear_orden(0, None, 1).unwrap();
             
             // Orden en estado Pendiente no puede pasar a Recibida directamente
             let resultado = contrato.marcar_como_recibida(orden_id);
@@ -2481,7 +7791,7 @@
             assert!(resultado_pub.is_ok());
             
             // Puede crear orden (como comprador)
-            let resultado_orden = contrato.crear_orden(0, 1);
+            let resultado_orden = contrato.crear_orden(0, None, 1);
             assert!(resultado_orden.is_ok());
         }
 
@@ -2494,12 +7804,12 @@
             let juan = cuentas.bob;
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
-            let orden_id = contrato.crear_orden(0, 1).unwrap();
+            let orden_id = contrato.crear_orden(0, None, 1).unwrap();
             
             let maria = cuentas.alice;
             test::set_caller::<ink::env::DefaultEnvironment>(maria);
             // Orden en Pendiente no puede ser calificada todavía
-            let resultado = contrato.calificar_comprador(orden_id, 5);
+            let resultado = comprometer(&mut contrato, orden_id, 5);
             assert!(resultado.is_err());
         }
 
@@ -2524,7 +7834,7 @@
             contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
 
             // 4. Juan crea una orden
-            let orden_id = contrato.crear_orden(0, 2).unwrap();
+            let orden_id = contrato.crear_orden(0, None, 2).unwrap();
             assert_eq!(orden_id, 0);
 
             // 5. Maria marca la orden como enviada
@@ -2535,12 +7845,8 @@
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             contrato.marcar_como_recibida(orden_id).unwrap();
 
-            // 7. Juan califica a Maria (vendedor)
-            contrato.calificar_vendedor(orden_id, 5).unwrap();
-
-            // 8. Maria califica a Juan (comprador)
-            test::set_caller::<ink::env::DefaultEnvironment>(maria);
-            contrato.calificar_comprador(orden_id, 5).unwrap();
+            // 7. Juan y Maria se califican mutuamente
+            calificar_ambos(&mut contrato, orden_id, 5, 5);
 
             // 9. Verificar reputación
             let maria_user = contrato.obtener_usuario(maria).unwrap();
@@ -2565,7 +7871,7 @@
             // Setup: comprador y orden
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
-            let orden_id = contrato.crear_orden(0, 5).unwrap();
+            let orden_id = contrato.crear_orden(0, None, 5).unwrap();
 
             // Juan solicita cancelación
             contrato.solicitar_cancelacion_orden(orden_id).unwrap();
@@ -2602,14 +7908,14 @@
             contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
 
             for i in 0..3 {
-                let orden_id = contrato.crear_orden(i as u32, 2).unwrap();
+                let orden_id = contrato.crear_orden(i as u32, None, 2).unwrap();
                 
                 test::set_caller::<ink::env::DefaultEnvironment>(maria);
                 contrato.marcar_orden_como_enviada(orden_id).unwrap();
                 
                 test::set_caller::<ink::env::DefaultEnvironment>(juan);
                 contrato.marcar_como_recibida(orden_id).unwrap();
-                contrato.calificar_vendedor(orden_id, 5).unwrap();
+                calificar_solo(&mut contrato, orden_id, 5).unwrap();
             }
 
             // Verificar reputación de Maria después de 3 compras
@@ -2637,7 +7943,7 @@
             contrato.registrar_usuario(RolUsuario::Ambos).unwrap();
 
             // Juan compra de Maria (como comprador)
-            let orden_id_1 = contrato.crear_orden(0, 1).unwrap();
+            let orden_id_1 = contrato.crear_orden(0, None, 1).unwrap();
 
             // Flujo completo orden 1
             test::set_caller::<ink::env::DefaultEnvironment>(maria);
@@ -2645,17 +7951,14 @@
 
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             contrato.marcar_como_recibida(orden_id_1).unwrap();
-            contrato.calificar_vendedor(orden_id_1, 5).unwrap();
-
-            test::set_caller::<ink::env::DefaultEnvironment>(maria);
-            contrato.calificar_comprador(orden_id_1, 5).unwrap();
+            calificar_ambos(&mut contrato, orden_id_1, 5, 5);
 
             // Ahora Juan publica (como vendedor) e Maria compra (como comprador)
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             contrato.publicar_producto("Notebook".to_string(), "Java".to_string(), 45, 10, "Libros".to_string()).unwrap();
 
             test::set_caller::<ink::env::DefaultEnvironment>(maria);
-            let orden_id_2 = contrato.crear_orden(1, 1).unwrap();
+            let orden_id_2 = contrato.crear_orden(1, None, 1).unwrap();
 
             // Flujo completo orden 2
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
@@ -2663,10 +7966,7 @@
 
             test::set_caller::<ink::env::DefaultEnvironment>(maria);
             contrato.marcar_como_recibida(orden_id_2).unwrap();
-            contrato.calificar_vendedor(orden_id_2, 4).unwrap();
-
-            test::set_caller::<ink::env::DefaultEnvironment>(juan);
-            contrato.calificar_comprador(orden_id_2, 4).unwrap();
+            calificar_ambos(&mut contrato, orden_id_2, 4, 4);
 
             // Verificar reputación mutua
             let maria_user = contrato.obtener_usuario(maria).unwrap();
@@ -2695,17 +7995,14 @@
             contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
 
             // Carlos compra a Maria
-            let orden_id = contrato.crear_orden(0, 2).unwrap();
+            let orden_id = contrato.crear_orden(0, None, 2).unwrap();
 
             test::set_caller::<ink::env::DefaultEnvironment>(maria);
             contrato.marcar_orden_como_enviada(orden_id).unwrap();
 
             test::set_caller::<ink::env::DefaultEnvironment>(carlos);
             contrato.marcar_como_recibida(orden_id).unwrap();
-            contrato.calificar_vendedor(orden_id, 5).unwrap();
-
-            test::set_caller::<ink::env::DefaultEnvironment>(maria);
-            contrato.calificar_comprador(orden_id, 5).unwrap();
+            calificar_ambos(&mut contrato, orden_id, 5, 5);
 
             // Carlos cambia su rol de Comprador a Vendedor
             test::set_caller::<ink::env::DefaultEnvironment>(carlos);
@@ -2716,21 +8013,18 @@
 
             // Verificar cambio de rol
             let carlos_user = contrato.obtener_usuario(carlos).unwrap();
-            assert_eq!(carlos_user.rol, RolUsuario::Vendedor);
+            assert_eq!(carlos_user.permisos, Permisos::from(RolUsuario::Vendedor));
 
             // Maria compra de Carlos (Maria cambia a Comprador)
             test::set_caller::<ink::env::DefaultEnvironment>(maria);
-            let orden_id_2 = contrato.crear_orden(1, 1).unwrap();
+            let orden_id_2 = contrato.crear_orden(1, None, 1).unwrap();
 
             test::set_caller::<ink::env::DefaultEnvironment>(carlos);
             contrato.marcar_orden_como_enviada(orden_id_2).unwrap();
 
             test::set_caller::<ink::env::DefaultEnvironment>(maria);
             contrato.marcar_como_recibida(orden_id_2).unwrap();
-            contrato.calificar_vendedor(orden_id_2, 4).unwrap();
-
-            test::set_caller::<ink::env::DefaultEnvironment>(carlos);
-            contrato.calificar_comprador(orden_id_2, 5).unwrap();
+            calificar_ambos(&mut contrato, orden_id_2, 4, 5);
 
             // Verificar reputación final de Carlos
             let carlos_final = contrato.obtener_usuario(carlos).unwrap();
@@ -2752,11 +8046,11 @@
             contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
 
             // Juan intenta comprar más del stock disponible
-            let resultado = contrato.crear_orden(0, 10);
+            let resultado = contrato.crear_orden(0, None, 10);
             assert!(resultado.is_err());
 
             // Juan compra 3 (stock reduce a 2)
-            let _orden_id_1 = contrato.crear_orden(0, 3).unwrap();
+            let _orden_id_1 = contrato.crear_orden(0, None, 3).unwrap();
 
             // Verificar stock disponible
             let productos = contrato.obtener_todos_los_productos();
@@ -2764,14 +8058,14 @@
             assert_eq!(usb.cantidad, 2);
 
             // Juan crea otra orden con 2 (stock final 0)
-            let _orden_id_2 = contrato.crear_orden(0, 2).unwrap();
+            let _orden_id_2 = contrato.crear_orden(0, None, 2).unwrap();
 
             let productos_updated = contrato.obtener_todos_los_productos();
             let usb_updated = productos_updated.iter().find(|p| p.id == 0).unwrap();
             assert_eq!(usb_updated.cantidad, 0);
 
             // Juan intenta comprar más sin stock
-            let resultado_sin_stock = contrato.crear_orden(0, 1);
+            let resultado_sin_stock = contrato.crear_orden(0, None, 1);
             assert!(resultado_sin_stock.is_err());
         }
 
@@ -2791,18 +8085,18 @@
             // Juan comprador
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
-            let orden_juan = contrato.crear_orden(0, 10).unwrap();
+            let orden_juan = contrato.crear_orden(0, None, 10).unwrap();
 
             // Carlos comprador
             test::set_caller::<ink::env::DefaultEnvironment>(carlos);
             contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
-            let orden_carlos = contrato.crear_orden(0, 15).unwrap();
+            let orden_carlos = contrato.crear_orden(0, None, 15).unwrap();
 
             // Dave comprador
             let dave = AccountId::from([0x04; 32]);
             test::set_caller::<ink::env::DefaultEnvironment>(dave);
             contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
-            let orden_dave = contrato.crear_orden(0, 20).unwrap();
+            let orden_dave = contrato.crear_orden(0, None, 20).unwrap();
 
             // Maria procesa todos
             test::set_caller::<ink::env::DefaultEnvironment>(maria);
@@ -2813,21 +8107,17 @@
             // Todos marcan recibido y califican
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
             contrato.marcar_como_recibida(orden_juan).unwrap();
-            contrato.calificar_vendedor(orden_juan, 5).unwrap();
 
             test::set_caller::<ink::env::DefaultEnvironment>(carlos);
             contrato.marcar_como_recibida(orden_carlos).unwrap();
-            contrato.calificar_vendedor(orden_carlos, 5).unwrap();
 
             test::set_caller::<ink::env::DefaultEnvironment>(dave);
             contrato.marcar_como_recibida(orden_dave).unwrap();
-            contrato.calificar_vendedor(orden_dave, 4).unwrap();
 
-            // Maria califica a todos
-            test::set_caller::<ink::env::DefaultEnvironment>(maria);
-            contrato.calificar_comprador(orden_juan, 5).unwrap();
-            contrato.calificar_comprador(orden_carlos, 5).unwrap();
-            contrato.calificar_comprador(orden_dave, 5).unwrap();
+            // Maria y cada comprador se califican mutuamente
+            calificar_ambos(&mut contrato, orden_juan, 5, 5);
+            calificar_ambos(&mut contrato, orden_carlos, 5, 5);
+            calificar_ambos(&mut contrato, orden_dave, 4, 5);
 
             // Maria debe tener 14 puntos de reputación (5+5+4)
             let maria_user = contrato.obtener_usuario(maria).unwrap();
@@ -2870,13 +8160,13 @@
             // Juan intenta operaciones inválidas
             assert!(contrato.marcar_orden_como_enviada(999).is_err());
             assert!(contrato.marcar_como_recibida(999).is_err());
-            assert!(contrato.calificar_vendedor(999, 5).is_err());
+            assert!(comprometer(&mut contrato, 999, 5).is_err());
 
             // Juan crea orden válida
-            let orden_id = contrato.crear_orden(0, 2).unwrap();
+            let orden_id = contrato.crear_orden(0, None, 2).unwrap();
 
             // Juan intenta calificar vendedor antes de que sea enviada
-            assert!(contrato.calificar_vendedor(orden_id, 5).is_err());
+            assert!(comprometer(&mut contrato, orden_id, 5).is_err());
 
             // Maria envía
             test::set_caller::<ink::env::DefaultEnvironment>(maria);
@@ -2884,16 +8174,16 @@
 
             // Juan intenta calificar vendedor antes de que sea recibida
             test::set_caller::<ink::env::DefaultEnvironment>(juan);
-            assert!(contrato.calificar_vendedor(orden_id, 5).is_err());
+            assert!(comprometer(&mut contrato, orden_id, 5).is_err());
 
             // Juan marca recibido
             contrato.marcar_como_recibida(orden_id).unwrap();
 
             // Ahora sí puede calificar
-            assert!(contrato.calificar_vendedor(orden_id, 5).is_ok());
+            assert!(calificar_solo(&mut contrato, orden_id, 5).is_ok());
 
             // Juan intenta calificar dos veces
-            assert!(contrato.calificar_vendedor(orden_id, 3).is_err());
+            assert!(calificar_solo(&mut contrato, orden_id, 3).is_err());
         }
 
         #[ink::test]
@@ -2919,17 +8209,14 @@
             // 5 órdenes, todas con calificación 5
             for i in 0..5 {
                 test::set_caller::<ink::env::DefaultEnvironment>(juan);
-                let orden = contrato.crear_orden(i, 1).unwrap();
+                let orden = contrato.crear_orden(i, None, 1).unwrap();
 
                 test::set_caller::<ink::env::DefaultEnvironment>(maria);
                 contrato.marcar_orden_como_enviada(orden).unwrap();
 
                 test::set_caller::<ink::env::DefaultEnvironment>(juan);
                 contrato.marcar_como_recibida(orden).unwrap();
-                contrato.calificar_vendedor(orden, 5).unwrap();
-
-                test::set_caller::<ink::env::DefaultEnvironment>(maria);
-                contrato.calificar_comprador(orden, 5).unwrap();
+                calificar_ambos(&mut contrato, orden, 5, 5);
             }
 
             // Maria debe tener 25 en reputacion de vendedor y 0 en comprador
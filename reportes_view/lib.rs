@@ -22,15 +22,101 @@ use ink::prelude::string::String;
     Enviada,
     Recibida,
     Cancelada,
+    Preventa,
+    EnDisputa,
 }
 
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Permisos {
+    pub comprar: bool,
+    pub vender: bool,
+}
+
+    impl From<RolUsuario> for Permisos {
+        fn from(rol: RolUsuario) -> Self {
+            Self {
+                comprar: matches!(rol, RolUsuario::Comprador | RolUsuario::Ambos),
+                vender: matches!(rol, RolUsuario::Vendedor | RolUsuario::Ambos),
+            }
+        }
+    }
+
     #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
     pub struct Usuario {
     pub direccion: AccountId,
-    pub rol: RolUsuario,
+    pub permisos: Permisos,
     pub reputacion_como_comprador: u32,
     pub reputacion_como_vendedor: u32,
+    pub calificaciones_como_comprador: u32,
+    pub calificaciones_como_vendedor: u32,
+    pub perfil: PerfilUsuario,
+    pub verificacion: Option<Verificacion>,
+}
+
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Verificacion {
+    pub nivel: u8,
+    pub expira_en: u64, // Timestamp
+    pub verificador: AccountId,
+}
+
+    #[derive(Debug, Clone, Default, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct PerfilUsuario {
+    pub nombre_visible: String,
+    pub avatar: Option<Vec<u8>>, // Cid
+    pub hash_contacto: Option<[u8; 32]>,
+    pub region_envio: Option<[u8; 2]>,
+}
+
+    /// Mismo factor de punto fijo que `marketplace_principal::PRECISION_PROMEDIO`.
+    pub const PRECISION_PROMEDIO: u32 = 100;
+    /// Calificaciones mínimas para entrar en un ranking.
+    pub const MIN_CALIFICACIONES_RANKING: u32 = 3;
+
+    impl Usuario {
+        /// Promedio como comprador multiplicado por `PRECISION_PROMEDIO`.
+        pub fn promedio_como_comprador(&self) -> u32 {
+            promedio(self.reputacion_como_comprador, self.calificaciones_como_comprador)
+        }
+
+        /// Promedio como vendedor multiplicado por `PRECISION_PROMEDIO`.
+        pub fn promedio_como_vendedor(&self) -> u32 {
+            promedio(self.reputacion_como_vendedor, self.calificaciones_como_vendedor)
+        }
+    }
+
+    fn promedio(suma: u32, cantidad: u32) -> u32 {
+        if cantidad == 0 {
+            return 0;
+        }
+        (u64::from(suma) * u64::from(PRECISION_PROMEDIO) / u64::from(cantidad)) as u32
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Variante {
+    pub id: u32,
+    pub atributos: Vec<(String, String)>,
+    pub precio: Option<u128>, // Balance
+    pub cantidad: u32,
+}
+
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum TipoProducto {
+    Fisico,
+    Digital,
+}
+
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum ModoVenta {
+    PrecioFijo,
+    Subasta,
 }
 
     #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
@@ -43,6 +129,13 @@ use ink::prelude::string::String;
     pub cantidad: u32,
     pub categoria: String,
     pub vendedor: AccountId,
+    pub variantes: Vec<Variante>,
+    pub suma_puntajes_resenas: u32,
+    pub cantidad_resenas: u32,
+    pub modo_venta: ModoVenta,
+    pub activo: bool,
+    pub expira_en: u64, // Timestamp
+    pub tipo: TipoProducto,
 }
 
     #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
@@ -52,12 +145,27 @@ use ink::prelude::string::String;
     pub comprador: AccountId,
     pub vendedor: AccountId,
     pub producto_id: u32,
+    pub variante_id: Option<u32>,
     pub cantidad: u32,
+    pub total: u128, // Balance
     pub estado: EstadoOrden,
     pub comprador_califico: bool,
     pub vendedor_califico: bool,
     pub comprador_solicita_cancelacion: bool,
     pub vendedor_acepta_cancelacion: bool,
+    pub deposito: u128, // Balance
+    pub paquete_id: Option<u32>,
+    pub recibida_en: Option<u32>, // BlockNumber
+}
+
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Paquete {
+    pub id: u32,
+    pub vendedor: AccountId,
+    pub nombre: String,
+    pub componentes: Vec<(u32, u32)>,
+    pub precio: u128, // Balance
 }
 
     #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
@@ -83,6 +191,9 @@ pub trait MarketplaceTrait {
     
     #[ink(message)]
     fn obtener_todos_los_usuarios(&self) -> Vec<Usuario>;
+
+    #[ink(message)]
+    fn obtener_todos_los_paquetes(&self) -> Vec<Paquete>;
 }
 
 // -------------------------------------------------------------------------
@@ -114,8 +225,10 @@ mod reportes_view {
 
         /// Retorna los 5 vendedores con mayor reputación.
         ///
-        /// Filtra los usuarios que tienen rol `Vendedor` o `Ambos`, los ordena
-        /// por su `reputacion_como_vendedor` de mayor a menor y toma los primeros 5.
+        /// Filtra los usuarios con permiso de venta y al menos
+        /// `MIN_CALIFICACIONES_RANKING` calificaciones como vendedor, los ordena por
+        /// promedio (y cantidad de calificaciones, a igual promedio) de mayor a menor
+        /// y toma los primeros 5.
         ///
         /// # Retorno
         /// * `Vec<Usuario>`: Lista de hasta 5 usuarios.
@@ -126,17 +239,18 @@ mod reportes_view {
         }
 
         fn filtrar_top_5_vendedores(mut usuarios: Vec<Usuario>) -> Vec<Usuario> {
-            usuarios.sort_by(|a, b| b.reputacion_como_vendedor.cmp(&a.reputacion_como_vendedor));
+            usuarios.sort_by_key(|x| core::cmp::Reverse((x.promedio_como_vendedor(), x.calificaciones_como_vendedor)));
             usuarios.into_iter()
-                .filter(|u| matches!(u.rol, RolUsuario::Vendedor | RolUsuario::Ambos))
+                .filter(|u| u.permisos.vender)
+                .filter(|u| u.calificaciones_como_vendedor >= MIN_CALIFICACIONES_RANKING)
                 .take(5)
                 .collect()
         }
 
         /// Retorna los 5 compradores con mayor reputación.
         ///
-        /// Filtra los usuarios que tienen rol `Comprador` o `Ambos`, los ordena
-        /// por su `reputacion_como_comprador` de mayor a menor y toma los primeros 5.
+        /// Igual que `top_5_vendedores`, pero con permiso de compra y las
+        /// calificaciones recibidas como comprador.
         ///
         /// # Retorno
         /// * `Vec<Usuario>`: Lista de hasta 5 usuarios.
@@ -147,9 +261,10 @@ mod reportes_view {
         }
 
         fn filtrar_top_5_compradores(mut usuarios: Vec<Usuario>) -> Vec<Usuario> {
-            usuarios.sort_by(|a, b| b.reputacion_como_comprador.cmp(&a.reputacion_como_comprador));
+            usuarios.sort_by_key(|x| core::cmp::Reverse((x.promedio_como_comprador(), x.calificaciones_como_comprador)));
             usuarios.into_iter()
-                .filter(|u| matches!(u.rol, RolUsuario::Comprador | RolUsuario::Ambos))
+                .filter(|u| u.permisos.comprar)
+                .filter(|u| u.calificaciones_como_comprador >= MIN_CALIFICACIONES_RANKING)
                 .take(5)
                 .collect()
         }
@@ -157,27 +272,35 @@ mod reportes_view {
         /// Calcula los productos más vendidos (basado en órdenes recibidas).
         ///
         /// Itera sobre todas las órdenes con estado `Recibida`, suma las cantidades
-        /// por producto y devuelve el top 5 ordenado por volumen total de ventas.
+        /// por producto (las de paquetes, a cada componente) y devuelve el top 5
+        /// ordenado por volumen total de ventas.
         ///
         /// # Retorno
         /// * `Vec<ReporteProductoVendido>`: Lista de estructuras con nombre y total vendido.
         #[ink(message)]
         pub fn productos_mas_vendidos(&self) -> Vec<ReporteProductoVendido> {
-            let (productos, ordenes) = self.obtener_datos_productos();
-            Self::calcular_productos_mas_vendidos(productos, ordenes)
+            let (productos, ordenes, paquetes) = self.obtener_datos_productos();
+            Self::calcular_productos_mas_vendidos(productos, ordenes, paquetes)
         }
 
-        fn calcular_productos_mas_vendidos(productos: Vec<Producto>, ordenes: Vec<Orden>) -> Vec<ReporteProductoVendido> {
+        fn calcular_productos_mas_vendidos(productos: Vec<Producto>, ordenes: Vec<Orden>, paquetes: Vec<Paquete>) -> Vec<ReporteProductoVendido> {
             let mut reporte_map: ink::prelude::collections::BTreeMap<u32, (String, u32)> = ink::prelude::collections::BTreeMap::new();
 
             for orden in ordenes {
                 if matches!(orden.estado, EstadoOrden::Recibida) {
-                    for producto in &productos {
-                        if producto.id == orden.producto_id {
+                    // Un paquete suma a cada componente sus unidades por paquete
+                    let vendidos: Vec<(u32, u32)> = match orden.paquete_id {
+                        Some(paquete_id) => paquetes.iter()
+                            .find(|p| p.id == paquete_id)
+                            .map(|p| p.componentes.iter().map(|(id, unidades)| (*id, unidades.saturating_mul(orden.cantidad))).collect())
+                            .unwrap_or_default(),
+                        None => vec![(orden.producto_id, orden.cantidad)],
+                    };
+                    for (producto_id, cantidad) in vendidos {
+                        if let Some(producto) = productos.iter().find(|p| p.id == producto_id) {
                             reporte_map.entry(producto.id)
-                                .and_modify(|(_, cant)| *cant = cant.saturating_add(orden.cantidad))
-                                .or_insert((producto.nombre.clone(), orden.cantidad));
-                            break;
+                                .and_modify(|(_, cant)| *cant = cant.saturating_add(cantidad))
+                                .or_insert((producto.nombre.clone(), cantidad));
                         }
                     }
                 }
@@ -190,7 +313,7 @@ mod reportes_view {
                 })
                 .collect();
 
-            reportes.sort_by(|a, b| b.total_vendido.cmp(&a.total_vendido));
+            reportes.sort_by_key(|x| core::cmp::Reverse(x.total_vendido));
             reportes.truncate(5);
             reportes
         }
@@ -243,29 +366,41 @@ mod reportes_view {
             // Data mock
             let u1 = Usuario {
                 direccion: AccountId::from([0x90; 32]),
-                rol: RolUsuario::Vendedor,
+                permisos: Permisos::from(RolUsuario::Vendedor),
                 reputacion_como_comprador: 0,
                 reputacion_como_vendedor: 100,
+                calificaciones_como_comprador: 0,
+                calificaciones_como_vendedor: 20,
+                perfil: PerfilUsuario::default(),
+                verificacion: None,
             };
             let u2 = Usuario {
                 direccion: AccountId::from([0x91; 32]),
-                rol: RolUsuario::Comprador,
+                permisos: Permisos::from(RolUsuario::Comprador),
                 reputacion_como_comprador: 50,
                 reputacion_como_vendedor: 0,
+                calificaciones_como_comprador: 20,
+                calificaciones_como_vendedor: 0,
+                perfil: PerfilUsuario::default(),
+                verificacion: None,
             };
             vec![u1, u2]
         }
 
         /// Productos y datos (Producción).
         #[cfg(not(test))]
-        fn obtener_datos_productos(&self) -> (Vec<Producto>, Vec<Orden>) {
+        fn obtener_datos_productos(&self) -> (Vec<Producto>, Vec<Orden>, Vec<Paquete>) {
             let marketplace: ink::contract_ref!(MarketplaceTrait) = self.marketplace_contract.into();
-            (marketplace.obtener_todos_los_productos(), marketplace.obtener_todas_las_ordenes())
+            (
+                marketplace.obtener_todos_los_productos(),
+                marketplace.obtener_todas_las_ordenes(),
+                marketplace.obtener_todos_los_paquetes(),
+            )
         }
 
         /// Productos y datos (Mock).
         #[cfg(test)]
-        fn obtener_datos_productos(&self) -> (Vec<Producto>, Vec<Orden>) {
+        fn obtener_datos_productos(&self) -> (Vec<Producto>, Vec<Orden>, Vec<Paquete>) {
             let p1 = Producto {
                 id: 1,
                 nombre: "Producto Mock".into(),
@@ -274,20 +409,32 @@ mod reportes_view {
                 cantidad: 10,
                 categoria: "Cat".into(),
                 vendedor: AccountId::from([0x90; 32]),
+                variantes: Vec::new(),
+                suma_puntajes_resenas: 0,
+                cantidad_resenas: 0,
+                modo_venta: ModoVenta::PrecioFijo,
+                activo: true,
+                expira_en: u64::MAX,
+                tipo: TipoProducto::Fisico,
             };
             let o1 = Orden {
                 id: 1,
                 comprador: AccountId::from([0x91; 32]),
                 vendedor: AccountId::from([0x90; 32]),
                 producto_id: 1,
+                variante_id: None,
                 cantidad: 5,
+                total: 500,
                 estado: EstadoOrden::Recibida,
                 comprador_califico: false,
                 vendedor_califico: false,
                 comprador_solicita_cancelacion: false,
                 vendedor_acepta_cancelacion: false,
+                deposito: 0,
+                paquete_id: None,
+                recibida_en: None,
             };
-            (vec![p1], vec![o1])
+            (vec![p1], vec![o1], Vec::new())
         }
 
         /// Órdenes  (Producción).
@@ -305,12 +452,17 @@ mod reportes_view {
                 comprador: AccountId::from([0x91; 32]),
                 vendedor: AccountId::from([0x90; 32]),
                 producto_id: 1,
+                variante_id: None,
                 cantidad: 1,
+                total: 100,
                 estado: EstadoOrden::Pendiente,
                 comprador_califico: false,
                 vendedor_califico: false,
                 comprador_solicita_cancelacion: false,
                 vendedor_acepta_cancelacion: false,
+                deposito: 0,
+                paquete_id: None,
+                recibida_en: None,
             };
             vec![o1]
         }
@@ -344,13 +496,19 @@ mod reportes_view {
         fn test_estructura_usuario() {
             let usuario = Usuario {
                 direccion: AccountId::from([0x03; 32]),
-                rol: RolUsuario::Vendedor,
+                permisos: Permisos::from(RolUsuario::Vendedor),
                 reputacion_como_comprador: 5,
                 reputacion_como_vendedor: 10,
+                calificaciones_como_comprador: 1,
+                calificaciones_como_vendedor: 2,
+                perfil: PerfilUsuario::default(),
+                verificacion: None,
             };
 
             assert_eq!(usuario.reputacion_como_vendedor, 10);
             assert_eq!(usuario.reputacion_como_comprador, 5);
+            assert_eq!(usuario.promedio_como_comprador(), 500);
+            assert_eq!(usuario.promedio_como_vendedor(), 500);
         }
 
         #[test]
@@ -363,6 +521,13 @@ mod reportes_view {
                 cantidad: 5,
                 categoria: "Electrónica".into(),
                 vendedor: AccountId::from([0x04; 32]),
+                variantes: Vec::new(),
+                suma_puntajes_resenas: 0,
+                cantidad_resenas: 0,
+                modo_venta: ModoVenta::PrecioFijo,
+                activo: true,
+                expira_en: u64::MAX,
+                tipo: TipoProducto::Fisico,
             };
 
             assert_eq!(producto.id, 1);
@@ -377,12 +542,17 @@ mod reportes_view {
                 comprador: AccountId::from([0x05; 32]),
                 vendedor: AccountId::from([0x06; 32]),
                 producto_id: 1,
+                variante_id: None,
                 cantidad: 2,
+                total: 0,
                 estado: EstadoOrden::Recibida,
                 comprador_califico: true,
                 vendedor_califico: false,
                 comprador_solicita_cancelacion: false,
                 vendedor_acepta_cancelacion: false,
+                deposito: 0,
+                paquete_id: None,
+                recibida_en: None,
             };
 
             assert_eq!(orden.id, 1);
@@ -439,15 +609,27 @@ mod reportes_view {
                 cantidad: 10,
                 categoria: "Test".into(),
                 vendedor,
+                variantes: Vec::new(),
+                suma_puntajes_resenas: 0,
+                cantidad_resenas: 0,
+                modo_venta: ModoVenta::PrecioFijo,
+                activo: true,
+                expira_en: u64::MAX,
+                tipo: TipoProducto::Fisico,
             }
         }
 
         fn crear_usuario_test(dir: AccountId, rol: RolUsuario, rep_comprador: u32, rep_vendedor: u32) -> Usuario {
             Usuario {
                 direccion: dir,
-                rol,
+                permisos: Permisos::from(rol),
                 reputacion_como_comprador: rep_comprador,
                 reputacion_como_vendedor: rep_vendedor,
+                // Con 20 calificaciones el promedio conserva el orden de las sumas.
+                calificaciones_como_comprador: 20,
+                calificaciones_como_vendedor: 20,
+                perfil: PerfilUsuario::default(),
+                verificacion: None,
             }
         }
 
@@ -464,12 +646,17 @@ mod reportes_view {
                 comprador,
                 vendedor,
                 producto_id,
+                variante_id: None,
                 cantidad,
+                total: 0,
                 estado,
                 comprador_califico: false,
                 vendedor_califico: false,
                 comprador_solicita_cancelacion: false,
                 vendedor_acepta_cancelacion: false,
+                deposito: 0,
+                paquete_id: None,
+                recibida_en: None,
             }
         }
 
@@ -509,9 +696,9 @@ mod reportes_view {
             let top_5 = ReportesView::filtrar_top_5_vendedores(usuarios);
 
             assert_eq!(top_5.len(), 2);
-            assert!(top_5.iter().any(|u| u.rol == RolUsuario::Vendedor));
-            assert!(top_5.iter().any(|u| u.rol == RolUsuario::Ambos));
-            assert!(!top_5.iter().any(|u| u.rol == RolUsuario::Comprador));
+            assert!(top_5.iter().any(|u| u.permisos == Permisos::from(RolUsuario::Vendedor)));
+            assert!(top_5.iter().any(|u| u.permisos == Permisos::from(RolUsuario::Ambos)));
+            assert!(!top_5.iter().any(|u| u.permisos == Permisos::from(RolUsuario::Comprador)));
         }
 
         #[test]
@@ -551,6 +738,58 @@ mod reportes_view {
             assert_eq!(top_5[4].reputacion_como_vendedor, 80);
         }
 
+        #[test]
+        fn test_top_5_vendedores_ordena_por_promedio_no_por_suma() {
+            // 100 calificaciones de 1 estrella suman más que 10 de 5 estrellas
+            let mut muchas_malas = crear_usuario_test(AccountId::from([0x23; 32]), RolUsuario::Vendedor, 0, 100);
+            muchas_malas.calificaciones_como_vendedor = 100;
+            let mut pocas_buenas = crear_usuario_test(AccountId::from([0x24; 32]), RolUsuario::Vendedor, 0, 50);
+            pocas_buenas.calificaciones_como_vendedor = 10;
+
+            let top_5 = ReportesView::filtrar_top_5_vendedores(vec![muchas_malas, pocas_buenas]);
+
+            assert_eq!(top_5[0].direccion, AccountId::from([0x24; 32]));
+            assert_eq!(top_5[0].promedio_como_vendedor(), 500);
+            assert_eq!(top_5[1].promedio_como_vendedor(), 100);
+        }
+
+        #[test]
+        fn test_top_5_vendedores_incluye_verificacion() {
+            let mut verificado = crear_usuario_test(AccountId::from([0x27; 32]), RolUsuario::Vendedor, 0, 40);
+            verificado.verificacion = Some(Verificacion { nivel: 2, expira_en: 1_000, verificador: AccountId::from([0x01; 32]) });
+            let sin_verificar = crear_usuario_test(AccountId::from([0x28; 32]), RolUsuario::Vendedor, 0, 50);
+
+            let top_5 = ReportesView::filtrar_top_5_vendedores(vec![verificado, sin_verificar]);
+
+            assert!(top_5[0].verificacion.is_none());
+            assert_eq!(top_5[1].verificacion.as_ref().map(|v| v.nivel), Some(2));
+        }
+
+        #[test]
+        fn test_top_5_vendedores_excluye_pocas_calificaciones() {
+            let mut nuevo = crear_usuario_test(AccountId::from([0x25; 32]), RolUsuario::Vendedor, 0, 10);
+            nuevo.calificaciones_como_vendedor = MIN_CALIFICACIONES_RANKING - 1;
+            let mut habilitado = crear_usuario_test(AccountId::from([0x26; 32]), RolUsuario::Vendedor, 0, 12);
+            habilitado.calificaciones_como_vendedor = MIN_CALIFICACIONES_RANKING;
+
+            let top_5 = ReportesView::filtrar_top_5_vendedores(vec![nuevo, habilitado]);
+
+            assert_eq!(top_5.len(), 1);
+            assert_eq!(top_5[0].direccion, AccountId::from([0x26; 32]));
+        }
+
+        #[test]
+        fn test_top_5_vendedores_excluye_venta_deshabilitada() {
+            let mut deshabilitado = crear_usuario_test(AccountId::from([0x29; 32]), RolUsuario::Ambos, 0, 90);
+            deshabilitado.permisos.vender = false;
+            let activo = crear_usuario_test(AccountId::from([0x2A; 32]), RolUsuario::Vendedor, 0, 10);
+
+            let top_5 = ReportesView::filtrar_top_5_vendedores(vec![deshabilitado, activo]);
+
+            assert_eq!(top_5.len(), 1);
+            assert_eq!(top_5[0].direccion, AccountId::from([0x2A; 32]));
+        }
+
         // =====================================================================
         // TESTS PARA top_5_compradores - Similar a vendedores
         // =====================================================================
@@ -573,8 +812,8 @@ mod reportes_view {
             let usuarios = vec![usuario_comprador, usuario_vendedor];
             let top_1 = ReportesView::filtrar_top_5_compradores(usuarios);
 
-            assert!(top_1.iter().any(|u| u.rol == RolUsuario::Comprador));
-            assert!(!top_1.iter().any(|u| u.rol == RolUsuario::Vendedor));
+            assert!(top_1.iter().any(|u| u.permisos == Permisos::from(RolUsuario::Comprador)));
+            assert!(!top_1.iter().any(|u| u.permisos == Permisos::from(RolUsuario::Vendedor)));
         }
 
         #[test]
@@ -624,7 +863,7 @@ mod reportes_view {
                 crear_orden_test(2, AccountId::from([0x12; 32]), AccountId::from([0x10; 32]), 1, 3, EstadoOrden::Recibida),
             ];
 
-            let reportes = ReportesView::calcular_productos_mas_vendidos(productos, ordenes);
+            let reportes = ReportesView::calcular_productos_mas_vendidos(productos, ordenes, Vec::new());
 
             assert_eq!(reportes.len(), 1);
             assert_eq!(reportes[0].nombre_producto, "Laptop");
@@ -644,7 +883,7 @@ mod reportes_view {
                 crear_orden_test(3, AccountId::from([0x11; 32]), AccountId::from([0x10; 32]), 3, 30, EstadoOrden::Recibida),
             ];
 
-            let reportes = ReportesView::calcular_productos_mas_vendidos(productos, ordenes);
+            let reportes = ReportesView::calcular_productos_mas_vendidos(productos, ordenes, Vec::new());
 
             assert_eq!(reportes[0].nombre_producto, "Teclado");
             assert_eq!(reportes[0].total_vendido, 50);
@@ -662,18 +901,59 @@ mod reportes_view {
                 .map(|i| crear_orden_test(i, AccountId::from([0x11; 32]), AccountId::from([0x10; 32]), i, 100 - i * 5, EstadoOrden::Recibida))
                 .collect();
 
-            let reportes = ReportesView::calcular_productos_mas_vendidos(productos, ordenes);
+            let reportes = ReportesView::calcular_productos_mas_vendidos(productos, ordenes, Vec::new());
 
             assert_eq!(reportes.len(), 5);
             assert_eq!(reportes[0].total_vendido, 95);
         }
 
+        #[test]
+        fn test_productos_mas_vendidos_reparte_paquetes_en_componentes() {
+            let productos = vec![
+                crear_producto_test(1, "Mate", AccountId::from([0x10; 32])),
+                crear_producto_test(2, "Bombilla", AccountId::from([0x10; 32])),
+            ];
+            let mut orden = crear_orden_test(1, AccountId::from([0x11; 32]), AccountId::from([0x10; 32]), u32::MAX, 3, EstadoOrden::Recibida);
+            orden.paquete_id = Some(7);
+            let paquete = Paquete {
+                id: 7,
+                vendedor: AccountId::from([0x10; 32]),
+                nombre: "Kit".into(),
+                componentes: vec![(1, 2), (2, 1)],
+                precio: 200,
+            };
+
+            let reportes = ReportesView::calcular_productos_mas_vendidos(productos, vec![orden], vec![paquete]);
+
+            assert_eq!(reportes.len(), 2);
+            assert_eq!((reportes[0].nombre_producto.as_str(), reportes[0].total_vendido), ("Mate", 6));
+            assert_eq!((reportes[1].nombre_producto.as_str(), reportes[1].total_vendido), ("Bombilla", 3));
+        }
+
+        #[test]
+        fn test_productos_mas_vendidos_cuenta_productos_vencidos_y_despublicados() {
+            let mut vencido = crear_producto_test(1, "Agenda 2024", AccountId::from([0x10; 32]));
+            vencido.expira_en = 0;
+            let mut despublicado = crear_producto_test(2, "Calendario", AccountId::from([0x10; 32]));
+            despublicado.activo = false;
+            let ordenes = vec![
+                crear_orden_test(1, AccountId::from([0x11; 32]), AccountId::from([0x10; 32]), 1, 4, EstadoOrden::Recibida),
+                crear_orden_test(2, AccountId::from([0x11; 32]), AccountId::from([0x10; 32]), 2, 2, EstadoOrden::Recibida),
+            ];
+
+            let reportes = ReportesView::calcular_productos_mas_vendidos(vec![vencido, despublicado], ordenes, Vec::new());
+
+            assert_eq!(reportes.len(), 2);
+            assert_eq!((reportes[0].nombre_producto.as_str(), reportes[0].total_vendido), ("Agenda 2024", 4));
+            assert_eq!((reportes[1].nombre_producto.as_str(), reportes[1].total_vendido), ("Calendario", 2));
+        }
+
         #[test]
         fn test_productos_mas_vendidos_sin_ordenes() {
             let productos = vec![crear_producto_test(1, "Laptop", AccountId::from([0x10; 32]))];
             let ordenes = Vec::new();
 
-            let reportes = ReportesView::calcular_productos_mas_vendidos(productos, ordenes);
+            let reportes = ReportesView::calcular_productos_mas_vendidos(productos, ordenes, Vec::new());
 
             assert_eq!(reportes.len(), 0);
         }
@@ -754,7 +1034,7 @@ mod reportes_view {
             // El mock devuelve 1 vendedor (0x90) y 1 comprador (0x91)
             // Solo debe retornar el vendedor
             assert_eq!(vendedores.len(), 1);
-            assert_eq!(vendedores[0].rol, RolUsuario::Vendedor);
+            assert_eq!(vendedores[0].permisos, Permisos::from(RolUsuario::Vendedor));
         }
 
         #[test]
@@ -763,7 +1043,7 @@ mod reportes_view {
             let compradores = contract.top_5_compradores();
             // El mock devuelve 1 vendedor y 1 comprador
             assert_eq!(compradores.len(), 1);
-            assert_eq!(compradores[0].rol, RolUsuario::Comprador);
+            assert_eq!(compradores[0].permisos, Permisos::from(RolUsuario::Comprador));
         }
 
         #[test]
@@ -879,7 +1159,8 @@ mod e2e_tests {
             .await?
             .return_value();
         
-        assert!(top_vendedores.iter().any(|u| u.rol == RolUsuario::Vendedor));
+        // Maria recién se registró: sin MIN_CALIFICACIONES_RANKING calificaciones no entra al ranking.
+        assert!(top_vendedores.is_empty());
 
         // 5. Crear Orden
         // Maria publica producto
@@ -889,7 +1170,7 @@ mod e2e_tests {
                 ExecutionInput::new(Selector::new(ink::selector_bytes!("publicar_producto")))
                     .push_arg(String::from("Laptop"))
                     .push_arg(String::from("Laptop Gamer"))
-                    .push_arg(1000u32)
+                    .push_arg(1000u128)
                     .push_arg(10u32)
                     .push_arg(String::from("Electronica"))
             )
@@ -907,6 +1188,7 @@ mod e2e_tests {
             .exec_input(
                 ExecutionInput::new(Selector::new(ink::selector_bytes!("crear_orden")))
                     .push_arg(0u32) // id
+                    .push_arg(Option::<u32>::None) // variante
                     .push_arg(1u32) // cantidad
             )
             .returns::<Result<u32, marketplace_principal::SistemaError>>();